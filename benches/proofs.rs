@@ -0,0 +1,54 @@
+//! Proving 10 000 squarings naively and from checkpoints stored every k
+//! iterations, and verifying the proof
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use proof_of_latency::primality;
+use proof_of_latency::util;
+use proof_of_latency::vdf::evaluation::VDF;
+use proof_of_latency::vdf::proof::{ProofType, VDFProof};
+use proof_of_latency::vdf::ModulusChoice;
+
+const ITERATIONS: u32 = 10_000;
+
+/// A VDF evaluated to the end, with a cap to prove against
+fn evaluated(checkpoints: Option<usize>) -> VDF {
+    let modulus = ModulusChoice::Rsa2048;
+    let generator =
+        util::hash_with_domain(util::SEED_DOMAIN, b"bench", modulus.int());
+    let cap = primality::gen_safe_prime(128, &mut rand::thread_rng());
+    let mut vdf =
+        VDF::new(modulus, generator, ITERATIONS, ProofType::Sequential)
+            .unwrap();
+    if let Some(k) = checkpoints {
+        vdf = vdf.with_proof_checkpoints(k);
+    }
+    let mut vdf = vdf.with_cap(cap).unwrap();
+    vdf.evaluate();
+    vdf
+}
+
+fn proving(c: &mut Criterion) {
+    let mut group = c.benchmark_group("prove");
+    group.sample_size(10);
+    let naive = evaluated(None);
+    group.bench_function("naive", |b| {
+        b.iter(|| naive.clone().run_sync().unwrap())
+    });
+    for k in [4, 8, 10].iter() {
+        let vdf = evaluated(Some(*k));
+        group.bench_with_input(
+            BenchmarkId::new("checkpoints", k),
+            &vdf,
+            |b, vdf| b.iter(|| vdf.clone().run_sync().unwrap()),
+        );
+    }
+    group.finish();
+}
+
+fn verification(c: &mut Criterion) {
+    let proof: VDFProof = evaluated(Some(10)).run_sync().unwrap();
+    c.bench_function("verify", |b| b.iter(|| proof.verify()));
+}
+
+criterion_group!(benches, proving, verification);
+criterion_main!(benches);
@@ -0,0 +1,54 @@
+//! Throughput of verifying a batch of proofs one by one against
+//! verify_batch_parallel, on either side of PARALLEL_BATCH_THRESHOLD
+
+use criterion::{
+    criterion_group, criterion_main, BenchmarkId, Criterion, Throughput,
+};
+use proof_of_latency::primality;
+use proof_of_latency::util;
+use proof_of_latency::vdf::evaluation::VDF;
+use proof_of_latency::vdf::proof::{ProofType, VDFProof};
+use proof_of_latency::vdf::{self, ModulusChoice};
+
+/// Short proofs from distinct generators sharing one cap
+fn unrelated_proofs(count: usize) -> Vec<VDFProof> {
+    let modulus = ModulusChoice::Rsa2048;
+    let cap = primality::gen_safe_prime(64, &mut rand::thread_rng());
+    (0..count)
+        .map(|index| {
+            let generator = util::hash_with_domain(
+                util::SEED_DOMAIN,
+                &index.to_be_bytes(),
+                modulus.int(),
+            );
+            VDF::new(modulus.clone(), generator, 16, ProofType::Sequential)
+                .unwrap()
+                .with_cap(cap.clone())
+                .unwrap()
+                .run_sync()
+                .unwrap()
+        })
+        .collect()
+}
+
+fn verify_batch(c: &mut Criterion) {
+    let mut group = c.benchmark_group("verify_batch");
+    for count in [vdf::PARALLEL_BATCH_THRESHOLD / 2, 64, 256].iter() {
+        let proofs = unrelated_proofs(*count);
+        group.throughput(Throughput::Elements(*count as u64));
+        group.bench_with_input(
+            BenchmarkId::new("serial", count),
+            &proofs,
+            |b, proofs| b.iter(|| vdf::verify_batch(proofs)),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("parallel", count),
+            &proofs,
+            |b, proofs| b.iter(|| vdf::verify_batch_parallel(proofs)),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, verify_batch);
+criterion_main!(benches);
@@ -0,0 +1,23 @@
+fn main() {
+    #[cfg(feature = "capi")]
+    generate_header();
+    #[cfg(feature = "proto")]
+    prost_build::compile_protos(&["proto/proof.proto"], &["proto/"])
+        .expect("Couldn't compile the proof messages");
+    #[cfg(feature = "grpc")]
+    tonic_build::compile_protos("proto/pol.proto")
+        .expect("Couldn't compile the gRPC definitions");
+}
+
+/// Generates the C header for the ffi module into OUT_DIR. The checked-in
+/// include/pol.h is compared against it in CI.
+#[cfg(feature = "capi")]
+fn generate_header() {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    cbindgen::generate(&crate_dir)
+        .expect("Couldn't generate the C header")
+        .write_to_file(format!("{}/pol.h", out_dir));
+}
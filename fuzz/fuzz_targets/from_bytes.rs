@@ -0,0 +1,10 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use proof_of_latency::vdf::proof::VDFProof;
+
+fuzz_target!(|bytes: &[u8]| {
+    if let Ok(proof) = VDFProof::from_bytes(bytes) {
+        let _ = proof.fingerprint();
+        assert_eq!(VDFProof::from_bytes(&proof.to_bytes()), Ok(proof));
+    }
+});
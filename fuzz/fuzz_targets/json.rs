@@ -0,0 +1,11 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use proof_of_latency::vdf::proof::DeserializableVDFProof;
+
+fuzz_target!(|json: &[u8]| {
+    if let Ok(proof) = serde_json::from_slice::<DeserializableVDFProof>(json) {
+        if let Ok(proof) = proof.try_serialize() {
+            let _ = proof.to_bytes();
+        }
+    }
+});
@@ -0,0 +1,53 @@
+#ifndef POL_H
+#define POL_H
+
+/* Generated by cbindgen from src/ffi.rs, don't edit by hand */
+
+#include <stdint.h>
+#include <stddef.h>
+
+/**
+ * The pointer given was null
+ */
+#define POL_ERROR_NULL_POINTER -1
+
+/**
+ * The bytes weren't a proof in the canonical encoding
+ */
+#define POL_ERROR_PARSE -2
+
+/**
+ * Verification panicked
+ */
+#define POL_ERROR_PANIC -3
+
+/**
+ * Verifies a proof in the canonical byte encoding. Returns 1 if the proof is
+ * valid, 0 if it isn't, and a negative POL_ERROR code if it can't be read.
+ * The reason an invalid proof doesn't verify is kept as the last error.
+ *
+ * # Safety
+ *
+ * bytes must point to len readable bytes.
+ */
+int32_t pol_proof_verify(const uint8_t *bytes, size_t len);
+
+/**
+ * Returns the number of iterations a proof in the canonical byte encoding
+ * claims, or a negative POL_ERROR code if it can't be read. The proof is not
+ * verified.
+ *
+ * # Safety
+ *
+ * bytes must point to len readable bytes.
+ */
+int64_t pol_proof_iterations(const uint8_t *bytes, size_t len);
+
+/**
+ * Describes the last error on the calling thread, or returns null if there
+ * hasn't been one. The string is owned by the library and stays valid until
+ * the next call that fails on the same thread.
+ */
+const char *pol_last_error_message(void);
+
+#endif /* POL_H */
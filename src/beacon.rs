@@ -0,0 +1,227 @@
+//! VDF generators derived from randomness beacon rounds. A proof started
+//! from a beacon output can't have been computed before the round was
+//! published, which verifiers check against the fastest plausible squaring
+//! rate.
+//!
+//! The drand client trusts the HTTP relay it talks to, as it doesn't check
+//! the BLS signatures of the rounds.
+
+use crate::util;
+use ramp::Int;
+use serde::Deserialize;
+use std::error::Error;
+use std::fmt;
+
+/// BeaconError is returned when a beacon round can't be fetched or doesn't
+/// fit the proof
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BeaconError {
+    Unavailable(String),
+    Malformed(String),
+    /// The iterations take at least required seconds, but only elapsed
+    /// seconds passed since the round was published
+    TooManyIterations {
+        required: u64,
+        elapsed: u64,
+    },
+}
+
+impl fmt::Display for BeaconError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BeaconError::Unavailable(reason) => {
+                write!(f, "The beacon is unavailable: {}", reason)
+            }
+            BeaconError::Malformed(reason) => {
+                write!(f, "The beacon round is malformed: {}", reason)
+            }
+            BeaconError::TooManyIterations { required, elapsed } => write!(
+                f,
+                "The iterations take at least {} s, but the round was \
+                 published {} s before the proof",
+                required, elapsed
+            ),
+        }
+    }
+}
+
+impl Error for BeaconError {
+    fn description(&self) -> &str {
+        "Beacon error!"
+    }
+}
+
+/// A source of public randomness published in numbered rounds
+pub trait BeaconSource {
+    /// The number and the randomness of the latest published round
+    fn latest_round(&self) -> Result<(u64, [u8; 32]), BeaconError>;
+}
+
+/// Derives the VDF generator from the round number and its randomness
+pub fn derive_base(round: u64, randomness: &[u8; 32], modulus: &Int) -> Int {
+    let mut input = round.to_be_bytes().to_vec();
+    input.extend_from_slice(randomness);
+    util::hash_with_domain(util::BEACON_DOMAIN, &input, modulus)
+}
+
+/// Timing of a beacon's rounds, as served at /info by drand
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ChainInfo {
+    /// Seconds between rounds
+    pub period: u64,
+    /// Unix time of the first round
+    pub genesis_time: u64,
+}
+
+impl ChainInfo {
+    /// Unix time the round was published at
+    pub fn round_time(&self, round: u64) -> u64 {
+        self.genesis_time + round.saturating_sub(1) * self.period
+    }
+
+    /// Checks that the iterations could have been computed between the
+    /// publication of the round and finished_at, in unix seconds, at the
+    /// fastest plausible squaring rate
+    pub fn check_iterations(
+        &self,
+        round: u64,
+        iterations: u32,
+        max_squarings_per_second: u64,
+        finished_at: u64,
+    ) -> Result<(), BeaconError> {
+        let required = u64::from(iterations) / max_squarings_per_second.max(1);
+        let elapsed = finished_at.saturating_sub(self.round_time(round));
+        if required > elapsed {
+            return Err(BeaconError::TooManyIterations { required, elapsed });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct Round {
+    round: u64,
+    randomness: String,
+}
+
+fn randomness_from_hex(hex: &str) -> Result<[u8; 32], BeaconError> {
+    let malformed = || BeaconError::Malformed(format!("randomness {}", hex));
+    let bytes = util::hex_to_bytes(hex).map_err(|_| malformed())?;
+    if bytes.len() != 32 {
+        return Err(malformed());
+    }
+    let mut randomness = [0u8; 32];
+    randomness.copy_from_slice(&bytes);
+    Ok(randomness)
+}
+
+/// Reads rounds from a drand HTTP relay, such as https://api.drand.sh
+#[derive(Debug, Clone)]
+pub struct DrandHttp {
+    url: String,
+}
+
+impl DrandHttp {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    fn get<T: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+    ) -> Result<T, BeaconError> {
+        ureq::get(&format!("{}{}", self.url, path))
+            .call()
+            .map_err(|error| BeaconError::Unavailable(error.to_string()))?
+            .into_json()
+            .map_err(|error| BeaconError::Malformed(error.to_string()))
+    }
+
+    pub fn info(&self) -> Result<ChainInfo, BeaconError> {
+        self.get("/info")
+    }
+}
+
+impl BeaconSource for DrandHttp {
+    fn latest_round(&self) -> Result<(u64, [u8; 32]), BeaconError> {
+        let round: Round = self.get("/public/latest")?;
+        Ok((round.round, randomness_from_hex(&round.randomness)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vdf::evaluation::VDF;
+    use crate::vdf::proof::ProofType;
+    use crate::vdf::ModulusChoice;
+
+    struct FixedBeacon(u64, [u8; 32]);
+
+    impl BeaconSource for FixedBeacon {
+        fn latest_round(&self) -> Result<(u64, [u8; 32]), BeaconError> {
+            Ok((self.0, self.1))
+        }
+    }
+
+    const INFO: ChainInfo = ChainInfo {
+        period: 30,
+        genesis_time: 1_595_431_050,
+    };
+
+    #[test]
+    fn generators_come_from_the_round() {
+        let beacon = FixedBeacon(1000, [7; 32]);
+        let (round, randomness) = beacon.latest_round().unwrap();
+        let modulus = ModulusChoice::Rsa2048;
+        let vdf =
+            VDF::new(modulus.clone(), Int::from(2), 100, ProofType::Sequential)
+                .unwrap()
+                .with_beacon_seed(round, randomness);
+
+        let base = derive_base(1000, &[7; 32], modulus.int());
+        assert_eq!(vdf.generator, base);
+        assert_eq!(vdf.result.result, base);
+        assert_ne!(base, derive_base(1001, &[7; 32], modulus.int()));
+        assert_ne!(base, derive_base(1000, &[8; 32], modulus.int()));
+
+        let proof = vdf.run_sync().unwrap();
+        assert!(proof.verify());
+        assert_eq!(proof.generator, base);
+    }
+
+    #[test]
+    fn rejects_iterations_that_started_before_the_round() {
+        let published = INFO.round_time(1000);
+        assert_eq!(published, 1_595_431_050 + 999 * 30);
+
+        // A million squarings a second for a minute
+        assert_eq!(
+            INFO.check_iterations(1000, 60_000_000, 1_000_000, published + 60),
+            Ok(())
+        );
+        assert_eq!(
+            INFO.check_iterations(1000, 61_000_000, 1_000_000, published + 60),
+            Err(BeaconError::TooManyIterations {
+                required: 61,
+                elapsed: 60
+            })
+        );
+        assert!(INFO
+            .check_iterations(1000, 1, 1_000_000, published - 1)
+            .is_ok());
+        assert!(INFO
+            .check_iterations(1000, 2_000_000, 1_000_000, published - 1)
+            .is_err());
+    }
+
+    #[test]
+    fn parses_drand_randomness() {
+        let hex = "7".repeat(64);
+        assert_eq!(randomness_from_hex(&hex), Ok([0x77; 32]));
+        assert!(randomness_from_hex("77").is_err());
+        assert!(randomness_from_hex(&"z".repeat(64)).is_err());
+    }
+}
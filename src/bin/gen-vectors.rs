@@ -0,0 +1,165 @@
+//! Regenerates the golden test vectors in tests/vectors. The bases are
+//! hashed from the vector names and the caps are Mersenne primes, so every
+//! value can be derived again by other implementations.
+
+use clap::{App, Arg};
+use proof_of_latency::util;
+use proof_of_latency::vdf::evaluation::VDF;
+use proof_of_latency::vdf::proof::ProofType;
+use proof_of_latency::vdf::{ModulusChoice, ProofScheme};
+use ramp::Int;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+/// A parameter set. Wesolowski proofs are made against the cap 2^e - 1 for
+/// the cap exponent e, Pietrzak proofs have no cap.
+struct Params {
+    name: &'static str,
+    custom_modulus: bool,
+    iterations: u32,
+    cap_exponent: Option<usize>,
+}
+
+const PARAMS: [Params; 5] = [
+    Params {
+        name: "wesolowski-small",
+        custom_modulus: false,
+        iterations: 100,
+        cap_exponent: Some(61),
+    },
+    Params {
+        name: "wesolowski-medium",
+        custom_modulus: false,
+        iterations: 10_000,
+        cap_exponent: Some(127),
+    },
+    Params {
+        name: "wesolowski-custom-modulus",
+        custom_modulus: true,
+        iterations: 1000,
+        cap_exponent: Some(89),
+    },
+    Params {
+        name: "pietrzak-small",
+        custom_modulus: false,
+        iterations: 100,
+        cap_exponent: None,
+    },
+    Params {
+        name: "pietrzak-medium",
+        custom_modulus: false,
+        iterations: 10_001,
+        cap_exponent: None,
+    },
+];
+
+#[derive(Serialize)]
+struct Vector {
+    name: String,
+    scheme: ProofScheme,
+    backend: &'static str,
+    modulus: String,
+    base: String,
+    iterations: u32,
+    cap: Option<String>,
+    result: String,
+    proof: Vec<String>,
+}
+
+fn mersenne(exponent: usize) -> Int {
+    (Int::one() << exponent) - Int::one()
+}
+
+/// (2^521 - 1)(2^607 - 1), a custom modulus with known factors. Fine for
+/// test vectors, useless for anything else.
+fn custom_modulus() -> Int {
+    mersenne(521) * mersenne(607)
+}
+
+fn generate(params: &Params) -> Vector {
+    let (modulus, modulus_name) = if params.custom_modulus {
+        let modulus = custom_modulus();
+        let name = format!("0x{}", util::int_to_hex(&modulus));
+        (ModulusChoice::Custom(modulus), name)
+    } else {
+        (ModulusChoice::Rsa2048, "rsa-2048".to_string())
+    };
+    let base = util::hash_with_domain(
+        util::SEED_DOMAIN,
+        format!("vector/{}", params.name).as_bytes(),
+        modulus.int(),
+    );
+    let mut vdf = VDF::new(
+        modulus,
+        base.clone(),
+        params.iterations,
+        ProofType::Sequential,
+    )
+    .expect("The vector parameters are valid");
+    let (scheme, cap, result, proof) = match params.cap_exponent {
+        Some(exponent) => {
+            let cap = mersenne(exponent);
+            let proof = vdf
+                .with_cap(cap.clone())
+                .expect("Mersenne prime caps are prime")
+                .run_sync()
+                .expect("The proof is calculated");
+            (
+                ProofScheme::Wesolowski,
+                Some(cap),
+                proof.output.result.clone(),
+                vec![proof.pi],
+            )
+        }
+        None => {
+            let result = vdf.evaluate().result.clone();
+            let proof = vdf
+                .pietrzak_proof()
+                .expect("Every vector has iterations to prove");
+            (ProofScheme::Pietrzak, None, result, proof.midpoints)
+        }
+    };
+    Vector {
+        name: params.name.to_string(),
+        scheme,
+        backend: "ramp",
+        modulus: modulus_name,
+        base: util::int_to_hex(&base),
+        iterations: params.iterations,
+        cap: cap.as_ref().map(util::int_to_hex),
+        result: util::int_to_hex(&result),
+        proof: proof.iter().map(util::int_to_hex).collect(),
+    }
+}
+
+fn main() {
+    let matches = App::new("gen-vectors")
+        .about("Regenerates the golden test vectors")
+        .arg(
+            Arg::with_name("out")
+                .long("out")
+                .takes_value(true)
+                .default_value("tests/vectors")
+                .help("Directory to write the vectors to"),
+        )
+        .get_matches();
+
+    let out = Path::new(matches.value_of("out").unwrap());
+    if let Err(error) = fs::create_dir_all(out) {
+        eprintln!("Couldn't create {}: {}", out.display(), error);
+        process::exit(1);
+    }
+    for params in PARAMS.iter() {
+        let vector = generate(params);
+        let json = serde_json::to_string_pretty(&vector)
+            .expect("Vectors are always serializable");
+        let path = out.join(format!("{}.json", params.name));
+        if let Err(error) = fs::write(&path, json + "\n") {
+            eprintln!("Couldn't write {}: {}", path.display(), error);
+            process::exit(1);
+        }
+        println!("Wrote {}", path.display());
+    }
+}
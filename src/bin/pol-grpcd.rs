@@ -0,0 +1,61 @@
+use clap::{App, Arg};
+use proof_of_latency::grpc::{ProofService, ServiceConfig};
+use std::net::SocketAddr;
+use std::process;
+use tonic::transport::Server;
+
+fn parse<T: std::str::FromStr>(matches: &clap::ArgMatches, name: &str) -> T {
+    let value = matches.value_of(name).unwrap();
+    value.parse().unwrap_or_else(|_| {
+        eprintln!("Invalid --{}: {}", name, value);
+        process::exit(2);
+    })
+}
+
+#[tokio::main]
+async fn main() {
+    env_logger::init();
+
+    let defaults = ServiceConfig::default();
+    let max_concurrent_proofs = defaults.max_concurrent_proofs.to_string();
+    let max_request_bytes = defaults.max_request_bytes.to_string();
+    let matches = App::new("pol-grpcd")
+        .about("Proves and verifies proofs over gRPC")
+        .arg(
+            Arg::with_name("listen")
+                .long("listen")
+                .takes_value(true)
+                .default_value("127.0.0.1:50051")
+                .help("Address to serve on"),
+        )
+        .arg(
+            Arg::with_name("max-concurrent-proofs")
+                .long("max-concurrent-proofs")
+                .takes_value(true)
+                .default_value(&max_concurrent_proofs)
+                .help("Proofs calculated at once"),
+        )
+        .arg(
+            Arg::with_name("max-request-bytes")
+                .long("max-request-bytes")
+                .takes_value(true)
+                .default_value(&max_request_bytes)
+                .help("Largest proof accepted for verification"),
+        )
+        .get_matches();
+
+    let address: SocketAddr = parse(&matches, "listen");
+    let service = ProofService::new(ServiceConfig {
+        max_concurrent_proofs: parse(&matches, "max-concurrent-proofs"),
+        max_request_bytes: parse(&matches, "max-request-bytes"),
+    });
+
+    if let Err(error) = Server::builder()
+        .add_service(service.into_server())
+        .serve(address)
+        .await
+    {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,64 @@
+#[macro_use]
+extern crate log;
+
+use clap::{App, Arg};
+use proof_of_latency::http::{self, HttpConfig};
+use std::process;
+
+fn parse<T: std::str::FromStr>(matches: &clap::ArgMatches, name: &str) -> T {
+    let value = matches.value_of(name).unwrap();
+    value.parse().unwrap_or_else(|_| {
+        eprintln!("Invalid --{}: {}", name, value);
+        process::exit(2);
+    })
+}
+
+fn main() {
+    env_logger::init();
+
+    let max_body_bytes = HttpConfig::default().max_body_bytes.to_string();
+    let matches = App::new("pol-httpd")
+        .about("Verifies proofs over HTTP")
+        .arg(
+            Arg::with_name("listen")
+                .long("listen")
+                .takes_value(true)
+                .default_value("127.0.0.1:8080")
+                .help("Address to serve on"),
+        )
+        .arg(
+            Arg::with_name("max-body-bytes")
+                .long("max-body-bytes")
+                .takes_value(true)
+                .default_value(&max_body_bytes)
+                .help("Largest request body accepted"),
+        )
+        .arg(
+            Arg::with_name("calibration-ms")
+                .long("calibration-ms")
+                .takes_value(true)
+                .default_value("500")
+                .help("How long to measure the squaring rate for on start"),
+        )
+        .get_matches();
+
+    let ms: u64 = parse(&matches, "calibration-ms");
+    if ms == 0 {
+        eprintln!("--calibration-ms must be positive");
+        process::exit(2);
+    }
+    let rate = http::measure_squaring_rate(ms);
+    info!("Measured {} squarings per second", rate);
+
+    let app = http::app(
+        HttpConfig {
+            max_body_bytes: parse(&matches, "max-body-bytes"),
+        },
+        rate,
+    );
+    let listen = matches.value_of("listen").unwrap().to_string();
+    if let Err(error) = async_std::task::block_on(app.listen(listen)) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,191 @@
+use crate::util::HashAlg;
+use crate::vdf::proof::MIN_CAP_BITS;
+use crate::vdf::{InvalidModulusError, ModulusChoice};
+use serde::Deserialize;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Parameters for proving and verifying, loaded from a TOML file. Every field
+/// is optional in the file and falls back to its default.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub modulus: ModulusChoice,
+    pub upper_bound: u32,
+    pub cap_bits: usize,
+    /// Hash function the generator is derived from the seed with
+    pub hash: HashAlg,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            modulus: ModulusChoice::Rsa2048,
+            upper_bound: 150000,
+            cap_bits: 128,
+            hash: HashAlg::default(),
+        }
+    }
+}
+
+/// ConfigError is returned when a configuration can't be read or has invalid
+/// values
+#[derive(Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    Io(String),
+    Parse(String),
+    InvalidModulus(InvalidModulusError),
+    ZeroUpperBound,
+    CapBitsTooSmall(usize),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(reason) => {
+                write!(f, "Couldn't read the configuration: {}", reason)
+            }
+            ConfigError::Parse(reason) => {
+                write!(f, "Couldn't parse the configuration: {}", reason)
+            }
+            ConfigError::InvalidModulus(error) => write!(f, "{}", error),
+            ConfigError::ZeroUpperBound => {
+                write!(f, "upper_bound must be at least 1")
+            }
+            ConfigError::CapBitsTooSmall(bits) => {
+                write!(
+                    f,
+                    "cap_bits must be at least {}, got {}",
+                    MIN_CAP_BITS, bits
+                )
+            }
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn description(&self) -> &str {
+        "Invalid configuration!"
+    }
+}
+
+impl Config {
+    /// Parses and validates a configuration from TOML
+    pub fn from_toml(toml: &str) -> Result<Self, ConfigError> {
+        let config: Config = toml::from_str(toml)
+            .map_err(|error| ConfigError::Parse(error.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Reads, parses and validates a configuration file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let toml = fs::read_to_string(path).map_err(|error| {
+            ConfigError::Io(format!("{}: {}", path.display(), error))
+        })?;
+        Self::from_toml(&toml)
+    }
+
+    /// Checks that the values can be used for evaluating a VDF
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        self.modulus
+            .validate()
+            .map_err(ConfigError::InvalidModulus)?;
+        if self.upper_bound == 0 {
+            return Err(ConfigError::ZeroUpperBound);
+        }
+        if self.cap_bits < MIN_CAP_BITS as usize {
+            return Err(ConfigError::CapBitsTooSmall(self.cap_bits));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ramp::Int;
+
+    #[test]
+    fn loads_the_fixture() {
+        let config = Config::load(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/pol.toml"
+        ))
+        .unwrap();
+        assert_eq!(
+            config,
+            Config {
+                modulus: ModulusChoice::Rsa2048,
+                upper_bound: 300000,
+                cap_bits: 256,
+                hash: HashAlg::Shake256,
+            }
+        );
+    }
+
+    #[test]
+    fn empty_file_gives_defaults() {
+        assert_eq!(Config::from_toml("").unwrap(), Config::default());
+        assert_eq!(
+            Config::from_toml("cap_bits = 64").unwrap(),
+            Config {
+                cap_bits: 64,
+                ..Config::default()
+            }
+        );
+        assert_eq!(
+            Config::from_toml("hash = \"blake3\"").unwrap().hash,
+            HashAlg::Blake3
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        match Config::from_toml("modulus = \"rsa-1024\"") {
+            Err(ConfigError::Parse(reason)) => {
+                assert!(reason.contains("Unknown modulus \"rsa-1024\""))
+            }
+            other => panic!("Expected a parse error, got {:?}", other),
+        }
+        // 2^1279 - 1 is prime, so it can't be an RSA modulus
+        match Config::from_toml(&format!(
+            "modulus = \"{}\"",
+            ((Int::from(1) << 1279) - Int::from(1)).to_str_radix(10, false)
+        )) {
+            Err(ConfigError::Parse(reason)) => {
+                assert!(reason.contains("The modulus is prime"))
+            }
+            other => panic!("Expected a parse error, got {:?}", other),
+        }
+        match Config::from_toml("hash = \"md5\"") {
+            Err(ConfigError::Parse(reason)) => {
+                assert!(reason.contains("Unknown hash function \"md5\""))
+            }
+            other => panic!("Expected a parse error, got {:?}", other),
+        }
+        assert_eq!(
+            Config::from_toml("upper_bound = 0"),
+            Err(ConfigError::ZeroUpperBound)
+        );
+        assert_eq!(
+            Config::from_toml("cap_bits = 2"),
+            Err(ConfigError::CapBitsTooSmall(2))
+        );
+        assert!(matches!(
+            Config::from_toml("upper_bound = \"many\""),
+            Err(ConfigError::Parse(_))
+        ));
+        assert!(matches!(
+            Config::from_toml("iterations = 5"),
+            Err(ConfigError::Parse(_))
+        ));
+        assert!(matches!(
+            Config::load("does/not/exist.toml"),
+            Err(ConfigError::Io(_))
+        ));
+    }
+}
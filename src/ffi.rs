@@ -0,0 +1,183 @@
+//! C interface for verifying proofs in the canonical byte encoding. No panic
+//! crosses the boundary: every function catches them and reports
+//! POL_ERROR_PANIC instead.
+
+use crate::vdf::proof::VDFProof;
+use std::cell::RefCell;
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+/// The pointer given was null
+pub const POL_ERROR_NULL_POINTER: i32 = -1;
+
+/// The bytes weren't a proof in the canonical encoding
+pub const POL_ERROR_PARSE: i32 = -2;
+
+/// Verification panicked
+pub const POL_ERROR_PANIC: i32 = -3;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = RefCell::new(None);
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', ""))
+        .expect("Nul bytes were removed");
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Decodes the proof from the buffer, recording the reason on failure
+unsafe fn decode(bytes: *const u8, len: usize) -> Result<VDFProof, i32> {
+    if bytes.is_null() {
+        set_last_error("The proof pointer is null".to_string());
+        return Err(POL_ERROR_NULL_POINTER);
+    }
+    VDFProof::from_bytes(slice::from_raw_parts(bytes, len)).map_err(|error| {
+        set_last_error(error.to_string());
+        POL_ERROR_PARSE
+    })
+}
+
+/// Runs f, turning a panic into POL_ERROR_PANIC
+fn guard<T: From<i32>, F: FnOnce() -> T>(f: F) -> T {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|_| {
+        set_last_error("Panicked while handling the proof".to_string());
+        T::from(POL_ERROR_PANIC)
+    })
+}
+
+/// Verifies a proof in the canonical byte encoding. Returns 1 if the proof is
+/// valid, 0 if it isn't, and a negative POL_ERROR code if it can't be read.
+/// The reason an invalid proof doesn't verify is kept as the last error.
+///
+/// # Safety
+///
+/// bytes must point to len readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn pol_proof_verify(bytes: *const u8, len: usize) -> i32 {
+    guard(|| match decode(bytes, len) {
+        Ok(proof) => match proof.check() {
+            Ok(()) => 1,
+            Err(error) => {
+                set_last_error(error.to_string());
+                0
+            }
+        },
+        Err(code) => code,
+    })
+}
+
+/// Returns the number of iterations a proof in the canonical byte encoding
+/// claims, or a negative POL_ERROR code if it can't be read. The proof is not
+/// verified.
+///
+/// # Safety
+///
+/// bytes must point to len readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn pol_proof_iterations(
+    bytes: *const u8,
+    len: usize,
+) -> i64 {
+    guard(|| match decode(bytes, len) {
+        Ok(proof) => i64::from(proof.output.iterations),
+        Err(code) => i64::from(code),
+    })
+}
+
+/// Describes the last error on the calling thread, or returns null if there
+/// hasn't been one. The string is owned by the library and stays valid until
+/// the next call that fails on the same thread.
+#[no_mangle]
+pub extern "C" fn pol_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primality;
+    use crate::util;
+    use crate::vdf::evaluation::VDF;
+    use crate::vdf::proof::ProofType;
+    use crate::vdf::ModulusChoice;
+    use ramp::Int;
+    use std::ffi::CStr;
+
+    fn proof_bytes() -> Vec<u8> {
+        let modulus = ModulusChoice::Rsa2048;
+        let generator =
+            util::hash_with_domain(util::SEED_DOMAIN, b"ffi", modulus.int());
+        let (_, receiver) =
+            VDF::new(modulus, generator, 100, ProofType::Sequential)
+                .unwrap()
+                .with_cap(primality::gen_safe_prime(
+                    64,
+                    &mut rand::thread_rng(),
+                ))
+                .unwrap()
+                .run_vdf_worker();
+        receiver.recv().unwrap().unwrap().to_bytes()
+    }
+
+    fn last_error() -> String {
+        let message = pol_last_error_message();
+        assert!(!message.is_null());
+        unsafe { CStr::from_ptr(message) }
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn verifies_a_valid_proof() {
+        let bytes = proof_bytes();
+        unsafe {
+            assert_eq!(pol_proof_verify(bytes.as_ptr(), bytes.len()), 1);
+            assert_eq!(pol_proof_iterations(bytes.as_ptr(), bytes.len()), 100);
+        }
+    }
+
+    #[test]
+    fn rejects_a_corrupted_proof() {
+        let mut proof = VDFProof::from_bytes(&proof_bytes()).unwrap();
+        proof.pi = &proof.pi + Int::from(1);
+        let bytes = proof.to_bytes();
+        unsafe {
+            assert_eq!(pol_proof_verify(bytes.as_ptr(), bytes.len()), 0);
+            assert_eq!(
+                last_error(),
+                "The proof doesn't satisfy the verification equation"
+            );
+        }
+    }
+
+    #[test]
+    fn reports_unreadable_buffers() {
+        let bytes = proof_bytes();
+        unsafe {
+            assert_eq!(
+                pol_proof_verify(bytes.as_ptr(), bytes.len() - 1),
+                POL_ERROR_PARSE
+            );
+            assert!(last_error().contains("ends abruptly"));
+
+            assert_eq!(
+                pol_proof_iterations(bytes.as_ptr(), 3),
+                i64::from(POL_ERROR_PARSE)
+            );
+            assert_eq!(
+                pol_proof_verify(ptr::null(), bytes.len()),
+                POL_ERROR_NULL_POINTER
+            );
+            assert_eq!(last_error(), "The proof pointer is null");
+        }
+    }
+}
@@ -0,0 +1,303 @@
+//! gRPC service for proving and verifying proofs in the canonical byte
+//! encoding, served by pol-grpcd
+
+use crate::util;
+use crate::vdf::evaluation::VDF;
+use crate::vdf::proof::{ProofType, VDFProof};
+use crate::vdf::{ModulusChoice, VdfError, VerifyError};
+use bytes::Buf;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::sync::Semaphore;
+use tonic::codegen::{http, Body, Service, StdError};
+use tonic::transport::NamedService;
+use tonic::{Request, Response, Status};
+
+pub mod proto {
+    tonic::include_proto!("pol.v1");
+}
+
+use proto::proof_service_server::ProofServiceServer;
+use proto::{
+    ProofBatch, ProofBlob, ProveRequest, VerifyBatchReply, VerifyReply,
+};
+
+/// Limits for the service
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceConfig {
+    /// Proofs calculated at once, further Prove calls are rejected
+    pub max_concurrent_proofs: usize,
+    /// Largest request body accepted, in bytes, which for Verify is the
+    /// proof and a few bytes of framing
+    pub max_request_bytes: usize,
+}
+
+impl Default for ServiceConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent_proofs: 2,
+            max_request_bytes: 1 << 20,
+        }
+    }
+}
+
+pub struct ProofService {
+    config: ServiceConfig,
+    proof_permits: Arc<Semaphore>,
+}
+
+impl ProofService {
+    pub fn new(config: ServiceConfig) -> Self {
+        Self {
+            proof_permits: Arc::new(Semaphore::new(
+                config.max_concurrent_proofs,
+            )),
+            config,
+        }
+    }
+
+    /// Wraps the service for adding to a tonic server. Request bodies over
+    /// the limit are cut off while they stream in, before they're decoded.
+    /// tonic 0.5 has no max_decoding_message_size to do this with.
+    pub fn into_server(self) -> RequestLimit<ProofServiceServer<Self>> {
+        RequestLimit {
+            limit: self.config.max_request_bytes,
+            inner: ProofServiceServer::new(self),
+        }
+    }
+}
+
+/// Limits the request bodies of the wrapped service, see
+/// ProofService::into_server
+#[derive(Debug, Clone)]
+pub struct RequestLimit<S> {
+    limit: usize,
+    inner: S,
+}
+
+impl<S: NamedService> NamedService for RequestLimit<S> {
+    const NAME: &'static str = S::NAME;
+}
+
+impl<S, B> Service<http::Request<B>> for RequestLimit<S>
+where
+    S: Service<http::Request<LimitedBody<B>>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        let remaining = self.limit;
+        self.inner
+            .call(request.map(|inner| LimitedBody { inner, remaining }))
+    }
+}
+
+/// Passes on an error of the wrapped body
+fn body_error(error: impl Into<StdError>) -> Status {
+    Status::unknown(error.into().to_string())
+}
+
+/// A request body that fails with ResourceExhausted once more than the limit
+/// has been read
+#[derive(Debug)]
+pub struct LimitedBody<B> {
+    inner: B,
+    remaining: usize,
+}
+
+impl<B> Body for LimitedBody<B>
+where
+    B: Body + Unpin,
+    B::Error: Into<StdError>,
+{
+    type Data = B::Data;
+    type Error = Status;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let data = match Pin::new(&mut self.inner).poll_data(cx) {
+            Poll::Ready(Some(Ok(data))) => data,
+            Poll::Ready(Some(Err(error))) => {
+                return Poll::Ready(Some(Err(body_error(error))))
+            }
+            Poll::Ready(None) => return Poll::Ready(None),
+            Poll::Pending => return Poll::Pending,
+        };
+        if data.remaining() > self.remaining {
+            return Poll::Ready(Some(Err(Status::resource_exhausted(
+                "The request is over the size limit",
+            ))));
+        }
+        self.remaining -= data.remaining();
+        Poll::Ready(Some(Ok(data)))
+    }
+
+    fn poll_trailers(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+        Pin::new(&mut self.inner)
+            .poll_trailers(cx)
+            .map_err(body_error)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+}
+
+/// The reply for a proof of the iterations that checked as given
+fn reply(iterations: u32, checked: Result<(), VerifyError>) -> VerifyReply {
+    VerifyReply {
+        valid: checked.is_ok(),
+        iterations,
+        error: checked
+            .err()
+            .map_or_else(String::new, |error| error.to_string()),
+    }
+}
+
+/// Decodes the proofs and checks the ones that decode as one batch, on all
+/// cores with the parallel feature. Proofs that don't decode get the error
+/// in their place.
+fn check_blobs(blobs: &[Vec<u8>]) -> Vec<VerifyReply> {
+    let mut proofs = Vec::with_capacity(blobs.len());
+    let mut errors = Vec::with_capacity(blobs.len());
+    for bytes in blobs {
+        match VDFProof::from_bytes(bytes) {
+            Ok(proof) => {
+                proofs.push(proof);
+                errors.push(None);
+            }
+            Err(error) => errors.push(Some(error.to_string())),
+        }
+    }
+
+    #[cfg(feature = "parallel")]
+    let checked = crate::vdf::check_batch_parallel(&proofs);
+    #[cfg(not(feature = "parallel"))]
+    let checked: Vec<_> = proofs.iter().map(VDFProof::check).collect();
+
+    let mut checked = proofs.iter().zip(checked);
+    errors
+        .into_iter()
+        .map(|error| match error {
+            Some(error) => VerifyReply {
+                valid: false,
+                iterations: 0,
+                error,
+            },
+            None => {
+                let (proof, checked) =
+                    checked.next().expect("a result for every decoded proof");
+                reply(proof.output.iterations, checked)
+            }
+        })
+        .collect()
+}
+
+#[tonic::async_trait]
+impl proto::proof_service_server::ProofService for ProofService {
+    async fn verify(
+        &self,
+        request: Request<ProofBlob>,
+    ) -> Result<Response<VerifyReply>, Status> {
+        let bytes = request.into_inner().proof;
+        let proof = VDFProof::from_bytes(&bytes)
+            .map_err(|error| Status::invalid_argument(error.to_string()))?;
+
+        let iterations = proof.output.iterations;
+        let checked = tokio::task::spawn_blocking(move || proof.check())
+            .await
+            .map_err(|_| Status::internal("Verification panicked"))?;
+        Ok(Response::new(reply(iterations, checked)))
+    }
+
+    async fn verify_batch(
+        &self,
+        request: Request<ProofBatch>,
+    ) -> Result<Response<VerifyBatchReply>, Status> {
+        let blobs = request.into_inner().proofs;
+        let replies = tokio::task::spawn_blocking(move || check_blobs(&blobs))
+            .await
+            .map_err(|_| Status::internal("Verification panicked"))?;
+        Ok(Response::new(VerifyBatchReply { replies }))
+    }
+
+    async fn prove(
+        &self,
+        request: Request<ProveRequest>,
+    ) -> Result<Response<ProofBlob>, Status> {
+        let request = request.into_inner();
+        if request.upper_bound == 0 {
+            return Err(Status::invalid_argument(
+                "upper_bound must be at least 1",
+            ));
+        }
+        let cap = match request.cap.as_str() {
+            "" => None,
+            cap => Some(util::int_from_hex(cap).map_err(|_| {
+                Status::invalid_argument(format!("cap is not hex: {}", cap))
+            })?),
+        };
+        let permit =
+            self.proof_permits
+                .clone()
+                .try_acquire_owned()
+                .map_err(|_| {
+                    Status::resource_exhausted("Too many proofs in progress")
+                })?;
+
+        // The permit is held until the proof is done, even if the call is
+        // dropped before then
+        let proof = tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            let modulus = ModulusChoice::Rsa2048;
+            let generator = util::hash_with_domain(
+                util::SEED_DOMAIN,
+                b"pol",
+                modulus.int(),
+            );
+            let invalid =
+                |error: VdfError| Status::invalid_argument(error.to_string());
+            let mut vdf = VDF::new(
+                modulus,
+                generator,
+                request.upper_bound,
+                ProofType::Sequential,
+            )
+            .map_err(invalid)?;
+            if let Some(cap) = cap {
+                vdf = vdf.with_cap(cap).map_err(invalid)?;
+            }
+            let (_, receiver) = vdf.run_vdf_worker();
+            match receiver.recv() {
+                Ok(Ok(proof)) => Ok(proof),
+                Ok(Err(error)) => {
+                    Err(Status::invalid_argument(error.to_string()))
+                }
+                Err(_) => Err(Status::internal(
+                    "The VDF worker stopped without a proof",
+                )),
+            }
+        })
+        .await
+        .map_err(|_| Status::internal("Proving panicked"))??;
+
+        Ok(Response::new(ProofBlob {
+            proof: proof.to_bytes(),
+        }))
+    }
+}
@@ -0,0 +1,140 @@
+//! HTTP/JSON endpoint for verifying proofs in their JSON form, served by
+//! pol-httpd
+
+use crate::util;
+use crate::vdf::evaluation::VDF;
+use crate::vdf::proof::{DeserializableVDFProof, ProofType};
+use crate::vdf::ModulusChoice;
+use async_std::io::ReadExt;
+use serde::Serialize;
+use std::time;
+use tide::{Body, Request, Response, StatusCode};
+
+/// Limits for the server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpConfig {
+    /// Largest request body accepted, in bytes
+    pub max_body_bytes: usize,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            max_body_bytes: 1 << 20,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpState {
+    max_body_bytes: usize,
+    squarings_per_second: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct VerifyReply {
+    valid: bool,
+    iterations: u32,
+    reason: String,
+}
+
+#[derive(Debug, Serialize)]
+struct CalibrationReply {
+    squarings_per_second: u64,
+}
+
+fn reply<T: Serialize>(status: StatusCode, body: &T) -> tide::Result {
+    Ok(Response::builder(status)
+        .body(Body::from_json(body)?)
+        .build())
+}
+
+fn rejection(status: StatusCode, reason: String) -> tide::Result {
+    reply(
+        status,
+        &VerifyReply {
+            valid: false,
+            iterations: 0,
+            reason,
+        },
+    )
+}
+
+/// Measures the local squaring rate for ms milliseconds
+pub fn measure_squaring_rate(ms: u64) -> u64 {
+    let modulus = ModulusChoice::Rsa2048;
+    let generator =
+        util::hash_with_domain(util::SEED_DOMAIN, b"calibrate", modulus.int());
+    VDF::new(modulus, generator, u32::MAX, ProofType::Sequential)
+        .expect("The calibration parameters are valid")
+        .measure_rate(time::Duration::from_millis(ms))
+}
+
+async fn verify(mut request: Request<HttpState>) -> tide::Result {
+    let limit = request.state().max_body_bytes;
+    let mut body = Vec::new();
+    request
+        .take_body()
+        .into_reader()
+        .take(limit as u64 + 1)
+        .read_to_end(&mut body)
+        .await?;
+    if body.len() > limit {
+        return rejection(
+            StatusCode::PayloadTooLarge,
+            format!("The body is over the limit of {} bytes", limit),
+        );
+    }
+
+    let proof = match serde_json::from_slice::<DeserializableVDFProof>(&body) {
+        Ok(proof) => proof,
+        Err(error) => {
+            return rejection(StatusCode::BadRequest, error.to_string())
+        }
+    };
+    let proof = match proof.try_serialize() {
+        Ok(proof) => proof,
+        Err(error) => {
+            return rejection(
+                StatusCode::BadRequest,
+                format!("The proof has a number that doesn't parse: {}", error),
+            )
+        }
+    };
+
+    let iterations = proof.output.iterations;
+    let checked = async_std::task::spawn_blocking(move || proof.check()).await;
+    reply(
+        StatusCode::Ok,
+        &VerifyReply {
+            valid: checked.is_ok(),
+            iterations,
+            reason: checked
+                .err()
+                .map_or_else(String::new, |error| error.to_string()),
+        },
+    )
+}
+
+async fn calibration(request: Request<HttpState>) -> tide::Result {
+    reply(
+        StatusCode::Ok,
+        &CalibrationReply {
+            squarings_per_second: request.state().squarings_per_second,
+        },
+    )
+}
+
+/// The server, reporting the squaring rate measured when it was started
+pub fn app(
+    config: HttpConfig,
+    squarings_per_second: u64,
+) -> tide::Server<HttpState> {
+    let mut app = tide::with_state(HttpState {
+        max_body_bytes: config.max_body_bytes,
+        squarings_per_second,
+    });
+    app.at("/verify").post(verify);
+    app.at("/calibration").get(calibration);
+    app
+}
@@ -0,0 +1,255 @@
+//! X25519 key exchange for the VDF base
+//!
+//! The secret values are the ephemeral X25519 key, the shared secret and the
+//! transcript it is hashed from. x25519-dalek clears the first two when they
+//! are dropped, and the transcript is kept in a Zeroizing buffer. The base
+//! itself only needs to stay unknown until both parties have committed to
+//! the exchange, as the VDF outputs derived from it are published anyway.
+
+use crate::util;
+use ramp::Int;
+use rand::{CryptoRng, RngCore};
+use std::ops::{Deref, DerefMut};
+use std::ptr;
+use std::sync::atomic::{self, Ordering};
+use x25519_dalek::{EphemeralSecret, PublicKey};
+
+/// Length of the nonce each party contributes
+pub const NONCE_LENGTH: usize = 32;
+
+/// One party's half of an X25519 key exchange. Both parties send their public
+/// key and nonce to each other, and then derive the same VDF base that
+/// neither could know before the exchange.
+pub struct KeyExchange {
+    secret: EphemeralSecret,
+    public: PublicKey,
+    nonce: [u8; NONCE_LENGTH],
+}
+
+impl KeyExchange {
+    /// Generates a fresh key pair and nonce
+    pub fn new<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+        let secret = EphemeralSecret::new(&mut *rng);
+        let public = PublicKey::from(&secret);
+        let mut nonce = [0u8; NONCE_LENGTH];
+        rng.fill_bytes(&mut nonce);
+        Self {
+            secret,
+            public,
+            nonce,
+        }
+    }
+
+    pub fn public_key(&self) -> [u8; 32] {
+        *self.public.as_bytes()
+    }
+
+    pub fn nonce(&self) -> [u8; NONCE_LENGTH] {
+        self.nonce
+    }
+
+    /// Completes the exchange with the other party's public key, deriving the
+    /// VDF base from the shared secret and both nonces. The nonces are given
+    /// in the order of the roles, so both parties hash the same transcript.
+    pub fn derive_base(
+        self,
+        their_public_key: [u8; 32],
+        initiator_nonce: &[u8; NONCE_LENGTH],
+        responder_nonce: &[u8; NONCE_LENGTH],
+        modulus: &Int,
+    ) -> Int {
+        let shared = self
+            .secret
+            .diffie_hellman(&PublicKey::from(their_public_key));
+        derive_base(
+            shared.as_bytes(),
+            initiator_nonce,
+            responder_nonce,
+            modulus,
+        )
+    }
+}
+
+/// Secret bytes that are overwritten with zeros when dropped. The buffer
+/// should be allocated with its final capacity, as a reallocation leaves the
+/// old copy behind.
+pub struct Zeroizing(Vec<u8>);
+
+impl Zeroizing {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self(Vec::with_capacity(capacity))
+    }
+}
+
+impl Deref for Zeroizing {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        &self.0
+    }
+}
+
+impl DerefMut for Zeroizing {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.0
+    }
+}
+
+impl Drop for Zeroizing {
+    fn drop(&mut self) {
+        // Clear the spare capacity as well, left over from truncations
+        self.0.resize(self.0.capacity(), 0);
+        for byte in self.0.iter_mut() {
+            // Volatile so the writes aren't optimized away as dead stores
+            unsafe { ptr::write_volatile(byte, 0) };
+        }
+        atomic::compiler_fence(Ordering::SeqCst);
+        #[cfg(test)]
+        tests::CLEARED
+            .with(|cleared| cleared.borrow_mut().push(self.0.clone()));
+    }
+}
+
+/// Hashes the shared secret and both nonces to a VDF base that is larger than
+/// one and coprime with the modulus. A counter is appended to the transcript
+/// and increased until the output qualifies.
+pub fn derive_base(
+    shared_secret: &[u8],
+    initiator_nonce: &[u8; NONCE_LENGTH],
+    responder_nonce: &[u8; NONCE_LENGTH],
+    modulus: &Int,
+) -> Int {
+    let mut transcript =
+        Zeroizing::with_capacity(shared_secret.len() + 2 * NONCE_LENGTH + 4);
+    transcript.extend_from_slice(shared_secret);
+    transcript.extend_from_slice(initiator_nonce);
+    transcript.extend_from_slice(responder_nonce);
+    let prefix_length = transcript.len();
+
+    let mut counter: u32 = 0;
+    loop {
+        transcript.truncate(prefix_length);
+        transcript.extend_from_slice(&counter.to_be_bytes());
+        let base =
+            util::hash_with_domain(util::SEED_DOMAIN, &transcript, modulus);
+        if base > 1 && base.gcd(modulus) == 1 {
+            return base;
+        }
+        counter += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vdf::ModulusChoice;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::cell::RefCell;
+
+    thread_local! {
+        /// The whole capacity of every Zeroizing buffer dropped on this
+        /// thread, read back right after clearing
+        pub static CLEARED: RefCell<Vec<Vec<u8>>> = RefCell::new(Vec::new());
+    }
+
+    fn last_cleared() -> Vec<u8> {
+        CLEARED.with(|cleared| cleared.borrow().last().unwrap().clone())
+    }
+
+    fn exchange(
+        modulus: &Int,
+        initiator: KeyExchange,
+        responder: KeyExchange,
+    ) -> (Int, Int) {
+        let (initiator_key, initiator_nonce) =
+            (initiator.public_key(), initiator.nonce());
+        let (responder_key, responder_nonce) =
+            (responder.public_key(), responder.nonce());
+        (
+            initiator.derive_base(
+                responder_key,
+                &initiator_nonce,
+                &responder_nonce,
+                modulus,
+            ),
+            responder.derive_base(
+                initiator_key,
+                &initiator_nonce,
+                &responder_nonce,
+                modulus,
+            ),
+        )
+    }
+
+    #[test]
+    fn both_sides_derive_the_same_base() {
+        let modulus = ModulusChoice::Rsa2048.int().clone();
+        let mut rng = StdRng::seed_from_u64(1);
+        let (ours, theirs) = exchange(
+            &modulus,
+            KeyExchange::new(&mut rng),
+            KeyExchange::new(&mut rng),
+        );
+        assert_eq!(ours, theirs);
+        assert!(ours > 1 && ours < modulus);
+
+        // A fresh exchange gives a fresh base
+        let (again, _) = exchange(
+            &modulus,
+            KeyExchange::new(&mut rng),
+            KeyExchange::new(&mut rng),
+        );
+        assert_ne!(ours, again);
+    }
+
+    #[test]
+    fn nonces_change_the_base() {
+        let modulus = ModulusChoice::Rsa2048.int().clone();
+        let shared = [7u8; 32];
+        let (a, b) = ([1u8; NONCE_LENGTH], [2u8; NONCE_LENGTH]);
+        let base = derive_base(&shared, &a, &b, &modulus);
+
+        assert_eq!(base, derive_base(&shared, &a, &b, &modulus));
+        assert_ne!(base, derive_base(&shared, &b, &a, &modulus));
+        assert_ne!(base, derive_base(&shared, &a, &a, &modulus));
+        assert_ne!(base, derive_base(&[8u8; 32], &a, &b, &modulus));
+    }
+
+    #[test]
+    fn base_is_coprime_with_the_modulus() {
+        // Most outputs share a factor with 2 * 3 * 5 * 7, forcing retries
+        let modulus = Int::from(210);
+        for i in 0..50u8 {
+            let base = derive_base(
+                &[i],
+                &[0u8; NONCE_LENGTH],
+                &[1u8; NONCE_LENGTH],
+                &modulus,
+            );
+            assert!(base > 1);
+            assert_eq!(base.gcd(&modulus), 1);
+        }
+    }
+
+    #[test]
+    fn zeroizing_clears_the_buffer_on_drop() {
+        let mut secret = Zeroizing::with_capacity(8);
+        secret.extend_from_slice(&[1, 2, 3, 4, 5]);
+        drop(secret);
+        assert_eq!(last_cleared(), vec![0; 8]);
+    }
+
+    #[test]
+    fn derived_transcripts_are_cleared() {
+        let modulus = ModulusChoice::Rsa2048.int().clone();
+        let shared = [7u8; 32];
+        derive_base(
+            &shared,
+            &[1u8; NONCE_LENGTH],
+            &[2u8; NONCE_LENGTH],
+            &modulus,
+        );
+        assert_eq!(last_cleared(), vec![0; 32 + 2 * NONCE_LENGTH + 4]);
+    }
+}
@@ -1,15 +1,15 @@
 #![feature(test)]
 #[macro_use]
-extern crate log;
+extern crate tracing;
 extern crate lazy_static;
 extern crate sm;
 extern crate test;
 
 use ramp::Int;
-use ramp_primes::Generator;
 
 use std::error::Error;
 use std::fmt;
+use std::sync::Arc;
 
 use crossbeam::channel::unbounded;
 use crossbeam::channel::{Receiver, Sender};
@@ -19,15 +19,39 @@ use rkyv::{Archive, Deserialize, Serialize};
 
 // Internal imports
 // pub mod p2p;
+#[cfg(feature = "beacon")]
+pub mod beacon;
+pub mod config;
+#[cfg(feature = "capi")]
+pub mod ffi;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod keyexchange;
+pub mod metrics;
+pub mod primality;
+#[cfg(feature = "proto")]
+pub mod proto;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod util;
 pub mod vdf;
 use crate::PoL::*;
+use metrics::MetricsSink;
+use primality::PrimePool;
 use sm::sm;
-use vdf::evaluation::{DeserializableVDFResult, VDF};
+use util::ProtocolVersion;
+use vdf::evaluation::{self, DeserializableVDFResult, VDF};
 use vdf::proof::{DeserializableVDFProof, VDFProof};
-use vdf::InvalidCapError;
+use vdf::{InvalidCapError, ModulusChoice};
 
-// RSA-2048, copied from Wikipedia
-pub const RSA_2048: &str = "2519590847565789349402718324004839857142928212620403202777713783604366202070759555626401852588078440691829064124951508218929855914917618450280848912007284499268739280728777673597141834727026189637501497182469116507761337985909570009733045974880842840179742910064245869181719511874612151517265463228221686998754918242243363725908514186546204357679842338718477444792073993423658482382428119816381501067481045166037730605620161967625613384414360383390441495263443219011465754445417842402092461651572335077870774981712577246796292638635637328991215483143816789988504044536402352738195137863656439121201039712282120720357";
+pub use vdf::moduli::RSA_2048;
+
+/// How long the squaring rate is measured for when a session starts without
+/// one
+const CALIBRATION_TIME: std::time::Duration =
+    std::time::Duration::from_millis(20);
 
 // State machine macro for handling the protocol state
 sm!(
@@ -146,9 +170,12 @@ impl Error for PoLStartError {
 #[derive(Debug)]
 pub struct ProofOfLatency {
     // Starting parameters
-    pub modulus: Option<Int>,
+    pub modulus: Option<ModulusChoice>,
     pub generator: Option<Int>,
     pub upper_bound: Option<u32>,
+    pub protocol_version: ProtocolVersion,
+    /// Iterations per second of the VDFs, measured on start if not given
+    pub squarings_per_second: Option<u64>,
     // Channels for discussing with the VDF
     vdf_capper: Option<Sender<Int>>,
     vdf_result_channel: Option<Receiver<Result<VDFProof, InvalidCapError>>>,
@@ -158,6 +185,8 @@ pub struct ProofOfLatency {
     // VDF Proofs
     pub prover_result: Option<VDFProof>,
     pub verifier_result: Option<VDFProof>,
+    metrics: Option<Arc<dyn MetricsSink>>,
+    prime_pool: Option<Arc<PrimePool>>,
 }
 
 impl Default for ProofOfLatency {
@@ -166,24 +195,116 @@ impl Default for ProofOfLatency {
             modulus: None,
             generator: None,
             upper_bound: None,
+            protocol_version: ProtocolVersion::CURRENT,
+            squarings_per_second: None,
             vdf_capper: None,
             vdf_result_channel: None,
             prover_result: None,
             verifier_result: None,
             user_input_listener: None,
             user_output_sender: None,
+            metrics: None,
+            prime_pool: None,
         }
     }
 }
 
 impl ProofOfLatency {
-    pub fn init(mut self, modulus: Int, upper_bound: u32) -> Self {
+    pub fn init(mut self, modulus: ModulusChoice, upper_bound: u32) -> Self {
         self.modulus = Some(modulus);
         self.generator = None;
         self.upper_bound = Some(upper_bound);
         self
     }
 
+    /// Derives the generator the way the given version of the protocol does.
+    /// Both participants must use the same version.
+    pub fn with_protocol_version(mut self, version: ProtocolVersion) -> Self {
+        self.protocol_version = version;
+        self
+    }
+
+    /// Sets the squaring rate the minimum iterations of the VDFs are derived
+    /// from, instead of measuring it when the session starts
+    pub fn with_squaring_rate(mut self, squarings_per_second: u64) -> Self {
+        self.squarings_per_second = Some(squarings_per_second);
+        self
+    }
+
+    /// The iterations the VDFs do in evaluation::FLOOR_RTT. Caps that arrive
+    /// sooner can't have made a real round trip, so the VDFs hold them.
+    fn min_iterations(&self) -> u32 {
+        let rate = self.squarings_per_second.unwrap_or_else(|| {
+            let modulus = self.modulus.clone().unwrap();
+            let generator = util::hash_with_domain(
+                util::SEED_DOMAIN,
+                b"calibrate",
+                modulus.int(),
+            );
+            VDF::new(
+                modulus,
+                generator,
+                u32::MAX,
+                vdf::proof::ProofType::Sequential,
+            )
+            .map(|vdf| vdf.measure_rate(CALIBRATION_TIME))
+            .unwrap_or(0)
+        });
+        evaluation::min_iterations_for_rtt(evaluation::FLOOR_RTT, rate)
+    }
+
+    /// Reports the sessions, their VDFs and the verified proofs to the sink
+    pub fn with_metrics(mut self, metrics: Arc<dyn MetricsSink>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Draws the caps sent to the other participant from a shared pool of
+    /// pre-generated safe primes
+    pub fn with_prime_pool(mut self, prime_pool: Arc<PrimePool>) -> Self {
+        self.prime_pool = Some(prime_pool);
+        self
+    }
+
+    /// A cap for the other participant, taken from the prime pool when one
+    /// is set and generated with the given bit length otherwise
+    fn new_cap(&self, bits: usize) -> Int {
+        match self.prime_pool.as_ref() {
+            Some(pool) => pool.take(),
+            None => primality::gen_safe_prime(bits, &mut rand::thread_rng()),
+        }
+    }
+
+    /// Adds a metrics sink to the VDF if one is set
+    fn measured(&self, vdf: VDF) -> VDF {
+        match self.metrics.as_ref() {
+            Some(metrics) => vdf.with_metrics(metrics.clone()),
+            None => vdf,
+        }
+    }
+
+    /// Moves the sink's number of active sessions by one
+    fn report_session(&self, started: bool) {
+        if let Some(sink) = self.metrics.as_ref() {
+            if started {
+                sink.incr_counter(metrics::SESSIONS, &[]);
+                sink.add(metrics::ACTIVE_SESSIONS, 1.0, &[]);
+            } else {
+                sink.add(metrics::ACTIVE_SESSIONS, -1.0, &[]);
+            }
+        }
+    }
+
+    fn report_verification(&self, valid: bool) {
+        if let Some(sink) = self.metrics.as_ref() {
+            let outcome = if valid { "valid" } else { "invalid" };
+            sink.incr_counter(
+                metrics::PROOFS_VERIFIED,
+                &[("outcome", outcome)],
+            );
+        }
+    }
+
     pub fn open_io(&mut self) -> (Sender<PoLMessage>, Receiver<PoLMessage>) {
         let (input, listener): (Sender<PoLMessage>, Receiver<PoLMessage>) =
             unbounded();
@@ -211,8 +332,12 @@ impl ProofOfLatency {
     }
 
     fn combine_generator_parts(&self, our: &Int, other: &Int) -> Int {
-        let mul_str: String = (our * other).to_str_radix(16, true);
-        vdf::util::hash_to_mod(&mul_str, &self.modulus.as_ref().unwrap())
+        let mul_bytes: Vec<u8> = util::int_to_be_bytes(&(our * other));
+        self.protocol_version.hash_with_domain(
+            util::SEED_DOMAIN,
+            &mul_bytes,
+            self.modulus.as_ref().unwrap().int(),
+        )
     }
 
     pub fn start(mut self, role: PoLRole) -> Result<bool, PoLStartError> {
@@ -221,6 +346,16 @@ impl ProofOfLatency {
             return Err(PoLStartError);
         }
 
+        // Tells the events of concurrent sessions apart
+        let session_id: u64 = rand::random();
+        let span = info_span!(
+            "session",
+            session_id,
+            role = ?role,
+            upper_bound = self.upper_bound,
+        );
+        let dispatch = tracing::dispatcher::get_default(|d| d.clone());
+
         // Start a new state machine
         let mut sm = match role {
             PoLRole::Prover => Machine::new(Prover).as_enum(),
@@ -228,12 +363,20 @@ impl ProofOfLatency {
         };
 
         thread::spawn(move || {
+            let _dispatch = tracing::dispatcher::set_default(&dispatch);
+            let _span = span.enter();
+            self.report_session(true);
+
             // Unwrap the user I/O
             let user_input: &Receiver<PoLMessage> =
                 self.user_input_listener.as_ref().unwrap();
             let user_output: &Sender<PoLMessage> =
                 self.user_output_sender.as_ref().unwrap();
 
+            // Measured before anything is sent, so the calibration doesn't
+            // add to the latency
+            let min_iterations = self.min_iterations();
+
             // Create the sendable cap and generator part
             let mut sendable_cap = Int::zero();
             let mut our_generator_part = Int::zero();
@@ -243,14 +386,20 @@ impl ProofOfLatency {
                 sm = match sm {
                     // PROVER: Create g1 + l1
                     Variant::InitialProver(m) => {
-                        sendable_cap = Generator::new_safe_prime(bit_depth);
-                        our_generator_part = Generator::new_uint(bit_depth);
+                        sendable_cap = self.new_cap(bit_depth);
+                        our_generator_part = primality::gen_uint(
+                            bit_depth,
+                            &mut rand::thread_rng(),
+                        );
                         m.transition(CreateGeneratorPartAndCap).as_enum()
                     }
                     // VERIFIER: Create g2 + l2
                     Variant::InitialVerifier(m) => {
-                        sendable_cap = Generator::new_safe_prime(bit_depth);
-                        our_generator_part = Generator::new_uint(bit_depth);
+                        sendable_cap = self.new_cap(bit_depth);
+                        our_generator_part = primality::gen_uint(
+                            bit_depth,
+                            &mut rand::thread_rng(),
+                        );
                         m.transition(CreateGeneratorPartAndCap).as_enum()
                     }
                     // PROVER: Send g1
@@ -270,7 +419,7 @@ impl ProofOfLatency {
                             match message {
                                 PoLMessage::GeneratorPart { num } => {
                                     // Construct the VDF
-                                    verif_vdf = VDF::new(
+                                    match VDF::new(
                                         self.modulus.clone().unwrap(),
                                         self.combine_generator_parts(
                                             &our_generator_part,
@@ -279,7 +428,23 @@ impl ProofOfLatency {
                                         ),
                                         self.upper_bound.unwrap(),
                                         vdf::proof::ProofType::Sequential,
-                                    );
+                                    ) {
+                                        Ok(vdf) => {
+                                            verif_vdf = self.measured(
+                                                vdf.with_hash_alg(
+                                                    self.protocol_version
+                                                        .hash_alg(),
+                                                )
+                                                .with_min_iterations(
+                                                    min_iterations,
+                                                ),
+                                            )
+                                        }
+                                        Err(error) => {
+                                            self.abort(&format!("WaitingByCreateGeneratorPart: {}", error));
+                                            break;
+                                        }
+                                    }
                                 }
                                 _ => {
                                     self.abort("WaitingByCreateGeneratorPart: Expected PoLMessage::GeneratorPart, received something else");
@@ -292,7 +457,8 @@ impl ProofOfLatency {
                         }
 
                         // Start VDF
-                        let (capper, receiver) = verif_vdf.run_vdf_worker();
+                        let (capper, receiver) =
+                            verif_vdf.with_session(session_id).run_vdf_worker();
                         self.vdf_capper = Some(capper);
                         self.vdf_result_channel = Some(receiver);
 
@@ -320,7 +486,7 @@ impl ProofOfLatency {
                                     cap,
                                 } => {
                                     // Construct the VDF
-                                    prover_vdf = VDF::new(
+                                    match VDF::new(
                                         self.modulus.clone().unwrap(),
                                         self.combine_generator_parts(
                                             &our_generator_part,
@@ -333,9 +499,29 @@ impl ProofOfLatency {
                                         self.upper_bound.unwrap(),
                                         vdf::proof::ProofType::Parallel,
                                     )
-                                    .with_cap(
-                                        Int::from_str_radix(&cap, 10).unwrap(),
-                                    );
+                                    .and_then(|vdf| {
+                                        // Before the cap, which starts the
+                                        // parallel proof
+                                        vdf.with_hash_alg(
+                                            self.protocol_version.hash_alg(),
+                                        )
+                                        .with_cap(
+                                            Int::from_str_radix(&cap, 10)
+                                                .unwrap(),
+                                        )
+                                    }) {
+                                        Ok(vdf) => {
+                                            prover_vdf = self.measured(
+                                                vdf.with_min_iterations(
+                                                    min_iterations,
+                                                ),
+                                            )
+                                        }
+                                        Err(error) => {
+                                            self.abort(&format!("WaitingBySendGeneratorPart: {}", error));
+                                            break;
+                                        }
+                                    }
                                     debug!("{:?}", prover_vdf);
                                 }
                                 _ => {
@@ -348,7 +534,9 @@ impl ProofOfLatency {
                             break;
                         }
 
-                        let (_, receiver) = prover_vdf.run_vdf_worker();
+                        let (_, receiver) = prover_vdf
+                            .with_session(session_id)
+                            .run_vdf_worker();
                         self.vdf_result_channel = Some(receiver);
 
                         // Transition the state machine
@@ -384,9 +572,16 @@ impl ProofOfLatency {
                         if let Ok(message) = user_input.recv() {
                             match message {
                                 PoLMessage::VDFProofAndCap { proof, cap } => {
+                                    let proof = proof.serialize();
+                                    if let Err(error) = proof.check_hash_alg(
+                                        self.protocol_version.hash_alg(),
+                                    ) {
+                                        self.abort(&format!("EvaluatingAndWaitingBySendGeneratorPartAndCap: {}", error));
+                                        break;
+                                    }
                                     // Stop our VDF with cap l1
                                     match self.receive(
-                                        proof.serialize(),
+                                        proof,
                                         Int::from_str_radix(&cap, 10).unwrap(),
                                     ) {
                                         (
@@ -479,6 +674,7 @@ impl ProofOfLatency {
                     }
                 }
             }
+            self.report_session(false);
         });
 
         Ok(true)
@@ -512,7 +708,20 @@ impl ProofOfLatency {
                 Int::from(iter_verifier - iter_prover)
             };
 
-            if their_proof.verify() && proof.verify() {
+            if proof.cap_arrived_early() || their_proof.cap_arrived_early() {
+                warn!(
+                    ours = proof.cap_received_at,
+                    theirs = their_proof.cap_received_at,
+                    "A cap arrived before the minimum iterations"
+                );
+            }
+
+            let their_proof_valid = their_proof.verify();
+            self.report_verification(their_proof_valid);
+            let our_proof_valid = proof.verify();
+            self.report_verification(our_proof_valid);
+
+            if their_proof_valid && our_proof_valid {
                 info!(
                         "Both proofs are correct! Latency between peers was {:?} iterations.",
                         difference
@@ -528,12 +737,11 @@ impl ProofOfLatency {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use ramp_primes::Verification;
-    use std::str::FromStr;
+    use metrics::InMemoryMetrics;
 
     #[test]
     fn runs_without_blocking() {
-        let modulus = Int::from_str(RSA_2048).unwrap();
+        let modulus = ModulusChoice::Rsa2048;
         let mut pol = ProofOfLatency::default().init(modulus, u32::MAX);
 
         let (_input, _output) = pol.open_io();
@@ -543,18 +751,129 @@ mod tests {
 
     #[test]
     fn generator_combiner_is_commutative() {
-        let modulus = Int::from_str(RSA_2048).unwrap();
-        let rand1 = Generator::new_uint(128);
-        let rand2 = Generator::new_uint(128);
+        let modulus = ModulusChoice::Rsa2048;
+        let rand1 = primality::gen_uint(128, &mut rand::thread_rng());
+        let rand2 = primality::gen_uint(128, &mut rand::thread_rng());
         let pol = ProofOfLatency::default().init(modulus, u32::MAX);
         let result1 = pol.combine_generator_parts(&rand1, &rand2);
         let result2 = pol.combine_generator_parts(&rand2, &rand1);
         assert_eq!(result1, result2);
     }
 
+    #[test]
+    fn caps_come_from_the_prime_pool() {
+        let pol = ProofOfLatency::default().init(ModulusChoice::Rsa2048, 1000);
+        let cap = pol.new_cap(64);
+        assert!(primality::is_safe_prime(&cap));
+        assert_eq!(cap.bit_length(), 64);
+
+        let pol = pol.with_prime_pool(Arc::new(PrimePool::new(32, 1)));
+        let cap = pol.new_cap(64);
+        assert!(primality::is_safe_prime(&cap));
+        assert_eq!(cap.bit_length(), 32);
+    }
+
+    #[test]
+    fn min_iterations_follow_the_squaring_rate() {
+        let pol = ProofOfLatency::default()
+            .init(ModulusChoice::Rsa2048, 1000)
+            .with_squaring_rate(1_000_000);
+        assert_eq!(pol.min_iterations(), 100);
+        assert_eq!(pol.with_squaring_rate(0).min_iterations(), 0);
+    }
+
+    #[test]
+    fn full_session_reports_metrics() {
+        let metrics = Arc::new(InMemoryMetrics::default());
+        let start = |role: PoLRole| {
+            let mut pol = ProofOfLatency::default()
+                .init(ModulusChoice::Rsa2048, 1000)
+                .with_metrics(metrics.clone());
+            let io = pol.open_io();
+            assert!(pol.start(role).is_ok());
+            io
+        };
+        let (prover_input, prover_output) = start(PoLRole::Prover);
+        let (verifier_input, verifier_output) = start(PoLRole::Verifier);
+
+        // Relay the messages between the roles until the prover sends back
+        // the finished Proof of Latency
+        let generator_part = prover_output.recv().unwrap();
+        verifier_input.send(generator_part).unwrap();
+        let generator_part_and_cap = verifier_output.recv().unwrap();
+        prover_input.send(generator_part_and_cap).unwrap();
+        let proof_and_cap = prover_output.recv().unwrap();
+        verifier_input.send(proof_and_cap).unwrap();
+        let proof_of_latency = verifier_output.recv().unwrap();
+        assert!(matches!(
+            proof_of_latency,
+            PoLMessage::ProofOfLatency { .. }
+        ));
+        prover_input.send(proof_of_latency).unwrap();
+        assert!(matches!(
+            prover_output.recv().unwrap(),
+            PoLMessage::ProofOfLatency { .. }
+        ));
+        // The sessions have ended once they drop their outputs
+        assert!(prover_output.recv().is_err());
+        assert!(verifier_output.recv().is_err());
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.counter(metrics::SESSIONS, &[]), 2);
+        assert_eq!(snapshot.counter(metrics::PROOFS_GENERATED, &[]), 2);
+        assert_eq!(
+            snapshot.counter(metrics::PROOFS_VERIFIED, &[("outcome", "valid")]),
+            2
+        );
+        assert_eq!(
+            snapshot
+                .counter(metrics::PROOFS_VERIFIED, &[("outcome", "invalid")]),
+            0
+        );
+        assert_eq!(snapshot.counter(metrics::CAP_REJECTIONS, &[]), 0);
+        assert!(
+            snapshot.value(metrics::SQUARINGS_PER_SECOND, &[]).unwrap() > 0.0
+        );
+        assert_eq!(snapshot.value(metrics::ACTIVE_SESSIONS, &[]), Some(0.0));
+    }
+
+    #[test]
+    fn sessions_across_protocol_versions_fail_on_the_hash_function() {
+        let start = |role: PoLRole, version: ProtocolVersion| {
+            let mut pol = ProofOfLatency::default()
+                .init(ModulusChoice::Rsa2048, 1000)
+                .with_protocol_version(version);
+            let io = pol.open_io();
+            assert!(pol.start(role).is_ok());
+            io
+        };
+        let (prover_input, prover_output) =
+            start(PoLRole::Prover, ProtocolVersion::V1_1_0);
+        let (verifier_input, verifier_output) =
+            start(PoLRole::Verifier, ProtocolVersion::V1_0_0);
+
+        let generator_part = prover_output.recv().unwrap();
+        verifier_input.send(generator_part).unwrap();
+        let generator_part_and_cap = verifier_output.recv().unwrap();
+        prover_input.send(generator_part_and_cap).unwrap();
+        let proof_and_cap = prover_output.recv().unwrap();
+        verifier_input.send(proof_and_cap).unwrap();
+
+        let expected = vdf::VdfError::HashAlgMismatch {
+            expected: util::HashAlg::Sha3_512,
+            received: util::HashAlg::Shake256,
+        };
+        match verifier_output.recv().unwrap() {
+            PoLMessage::Error { reason } => {
+                assert!(reason.ends_with(&expected.to_string()), "{}", reason)
+            }
+            _ => panic!("The session should have failed"),
+        }
+    }
+
     #[test]
     fn runs_prover_state_machine_in_correct_order() {
-        let modulus = Int::from_str(RSA_2048).unwrap();
+        let modulus = ModulusChoice::Rsa2048;
         let mut pol = ProofOfLatency::default().init(modulus, 42);
         let (input, output) = pol.open_io();
 
@@ -574,8 +893,8 @@ mod tests {
 
         // Then, the state machine waits for our input, specifically a generator
         // part and the cap
-        let cap = Generator::new_prime(64);
-        let generator_part = Generator::new_uint(64);
+        let cap = primality::gen_prime(64, &mut rand::thread_rng());
+        let generator_part = primality::gen_uint(64, &mut rand::thread_rng());
         assert!(input
             .send(PoLMessage::GeneratorPartAndCap {
                 generator_part: generator_part.to_str_radix(10, false),
@@ -588,8 +907,8 @@ mod tests {
             match message {
                 PoLMessage::VDFProofAndCap { proof, cap } => {
                     assert!(proof.serialize().verify());
-                    assert!(Verification::verify_prime(
-                        Int::from_str_radix(&cap, 10).unwrap()
+                    assert!(primality::is_safe_prime(
+                        &Int::from_str_radix(&cap, 10).unwrap()
                     ));
                 }
                 _ => panic!(),
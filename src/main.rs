@@ -1,105 +1,325 @@
 #[macro_use]
 extern crate log;
 
-use proof_of_latency::vdf::util::hash_to_prime;
-use proof_of_latency::{PoLMessage, PoLRole, ProofOfLatency, RSA_2048};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use proof_of_latency::config::Config;
+use proof_of_latency::util::{self, HashAlg};
+use proof_of_latency::vdf::evaluation::{ProgressReport, VDF};
+use proof_of_latency::vdf::proof::{
+    DeserializableVDFProof, ProofType, VDFProof, PROOF_STRING_PREFIX,
+};
+use proof_of_latency::vdf::worker::WorkerMessage;
+use proof_of_latency::vdf::{ModulusChoice, VdfError};
 use ramp::Int;
-use ramp_primes::Generator;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::process;
 use std::time::Instant;
-// use ockam::{Context, Result, Route, SecureChannel, TcpTransport, Vault, TCP};
 
-// #[ockam::node]
-fn main() {
-    env_logger::init();
+/// Everything that can go wrong in the CLI. Each variant exits with its own
+/// code, so scripts can tell the failures apart.
+#[derive(Debug)]
+enum CliError {
+    InvalidProof,
+    Usage(String),
+    Io(String),
+    Parse(String),
+    Evaluation(String),
+    Config(String),
+}
 
-    //   // Initialize the TCP Transport.
-    //   let tcp = TcpTransport::create(&ctx).await?;
-    //
-    //   // Create a TCP connection.
-    //   tcp.connect("127.0.0.1:3000").await?;
-    //
-    //   let vault = Vault::create(&ctx).await?;
-    //
-    //   // Connect to a secure channel listener and perform a handshake.
-    //   let channel = SecureChannel::create(
-    //       &mut ctx,
-    //       // route to the secure channel listener
-    //       Route::new()
-    //           .append_t(TCP, "127.0.0.1:4000") // responder node
-    //           .append("secure_channel_listener"), // secure_channel_listener
-    // on responder node,       &vault,
-    //   )
-    //       .await?;
-    //
-    //   // Send a message to the echoer worker via the channel.
-    //   ctx.send(
-    //       Route::new().append(channel.address()).append("echoer"),
-    //       "Hello Ockam!".to_string(),
-    //   )
-    //       .await?;
-    //
-    //   // Wait to receive a reply and print it.
-    //   let reply = ctx.receive::<String>().await?;
-    //   println!("App Received: {}", reply); // should print "Hello Ockam!"
-
-    let modulus = Int::from_str_radix(RSA_2048, 10).unwrap();
+impl CliError {
+    fn exit_code(&self) -> i32 {
+        match self {
+            CliError::InvalidProof => 1,
+            CliError::Usage(_) => 2,
+            CliError::Io(_) => 3,
+            CliError::Parse(_) => 4,
+            CliError::Evaluation(_) => 5,
+            CliError::Config(_) => 6,
+        }
+    }
+}
 
-    let timer = Instant::now();
-    let prime1 = hash_to_prime("asdfhjaefhliuefeajie", &modulus);
-    debug!(
-        "Prime {:?} calculated in {:?}ms",
-        prime1,
-        timer.elapsed().as_millis()
-    );
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::InvalidProof => write!(f, "The proof is not valid"),
+            CliError::Usage(reason) => write!(f, "{}", reason),
+            CliError::Io(reason) => write!(f, "I/O error: {}", reason),
+            CliError::Parse(reason) => write!(f, "Invalid input: {}", reason),
+            CliError::Evaluation(reason) => {
+                write!(f, "Evaluation failed: {}", reason)
+            }
+            CliError::Config(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+fn app() -> App<'static, 'static> {
+    App::new("pol")
+        .about("Proof of Latency")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .takes_value(true)
+                .global(true)
+                .help("TOML configuration file, overridden by flags"),
+        )
+        .arg(
+            Arg::with_name("modulus")
+                .long("modulus")
+                .takes_value(true)
+                .global(true)
+                .help("rsa-2048, or a custom modulus in decimal or 0x-hex"),
+        )
+        .arg(
+            Arg::with_name("hash")
+                .long("hash")
+                .takes_value(true)
+                .global(true)
+                .help("sha3-512, sha256, blake3 or shake256 for the generator"),
+        )
+        .subcommand(
+            SubCommand::with_name("calibrate")
+                .about("Measures the local squaring rate")
+                .arg(
+                    Arg::with_name("ms")
+                        .long("ms")
+                        .takes_value(true)
+                        .default_value("500")
+                        .help("How long to measure for, in milliseconds"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("prove")
+                .about("Evaluates the VDF and writes its proof as JSON")
+                .arg(
+                    Arg::with_name("upper-bound")
+                        .long("upper-bound")
+                        .takes_value(true)
+                        .help("Number of squarings to evaluate"),
+                )
+                .arg(
+                    Arg::with_name("cap-bits")
+                        .long("cap-bits")
+                        .takes_value(true)
+                        .help("Bit length of the cap if it's generated"),
+                )
+                .arg(
+                    Arg::with_name("cap")
+                        .long("cap")
+                        .takes_value(true)
+                        .help("Cap prime in hex, generated if not given"),
+                )
+                .arg(
+                    Arg::with_name("seed")
+                        .long("seed")
+                        .takes_value(true)
+                        .default_value("pol")
+                        .help("Seed the generator is derived from"),
+                )
+                .arg(
+                    Arg::with_name("out")
+                        .long("out")
+                        .takes_value(true)
+                        .required(true)
+                        .help("File to write the proof to"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("verify")
+                .about("Verifies a proof written by prove")
+                .arg(Arg::with_name("proof").required(true).index(1).help(
+                    "JSON file written by prove, or the proof itself \
+                             as hex or polproof1-prefixed base64",
+                )),
+        )
+}
 
-    let diff = &prime1 - &modulus;
-    debug!("The prime is {:?} larger than the modulus!", diff);
+fn parse_number<T: std::str::FromStr>(
+    matches: &ArgMatches,
+    name: &str,
+) -> Result<T, CliError> {
+    let value = matches.value_of(name).unwrap_or_default();
+    value.parse().map_err(|_| {
+        CliError::Parse(format!("--{} is not a number: {}", name, value))
+    })
+}
 
-    let mut pol = ProofOfLatency::default().init(modulus, 150000);
-    let (input, output) = pol.open_io();
-    debug!("Proof of latency instance created");
+fn evaluation_error(error: VdfError) -> CliError {
+    CliError::Evaluation(error.to_string())
+}
 
-    match pol.start(PoLRole::Prover) {
-        Ok(_) => info!("PoL state machine started"),
-        Err(_) => error!("Couldn't start the PoL state machine"),
+/// Loads the configuration file if one is given, then applies the flags that
+/// override its values
+fn config(matches: &ArgMatches) -> Result<Config, CliError> {
+    let mut config = match matches.value_of("config") {
+        Some(path) => Config::load(path)
+            .map_err(|error| CliError::Config(error.to_string()))?,
+        None => Config::default(),
+    };
+    if let Some(modulus) = matches.value_of("modulus") {
+        config.modulus = modulus
+            .parse::<ModulusChoice>()
+            .map_err(|error| CliError::Config(error.to_string()))?;
+    }
+    if let Some(hash) = matches.value_of("hash") {
+        config.hash = hash
+            .parse::<HashAlg>()
+            .map_err(|error| CliError::Config(error.to_string()))?;
     }
+    if matches.is_present("upper-bound") {
+        config.upper_bound = parse_number(matches, "upper-bound")?;
+    }
+    if matches.is_present("cap-bits") {
+        config.cap_bits = parse_number(matches, "cap-bits")?;
+    }
+    config
+        .validate()
+        .map_err(|error| CliError::Config(error.to_string()))?;
+    Ok(config)
+}
 
-    if let Ok(message) = output.recv() {
-        match message {
-            PoLMessage::GeneratorPart { num } => {
-                info!("Generator part received: {:?}", num)
-            }
-            _ => error!("Wrong message received"),
-        }
-    } else {
-        error!("Channel closed!")
+fn generator(hash: HashAlg, seed: &str, modulus: &Int) -> Int {
+    hash.hash_with_domain(util::SEED_DOMAIN, seed.as_bytes(), modulus)
+}
+
+fn calibrate(matches: &ArgMatches) -> Result<(), CliError> {
+    let ms: u64 = parse_number(matches, "ms")?;
+    if ms == 0 {
+        return Err(CliError::Usage("--ms must be positive".to_string()));
     }
+    let config = config(matches)?;
+    let modulus = config.modulus;
+    let generator = generator(config.hash, "calibrate", modulus.int());
 
-    let cap = Generator::new_safe_prime(128);
-    let generator_part = Generator::new_uint(128);
-    match input.send(PoLMessage::GeneratorPartAndCap {
-        generator_part: generator_part.to_str_radix(10, false),
-        cap: cap.to_str_radix(10, false),
-    }) {
-        Ok(_) => info!("Received g2, l2"),
-        Err(_) => error!("Channel closed!"),
+    let vdf = VDF::new(modulus, generator, u32::MAX, ProofType::Sequential)
+        .map_err(evaluation_error)?
+        .estimate_upper_bound(ms);
+    let rate = vdf.upper_bound as u64 * 1000 / ms;
+    println!(
+        "{} squarings in {} ms, {} squarings per second",
+        vdf.upper_bound, ms, rate
+    );
+    Ok(())
+}
+
+fn prove(matches: &ArgMatches) -> Result<(), CliError> {
+    let config = config(matches)?;
+    let modulus = config.modulus.clone();
+    let generator = generator(
+        config.hash,
+        matches.value_of("seed").unwrap(),
+        modulus.int(),
+    );
+
+    let mut vdf = VDF::new(
+        modulus,
+        generator,
+        config.upper_bound,
+        ProofType::Sequential,
+    )
+    .map_err(evaluation_error)?
+    .with_cap_bits(config.cap_bits);
+    if let Some(cap) = matches.value_of("cap") {
+        let cap = util::int_from_hex(cap)
+            .map_err(|_| CliError::Parse(format!("--cap: {}", cap)))?;
+        vdf = vdf.with_cap(cap).map_err(evaluation_error)?;
     }
 
-    if let Ok(message) = output.recv() {
-        match message {
-            PoLMessage::VDFProofAndCap { proof, cap: _ } => {
-                if proof.verify() {
-                    info!("VDF ready!")
-                } else {
-                    error!("Our VDF proof was not correct!")
-                }
-            }
-            _ => error!("Wrong message received"),
+    let timer = Instant::now();
+    // About a hundred progress lines over the run
+    let poll_interval = (config.upper_bound / 100).max(1);
+    let handle = vdf.with_poll_interval(poll_interval).spawn_worker();
+    for message in handle.subscribe().iter() {
+        if let WorkerMessage::Progress(report) = message {
+            eprint!("\r{}", progress_line(&report));
         }
+    }
+    eprintln!();
+    let proof = handle.join().map_err(evaluation_error)?;
+    debug!("Proof calculated in {:?}ms", timer.elapsed().as_millis());
+
+    let json = serde_json::to_string_pretty(&proof.deserialize()).unwrap();
+    let out = matches.value_of("out").unwrap();
+    fs::write(out, json)
+        .map_err(|error| CliError::Io(format!("{}: {}", out, error)))
+}
+
+/// The line prove redraws on stderr as the VDF runs
+fn progress_line(report: &ProgressReport) -> String {
+    match (report.fraction_complete, report.eta) {
+        (Some(fraction), Some(eta)) => format!(
+            "{:.0}% complete, ~{:.0} s remaining",
+            fraction * 100.0,
+            eta.as_secs_f64()
+        ),
+        _ => format!(
+            "{} iterations in {:.1} s, {:.0} per second",
+            report.iterations,
+            report.elapsed.as_secs_f64(),
+            report.rate
+        ),
+    }
+}
+
+/// Whether the argument is meant as a proof string rather than a file path
+fn is_proof_string(argument: &str) -> bool {
+    argument.starts_with(PROOF_STRING_PREFIX)
+        || (!argument.is_empty()
+            && argument.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+fn verify(matches: &ArgMatches) -> Result<(), CliError> {
+    let argument = matches.value_of("proof").unwrap();
+    let valid = if !Path::new(argument).exists() && is_proof_string(argument) {
+        let proof: VDFProof = argument.parse().map_err(|error| {
+            CliError::Parse(format!("The proof string: {}", error))
+        })?;
+        proof.verify()
+    } else {
+        let json = fs::read_to_string(argument).map_err(|error| {
+            CliError::Io(format!("{}: {}", argument, error))
+        })?;
+        let proof: DeserializableVDFProof = serde_json::from_str(&json)
+            .map_err(|error| {
+                CliError::Parse(format!("{}: {}", argument, error))
+            })?;
+        proof.verify()
+    };
+
+    if valid {
+        println!("Proof is valid");
+        Ok(())
     } else {
-        error!("Channel closed!");
+        Err(CliError::InvalidProof)
     }
+}
+
+fn main() {
+    env_logger::init();
+
+    let matches = match app().get_matches_safe() {
+        Ok(matches) => matches,
+        Err(error) if error.use_stderr() => {
+            eprintln!("{}", error.message);
+            process::exit(CliError::Usage(String::new()).exit_code());
+        }
+        Err(error) => error.exit(),
+    };
+
+    let result = match matches.subcommand() {
+        ("calibrate", Some(sub)) => calibrate(sub),
+        ("prove", Some(sub)) => prove(sub),
+        ("verify", Some(sub)) => verify(sub),
+        _ => unreachable!("clap requires a subcommand"),
+    };
 
-    //   // Stop all workers, stop the node, cleanup and return.
-    //   ctx.stop().await
+    if let Err(error) = result {
+        eprintln!("{}", error);
+        process::exit(error.exit_code());
+    }
 }
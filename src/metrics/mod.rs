@@ -0,0 +1,145 @@
+//! Hooks for reporting operational metrics. The VDF worker and the Proof of
+//! Latency sessions report to a MetricsSink when one is set.
+
+#[cfg(feature = "prometheus")]
+pub mod prometheus;
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Mutex;
+
+/// Squarings per second of the last evaluation, a gauge
+pub const SQUARINGS_PER_SECOND: &str = "pol_squarings_per_second";
+/// Sessions running, a gauge
+pub const ACTIVE_SESSIONS: &str = "pol_active_sessions";
+/// Sessions started
+pub const SESSIONS: &str = "pol_sessions_total";
+/// Proofs calculated by the VDF worker
+pub const PROOFS_GENERATED: &str = "pol_proofs_generated_total";
+/// Proofs verified, labeled with an outcome of valid or invalid
+pub const PROOFS_VERIFIED: &str = "pol_proofs_verified_total";
+/// Caps the VDF worker rejected for not being prime
+pub const CAP_REJECTIONS: &str = "pol_cap_rejections_total";
+/// Panics caught in the VDF worker
+pub const WORKER_PANICS: &str = "pol_worker_panics_total";
+/// Worker messages dropped because a subscriber fell behind
+pub const SUBSCRIBER_DROPS: &str = "pol_subscriber_drops_total";
+
+/// Receives metrics. Labels are name and value pairs.
+pub trait MetricsSink: fmt::Debug + Send + Sync {
+    fn incr_counter(&self, name: &str, labels: &[(&str, &str)]);
+    /// Sets a gauge to the value
+    fn observe(&self, name: &str, value: f64, labels: &[(&str, &str)]);
+    /// Moves a gauge by the delta, starting from zero
+    fn add(&self, name: &str, delta: f64, labels: &[(&str, &str)]);
+}
+
+/// Formats the metric the way Prometheus does, with the labels sorted
+fn key(name: &str, labels: &[(&str, &str)]) -> String {
+    if labels.is_empty() {
+        return name.to_string();
+    }
+    let mut labels = labels.to_vec();
+    labels.sort();
+    let labels: Vec<String> = labels
+        .iter()
+        .map(|(label, value)| format!("{}=\"{}\"", label, value))
+        .collect();
+    format!("{}{{{}}}", name, labels.join(","))
+}
+
+/// The metrics at one point in time
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MetricsSnapshot {
+    pub counters: BTreeMap<String, u64>,
+    pub values: BTreeMap<String, f64>,
+}
+
+impl MetricsSnapshot {
+    /// The counter's value, zero if it was never incremented
+    pub fn counter(&self, name: &str, labels: &[(&str, &str)]) -> u64 {
+        self.counters
+            .get(&key(name, labels))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// The last value observed for the gauge
+    pub fn value(&self, name: &str, labels: &[(&str, &str)]) -> Option<f64> {
+        self.values.get(&key(name, labels)).copied()
+    }
+}
+
+/// Keeps the metrics in memory, for tests and for exporting them elsewhere
+#[derive(Debug, Default)]
+pub struct InMemoryMetrics {
+    snapshot: Mutex<MetricsSnapshot>,
+}
+
+impl InMemoryMetrics {
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        self.snapshot.lock().unwrap().clone()
+    }
+}
+
+impl MetricsSink for InMemoryMetrics {
+    fn incr_counter(&self, name: &str, labels: &[(&str, &str)]) {
+        *self
+            .snapshot
+            .lock()
+            .unwrap()
+            .counters
+            .entry(key(name, labels))
+            .or_default() += 1;
+    }
+
+    fn observe(&self, name: &str, value: f64, labels: &[(&str, &str)]) {
+        self.snapshot
+            .lock()
+            .unwrap()
+            .values
+            .insert(key(name, labels), value);
+    }
+
+    fn add(&self, name: &str, delta: f64, labels: &[(&str, &str)]) {
+        *self
+            .snapshot
+            .lock()
+            .unwrap()
+            .values
+            .entry(key(name, labels))
+            .or_default() += delta;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_keeps_labels_apart() {
+        let metrics = InMemoryMetrics::default();
+        metrics.incr_counter(PROOFS_VERIFIED, &[("outcome", "valid")]);
+        metrics.incr_counter(PROOFS_VERIFIED, &[("outcome", "valid")]);
+        metrics.incr_counter(PROOFS_VERIFIED, &[("outcome", "invalid")]);
+        metrics.observe(SQUARINGS_PER_SECOND, 10.0, &[]);
+        metrics.observe(SQUARINGS_PER_SECOND, 20.0, &[]);
+        metrics.add(ACTIVE_SESSIONS, 1.0, &[]);
+        metrics.add(ACTIVE_SESSIONS, 1.0, &[]);
+        metrics.add(ACTIVE_SESSIONS, -1.0, &[]);
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(
+            snapshot.counter(PROOFS_VERIFIED, &[("outcome", "valid")]),
+            2
+        );
+        assert_eq!(
+            snapshot.counter(PROOFS_VERIFIED, &[("outcome", "invalid")]),
+            1
+        );
+        assert_eq!(snapshot.counter(CAP_REJECTIONS, &[]), 0);
+        assert_eq!(snapshot.value(SQUARINGS_PER_SECOND, &[]), Some(20.0));
+        assert_eq!(snapshot.value(ACTIVE_SESSIONS, &[]), Some(1.0));
+        assert_eq!(key("m", &[("b", "2"), ("a", "1")]), "m{a=\"1\",b=\"2\"}");
+    }
+}
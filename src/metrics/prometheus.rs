@@ -0,0 +1,196 @@
+//! Registers the metrics with a Prometheus registry and serves them over
+//! HTTP at /metrics
+
+use super::MetricsSink;
+use ::prometheus::{
+    Encoder, Gauge, GaugeVec, IntCounterVec, Opts, Registry, TextEncoder,
+};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// How long a connection may take to send its request line
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A MetricsSink that registers each metric with the registry on first use
+pub struct PrometheusMetrics {
+    registry: Registry,
+    counters: Mutex<HashMap<String, IntCounterVec>>,
+    gauges: Mutex<HashMap<String, GaugeVec>>,
+}
+
+impl Default for PrometheusMetrics {
+    fn default() -> Self {
+        Self::new(Registry::new())
+    }
+}
+
+impl fmt::Debug for PrometheusMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PrometheusMetrics").finish()
+    }
+}
+
+fn split<'a>(labels: &'a [(&'a str, &'a str)]) -> (Vec<&'a str>, Vec<&'a str>) {
+    labels.iter().cloned().unzip()
+}
+
+impl PrometheusMetrics {
+    pub fn new(registry: Registry) -> Self {
+        Self {
+            registry,
+            counters: Mutex::new(HashMap::new()),
+            gauges: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    /// The metrics in the Prometheus text format
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .expect("Metrics are always encodable");
+        String::from_utf8(buffer).expect("The text format is UTF-8")
+    }
+
+    /// Serves the metrics at /metrics from a background thread, returning the
+    /// bound address. Each connection gets a thread of its own, so a client
+    /// that doesn't send its request doesn't hold up the others.
+    pub fn serve<A: ToSocketAddrs>(
+        self: Arc<Self>,
+        address: A,
+    ) -> io::Result<SocketAddr> {
+        let listener = TcpListener::bind(address)?;
+        let address = listener.local_addr()?;
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let metrics = self.clone();
+                thread::spawn(move || {
+                    if let Err(error) = metrics.respond(stream) {
+                        debug!("Couldn't serve metrics: {}", error);
+                    }
+                });
+            }
+        });
+        Ok(address)
+    }
+
+    /// Runs f on the labeled gauge, registering the gauge on first use
+    fn with_gauge<F: FnOnce(&Gauge)>(
+        &self,
+        name: &str,
+        labels: &[(&str, &str)],
+        f: F,
+    ) {
+        let (names, values) = split(labels);
+        let mut gauges = self.gauges.lock().unwrap();
+        let gauge = gauges.entry(name.to_string()).or_insert_with(|| {
+            let gauge = GaugeVec::new(Opts::new(name, name), &names)
+                .expect("Metric names are valid");
+            if let Err(error) = self.registry.register(Box::new(gauge.clone()))
+            {
+                error!("Couldn't register {}: {}", name, error);
+            }
+            gauge
+        });
+        match gauge.get_metric_with_label_values(&values) {
+            Ok(gauge) => f(&gauge),
+            Err(error) => error!("Couldn't set {}: {}", name, error),
+        }
+    }
+
+    fn respond(&self, mut stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+        let mut request_line = String::new();
+        BufReader::new(&stream).read_line(&mut request_line)?;
+        let (status, body) = match request_line.split_whitespace().nth(1) {
+            Some("/metrics") => ("200 OK", self.render()),
+            _ => ("404 Not Found", String::new()),
+        };
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        )
+    }
+}
+
+impl MetricsSink for PrometheusMetrics {
+    fn incr_counter(&self, name: &str, labels: &[(&str, &str)]) {
+        let (names, values) = split(labels);
+        let mut counters = self.counters.lock().unwrap();
+        let counter = counters.entry(name.to_string()).or_insert_with(|| {
+            let counter = IntCounterVec::new(Opts::new(name, name), &names)
+                .expect("Metric names are valid");
+            if let Err(error) =
+                self.registry.register(Box::new(counter.clone()))
+            {
+                error!("Couldn't register {}: {}", name, error);
+            }
+            counter
+        });
+        match counter.get_metric_with_label_values(&values) {
+            Ok(counter) => counter.inc(),
+            Err(error) => error!("Couldn't increment {}: {}", name, error),
+        }
+    }
+
+    fn observe(&self, name: &str, value: f64, labels: &[(&str, &str)]) {
+        self.with_gauge(name, labels, |gauge| gauge.set(value));
+    }
+
+    fn add(&self, name: &str, delta: f64, labels: &[(&str, &str)]) {
+        self.with_gauge(name, labels, |gauge| gauge.add(delta));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::{PROOFS_VERIFIED, SQUARINGS_PER_SECOND};
+    use std::io::Read;
+
+    #[test]
+    fn exports_the_metrics() {
+        let metrics = Arc::new(PrometheusMetrics::default());
+        metrics.incr_counter(PROOFS_VERIFIED, &[("outcome", "valid")]);
+        metrics.observe(SQUARINGS_PER_SECOND, 1234.0, &[]);
+
+        let address = metrics.clone().serve("127.0.0.1:0").unwrap();
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(b"GET /metrics HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(
+            response.contains("pol_proofs_verified_total{outcome=\"valid\"} 1")
+        );
+        assert!(response.contains("pol_squarings_per_second 1234"));
+    }
+
+    #[test]
+    fn idle_connections_dont_block_others() {
+        let metrics = Arc::new(PrometheusMetrics::default());
+        let address = metrics.serve("127.0.0.1:0").unwrap();
+        let _idle = TcpStream::connect(address).unwrap();
+
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.set_read_timeout(Some(READ_TIMEOUT / 2)).unwrap();
+        stream.write_all(b"GET /metrics HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+    }
+}
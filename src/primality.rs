@@ -0,0 +1,992 @@
+use crate::util;
+use crossbeam::channel::{bounded, Receiver};
+use lazy_static::lazy_static;
+use ramp::Int;
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+use rkyv::{Archive, Deserialize, Serialize};
+use std::mem;
+use std::thread;
+use subtle::Choice;
+
+lazy_static! {
+    static ref ONE: Int = Int::from(1);
+    static ref TWO: Int = Int::from(2);
+    static ref FOUR: Int = Int::from(4);
+    static ref EIGHT: Int = Int::from(8);
+    static ref SMALL_PRIMES: Vec<Int> = small_primes(1000);
+    static ref CERTIFICATE_PRIMES: Vec<Int> = small_primes(1 << 16);
+}
+
+/// Bases for which Miller-Rabin is deterministic for every n < 2^64
+const U64_BASES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+
+/// (a * b) mod m without overflow
+fn mul_mod_u64(a: u64, b: u64, m: u64) -> u64 {
+    (a as u128 * b as u128 % m as u128) as u64
+}
+
+/// base^exp mod m by square-and-multiply
+fn pow_mod_u64(mut base: u64, mut exp: u64, m: u64) -> u64 {
+    let mut result = 1;
+    base %= m;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = mul_mod_u64(result, base, m);
+        }
+        base = mul_mod_u64(base, base, m);
+        exp >>= 1;
+    }
+    result
+}
+
+/// Deterministic primality for 64-bit integers, using Miller-Rabin with a
+/// base set that has no strong pseudoprimes below 2^64
+pub fn is_prime_u64(n: u64) -> bool {
+    if n < 2 {
+        return false;
+    }
+    for p in U64_BASES.iter() {
+        if n % p == 0 {
+            return n == *p;
+        }
+    }
+    let s = (n - 1).trailing_zeros();
+    let d = (n - 1) >> s;
+    U64_BASES.iter().all(|base| {
+        let mut x = pow_mod_u64(*base, d, n);
+        if x == 1 || x == n - 1 {
+            return true;
+        }
+        for _ in 1..s {
+            x = mul_mod_u64(x, x, n);
+            if x == n - 1 {
+                return true;
+            }
+        }
+        false
+    })
+}
+
+/// Primes below bound, found by trial division
+fn small_primes(bound: usize) -> Vec<Int> {
+    (2..bound)
+        .filter(|i| (2..).take_while(|j| j * j <= *i).all(|j| i % j != 0))
+        .map(Int::from)
+        .collect()
+}
+
+/// Returns n as a u64 when it fits
+fn to_u64(n: &Int) -> Option<u64> {
+    let bytes = util::int_to_be_bytes_padded(n, 8).ok()?;
+    let mut buffer = [0u8; 8];
+    buffer.copy_from_slice(&bytes);
+    Some(u64::from_be_bytes(buffer))
+}
+
+/// Trial divides n by the small primes. Returns Some when that alone decides
+/// the primality of n.
+fn trial_division(n: &Int) -> Option<bool> {
+    if n < &*TWO {
+        return Some(false);
+    }
+    for p in SMALL_PRIMES.iter() {
+        if n == p {
+            return Some(true);
+        }
+        if n % p == 0 {
+            return Some(false);
+        }
+    }
+    None
+}
+
+/// Splits n - 1 into d * 2^s where d is odd
+fn decompose(n: &Int) -> (Int, u32) {
+    let mut d = n - &*ONE;
+    let mut s = 0;
+    while d.is_even() {
+        d = d >> 1;
+        s += 1;
+    }
+    (d, s)
+}
+
+/// A single round of Miller-Rabin with the given base
+fn is_strong_probable_prime(n: &Int, d: &Int, s: u32, base: &Int) -> bool {
+    let n_minus_one = n - &*ONE;
+    let mut x = base.pow_mod(d, n);
+    if x == 1 || x == n_minus_one {
+        return true;
+    }
+    for _ in 1..s {
+        x = &x * &x % n;
+        if x == n_minus_one {
+            return true;
+        }
+        if x == 1 {
+            return false;
+        }
+    }
+    false
+}
+
+/// Draws a practically uniform integer from [0, bound)
+fn random_below<R: RngCore>(bound: &Int, rng: &mut R) -> Int {
+    let mut bytes = vec![0u8; (bound.bit_length() as usize + 7) / 8 + 8];
+    rng.fill_bytes(&mut bytes);
+    util::int_from_be_bytes(&bytes) % bound
+}
+
+/// Draws a uniformly distributed Miller-Rabin base from [2, n - 2]
+fn random_base<R: RngCore>(n: &Int, rng: &mut R) -> Int {
+    random_below(&(n - Int::from(3)), rng) + &*TWO
+}
+
+/// Miller-Rabin with the given amount of rounds, drawing the bases from rng.
+/// Composites pass with a probability of at most 4^-rounds. Candidates that
+/// fit in 64 bits are decided deterministically instead.
+pub fn is_probable_prime_with_rng<R: RngCore>(
+    n: &Int,
+    rounds: u32,
+    rng: &mut R,
+) -> bool {
+    if let Some(small) = to_u64(n) {
+        return is_prime_u64(small);
+    }
+    if let Some(decided) = trial_division(n) {
+        return decided;
+    }
+    let (d, s) = decompose(n);
+    (0..rounds)
+        .all(|_| is_strong_probable_prime(n, &d, s, &random_base(n, rng)))
+}
+
+/// Miller-Rabin with the given amount of rounds and thread local randomness
+pub fn is_probable_prime(n: &Int, rounds: u32) -> bool {
+    is_probable_prime_with_rng(n, rounds, &mut rand::thread_rng())
+}
+
+/// Checks whether n is a perfect square with Newton's method
+fn is_square(n: &Int) -> bool {
+    let mut x = n.clone();
+    let mut y = (&x + &*ONE) >> 1;
+    while y < x {
+        x = y;
+        y = (&x + n / &x) >> 1;
+    }
+    &x * &x == *n
+}
+
+/// Jacobi symbol (a/n) for an odd positive n
+fn jacobi(a: &Int, n: &Int) -> i32 {
+    let mut a = a % n;
+    let mut n = n.clone();
+    let mut result = 1;
+    while a != 0 {
+        while a.is_even() {
+            a = a >> 1;
+            let r = &n % &*EIGHT;
+            if r == 3 || r == 5 {
+                result = -result;
+            }
+        }
+        mem::swap(&mut a, &mut n);
+        if &a % &*FOUR == 3 && &n % &*FOUR == 3 {
+            result = -result;
+        }
+        a = a % &n;
+    }
+    if n == 1 {
+        result
+    } else {
+        0
+    }
+}
+
+/// Maps a possibly negative small integer into [0, n)
+fn signed_mod(value: i64, n: &Int) -> Int {
+    if value >= 0 {
+        Int::from(value) % n
+    } else {
+        (n - Int::from(-value) % n) % n
+    }
+}
+
+/// (a - b) mod n for a and b already reduced mod n
+fn sub_mod(a: &Int, b: &Int, n: &Int) -> Int {
+    (a + n - b) % n
+}
+
+/// x / 2 mod n for an odd n
+fn half_mod(x: Int, n: &Int) -> Int {
+    let x = if x.is_even() { x } else { x + n };
+    (x >> 1) % n
+}
+
+/// Strong Lucas probable prime test with Selfridge's parameters P = 1,
+/// Q = (1 - D) / 4
+fn is_strong_lucas_probable_prime(n: &Int) -> bool {
+    if is_square(n) {
+        return false;
+    }
+
+    // Selfridge's method A: the first D in 5, -7, 9, -11, ... with (D/n) = -1
+    let mut d: i64 = 5;
+    let d_mod = loop {
+        let d_mod = signed_mod(d, n);
+        match jacobi(&d_mod, n) {
+            -1 => break d_mod,
+            0 if Int::from(d.abs()) != *n => return false,
+            _ => d = if d > 0 { -d - 2 } else { -d + 2 },
+        }
+    };
+    let q_mod = signed_mod((1 - d) / 4, n);
+
+    // n + 1 = k * 2^s where k is odd
+    let mut k = n + &*ONE;
+    let mut s = 0;
+    while k.is_even() {
+        k = k >> 1;
+        s += 1;
+    }
+
+    // Walk the bits of k, starting from U_1 = 1, V_1 = P = 1, Q^1 = Q
+    let mut u = Int::from(1);
+    let mut v = Int::from(1);
+    let mut q_k = q_mod.clone();
+    for i in (0..k.bit_length() - 1).rev() {
+        // U_2k = U_k V_k, V_2k = V_k^2 - 2Q^k
+        u = &u * &v % n;
+        v = sub_mod(&(&v * &v % n), &(&q_k * &*TWO % n), n);
+        q_k = &q_k * &q_k % n;
+        if k.bit(i) {
+            // U_2k+1 = (U_2k + V_2k) / 2, V_2k+1 = (D U_2k + V_2k) / 2
+            let next_u = half_mod(&u + &v, n);
+            v = half_mod(&d_mod * &u + &v, n);
+            u = next_u;
+            q_k = q_k * &q_mod % n;
+        }
+    }
+
+    if u == 0 || v == 0 {
+        return true;
+    }
+    for _ in 1..s {
+        v = sub_mod(&(&v * &v % n), &(&q_k * &*TWO % n), n);
+        q_k = &q_k * &q_k % n;
+        if v == 0 {
+            return true;
+        }
+    }
+    false
+}
+
+/// Baillie-PSW: a base 2 Miller-Rabin round followed by a strong Lucas test.
+/// Deterministic, and no composite passing it is known.
+pub fn is_baillie_psw(n: &Int) -> bool {
+    if let Some(decided) = trial_division(n) {
+        return decided;
+    }
+    let (d, s) = decompose(n);
+    is_strong_probable_prime(n, &d, s, &*TWO)
+        && is_strong_lucas_probable_prime(n)
+}
+
+/// Checks that both n and (n - 1) / 2 are prime
+pub fn is_safe_prime(n: &Int) -> bool {
+    is_baillie_psw(n) && is_baillie_psw(&((n - &*ONE) >> 1))
+}
+
+/// Draws a random odd integer of exactly bits bits
+fn random_odd<R: RngCore>(bits: usize, rng: &mut R) -> Int {
+    let mut bytes = vec![0u8; (bits + 7) / 8];
+    rng.fill_bytes(&mut bytes);
+    let mut candidate =
+        util::int_from_be_bytes(&bytes) >> (bytes.len() * 8 - bits);
+    candidate.set_bit(bits as u32 - 1, true);
+    candidate.set_bit(0, true);
+    candidate
+}
+
+/// Draws a uniformly random integer below 2^bits
+pub fn gen_uint<R: RngCore>(bits: usize, rng: &mut R) -> Int {
+    let mut bytes = vec![0u8; (bits + 7) / 8];
+    rng.fill_bytes(&mut bytes);
+    util::int_from_be_bytes(&bytes) >> (bytes.len() * 8 - bits)
+}
+
+/// Generates a random prime of exactly bits bits
+pub fn gen_prime<R: RngCore>(bits: usize, rng: &mut R) -> Int {
+    assert!(bits >= 2, "There are no primes below 2 bits");
+    loop {
+        let candidate = random_odd(bits, rng);
+        if is_baillie_psw(&candidate) {
+            return candidate;
+        }
+    }
+}
+
+/// Generates a random safe prime p = 2q + 1 of exactly bits bits
+pub fn gen_safe_prime<R: RngCore>(bits: usize, rng: &mut R) -> Int {
+    assert!(bits >= 3, "There are no safe primes below 3 bits");
+    loop {
+        let q = random_odd(bits - 1, rng);
+        if !is_baillie_psw(&q) {
+            continue;
+        }
+        let p = (q << 1) + &*ONE;
+        if is_baillie_psw(&p) {
+            return p;
+        }
+    }
+}
+
+/// Finds a Pocklington witness a for the prime factor q of n - 1, meaning
+/// a^(n-1) = 1 mod n and gcd(a^((n-1)/q) - 1, n) = 1
+fn find_witness(n: &Int, factor: &Int) -> Option<Int> {
+    let n_minus_one = n - &*ONE;
+    let exponent = &n_minus_one / factor;
+    (2..1000).map(Int::from).find(|a| {
+        a.pow_mod(&n_minus_one, n) == 1
+            && (a.pow_mod(&exponent, n) - &*ONE).gcd(n) == 1
+    })
+}
+
+/// A single Pocklington step. The listed prime factors of prime - 1 must
+/// cover more than the square root of prime, and each of them comes with a
+/// witness.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CertificateStep {
+    pub prime: Int,
+    pub factors: Vec<Int>,
+    pub witnesses: Vec<Int>,
+}
+
+/// A deserializable CertificateStep because ramp::Int is not deserializable
+#[derive(
+    Archive,
+    Debug,
+    Deserialize,
+    Serialize,
+    Clone,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub struct DeserializableCertificateStep {
+    pub prime: String,
+    pub factors: Vec<String>,
+    pub witnesses: Vec<String>,
+}
+
+impl DeserializableCertificateStep {
+    pub fn serialize(&self) -> CertificateStep {
+        self.try_serialize().unwrap()
+    }
+
+    /// Like serialize, but fails on numbers that don't parse or are negative
+    pub fn try_serialize(
+        &self,
+    ) -> Result<CertificateStep, util::ParseUintError> {
+        let parse = |num: &String| util::uint_from_str(num);
+        Ok(CertificateStep {
+            prime: parse(&self.prime)?,
+            factors: self
+                .factors
+                .iter()
+                .map(parse)
+                .collect::<Result<_, _>>()?,
+            witnesses: self
+                .witnesses
+                .iter()
+                .map(parse)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl CertificateStep {
+    /// Finds the witnesses for the given prime factors of prime - 1
+    fn new(prime: Int, factors: Vec<Int>) -> Option<Self> {
+        let witnesses = factors
+            .iter()
+            .map(|factor| find_witness(&prime, factor))
+            .collect::<Option<Vec<Int>>>()?;
+        Some(Self {
+            prime,
+            factors,
+            witnesses,
+        })
+    }
+
+    pub fn deserialize(&self) -> DeserializableCertificateStep {
+        let format = |num: &Int| num.to_str_radix(10, false);
+        DeserializableCertificateStep {
+            prime: format(&self.prime),
+            factors: self.factors.iter().map(format).collect(),
+            witnesses: self.witnesses.iter().map(format).collect(),
+        }
+    }
+
+    /// Checks the step, given the primes proven by earlier steps. Factors
+    /// below 2^64 are checked deterministically.
+    fn check(&self, proven: &[&Int]) -> bool {
+        let n = &self.prime;
+        if n <= &*TWO
+            || self.factors.is_empty()
+            || self.factors.len() != self.witnesses.len()
+        {
+            return false;
+        }
+
+        let n_minus_one = n - &*ONE;
+        let mut rest = n_minus_one.clone();
+        for (factor, witness) in self.factors.iter().zip(self.witnesses.iter())
+        {
+            let factor_is_prime = to_u64(factor).map_or(false, is_prime_u64)
+                || proven.contains(&factor);
+            // Each factor has to divide the part of n - 1 not covered yet,
+            // which also rules out listing a factor twice
+            if !factor_is_prime
+                || &rest % factor != 0
+                || witness < &*TWO
+                || witness >= n
+            {
+                return false;
+            }
+            while &rest % factor == 0 {
+                rest = rest / factor;
+            }
+
+            if witness.pow_mod(&n_minus_one, n) != 1
+                || (witness.pow_mod(&(&n_minus_one / factor), n) - &*ONE).gcd(n)
+                    != 1
+            {
+                return false;
+            }
+        }
+
+        let covered = &n_minus_one / &rest;
+        &covered * &covered > *n
+    }
+}
+
+/// A Pocklington certificate of primality. Checking it takes a handful of
+/// modular exponentiations, and unlike probabilistic tests it can't be fooled.
+/// The steps are ordered so that every factor above 2^64 is the prime of an
+/// earlier step, and the last step proves the certified prime itself. Primes
+/// below 2^64 need no steps.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PrimeCertificate {
+    pub steps: Vec<CertificateStep>,
+}
+
+/// A deserializable PrimeCertificate because ramp::Int is not deserializable
+#[derive(
+    Archive,
+    Debug,
+    Deserialize,
+    Serialize,
+    Clone,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub struct DeserializablePrimeCertificate {
+    pub steps: Vec<DeserializableCertificateStep>,
+}
+
+impl DeserializablePrimeCertificate {
+    pub fn serialize(&self) -> PrimeCertificate {
+        self.try_serialize().unwrap()
+    }
+
+    /// Like serialize, but fails on numbers that don't parse or are negative
+    pub fn try_serialize(
+        &self,
+    ) -> Result<PrimeCertificate, util::ParseUintError> {
+        Ok(PrimeCertificate {
+            steps: self
+                .steps
+                .iter()
+                .map(|step| step.try_serialize())
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl PrimeCertificate {
+    pub fn deserialize(&self) -> DeserializablePrimeCertificate {
+        DeserializablePrimeCertificate {
+            steps: self.steps.iter().map(|step| step.deserialize()).collect(),
+        }
+    }
+
+    /// Compares the certificates in constant time, the Ints padded to len
+    /// bytes like util::ct_eq. Only the amounts of steps, factors and
+    /// witnesses are compared in variable time.
+    pub fn ct_eq(&self, other: &Self, len: usize) -> Choice {
+        let all_eq = |a: &[Int], b: &[Int]| {
+            a.iter().zip(b.iter()).fold(
+                Choice::from(u8::from(a.len() == b.len())),
+                |eq, (x, y)| eq & util::ct_eq(x, y, len),
+            )
+        };
+        self.steps.iter().zip(other.steps.iter()).fold(
+            Choice::from(u8::from(self.steps.len() == other.steps.len())),
+            |eq, (a, b)| {
+                eq & util::ct_eq(&a.prime, &b.prime, len)
+                    & all_eq(&a.factors, &b.factors)
+                    & all_eq(&a.witnesses, &b.witnesses)
+            },
+        )
+    }
+
+    /// Checks that the certificate proves p prime
+    pub fn check(&self, p: &Int) -> bool {
+        if let Some(small) = to_u64(p) {
+            return is_prime_u64(small);
+        }
+        let mut proven: Vec<&Int> = Vec::with_capacity(self.steps.len());
+        for step in self.steps.iter() {
+            if !step.check(&proven) {
+                return false;
+            }
+            proven.push(&step.prime);
+        }
+        proven.last().map_or(false, |last| *last == p)
+    }
+}
+
+/// Tries to certify p by factoring p - 1 with small primes, recursing into
+/// the remaining cofactor when the small factors don't cover sqrt(p)
+fn certify_into(p: &Int, steps: &mut Vec<CertificateStep>) -> bool {
+    if let Some(small) = to_u64(p) {
+        return is_prime_u64(small);
+    }
+    if !is_baillie_psw(p) {
+        return false;
+    }
+
+    let mut rest = p - &*ONE;
+    let mut covered = Int::from(1);
+    let mut factors = Vec::new();
+    for q in CERTIFICATE_PRIMES.iter() {
+        if &covered * &covered > *p {
+            break;
+        }
+        if &rest % q == 0 {
+            while &rest % q == 0 {
+                rest = rest / q;
+                covered = covered * q;
+            }
+            factors.push(q.clone());
+        }
+    }
+    if &covered * &covered <= *p {
+        if rest == 1 || !certify_into(&rest, steps) {
+            return false;
+        }
+        factors.push(rest);
+    }
+
+    match CertificateStep::new(p.clone(), factors) {
+        Some(step) => {
+            steps.push(step);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Creates a primality certificate for p. This only succeeds when p - 1
+/// factors far enough with small primes and provable cofactors, which is
+/// unlikely for random primes. Use gen_certified_safe_prime to generate
+/// primes that come with a certificate.
+pub fn certify_prime(p: &Int) -> Option<PrimeCertificate> {
+    let mut steps = Vec::new();
+    if certify_into(p, &mut steps) {
+        Some(PrimeCertificate { steps })
+    } else {
+        None
+    }
+}
+
+/// Draws p = 2ks + 1 of exactly bits bits for a random k
+fn random_with_factor<R: RngCore>(bits: usize, s: &Int, rng: &mut R) -> Int {
+    let two_s = s * &*TWO;
+    let top = Int::from(1) << (bits - 1);
+    let lowest = (&top - &*ONE) / &two_s + &*ONE;
+    let highest = ((top << 1) - &*TWO) / &two_s;
+    let k = random_below(&(&highest - &lowest + &*ONE), rng) + lowest;
+    k * &two_s + &*ONE
+}
+
+/// Generates a prime of exactly bits bits as p = 2ks + 1 around a recursively
+/// generated prime s > sqrt(p), pushing the steps proving it
+fn gen_certified_prime<R: RngCore>(
+    bits: usize,
+    rng: &mut R,
+    steps: &mut Vec<CertificateStep>,
+) -> Int {
+    if bits <= 64 {
+        return gen_prime(bits, rng);
+    }
+    let s = gen_certified_prime(bits / 2 + 2, rng, steps);
+    loop {
+        let p = random_with_factor(bits, &s, rng);
+        if !is_baillie_psw(&p) {
+            continue;
+        }
+        if let Some(step) = CertificateStep::new(p.clone(), vec![s.clone()]) {
+            steps.push(step);
+            return p;
+        }
+    }
+}
+
+/// Generates a random safe prime of exactly bits bits together with a
+/// certificate of its primality
+pub fn gen_certified_safe_prime<R: RngCore>(
+    bits: usize,
+    rng: &mut R,
+) -> (Int, PrimeCertificate) {
+    assert!(bits >= 3, "There are no safe primes below 3 bits");
+    let mut steps = Vec::new();
+
+    // (p - 1) / 2 fits in 64 bits, so it proves p on its own
+    if bits <= 65 {
+        loop {
+            let p = gen_safe_prime(bits, rng);
+            if to_u64(&p).is_some() {
+                return (p, PrimeCertificate { steps });
+            }
+            let q = (&p - &*ONE) >> 1;
+            if let Some(step) = CertificateStep::new(p.clone(), vec![q]) {
+                steps.push(step);
+                return (p, PrimeCertificate { steps });
+            }
+        }
+    }
+
+    let q_bits = bits - 1;
+    let s = gen_certified_prime(q_bits / 2 + 2, rng, &mut steps);
+    loop {
+        let q = random_with_factor(q_bits, &s, rng);
+        if !is_baillie_psw(&q) {
+            continue;
+        }
+        let p = &q * &*TWO + &*ONE;
+        if !is_baillie_psw(&p) {
+            continue;
+        }
+        if let (Some(q_step), Some(p_step)) = (
+            CertificateStep::new(q.clone(), vec![s.clone()]),
+            CertificateStep::new(p.clone(), vec![q]),
+        ) {
+            steps.push(q_step);
+            steps.push(p_step);
+            return (p, PrimeCertificate { steps });
+        }
+    }
+}
+
+/// A pool of certified safe primes generated ahead of time on a background
+/// thread, so that drawing a cap doesn't stall the caller
+#[derive(Debug)]
+pub struct PrimePool {
+    bits: usize,
+    receiver: Receiver<(Int, PrimeCertificate)>,
+}
+
+impl PrimePool {
+    /// Starts filling a pool of at most capacity safe primes of the given bit
+    /// length. The generator thread stops once the pool is dropped.
+    pub fn new(bits: usize, capacity: usize) -> Self {
+        let (sender, receiver) = bounded(capacity);
+        thread::spawn(move || {
+            let mut rng = StdRng::from_entropy();
+            loop {
+                let prime = gen_certified_safe_prime(bits, &mut rng);
+                if sender.send(prime).is_err() {
+                    break;
+                }
+            }
+        });
+        Self { bits, receiver }
+    }
+
+    /// Takes a pre-generated safe prime, generating one inline if the pool
+    /// has run dry
+    pub fn take(&self) -> Int {
+        self.take_certified().0
+    }
+
+    /// Takes a pre-generated safe prime along with its certificate
+    pub fn take_certified(&self) -> (Int, PrimeCertificate) {
+        match self.receiver.try_recv() {
+            Ok(prime) => prime,
+            Err(_) => {
+                debug!("Prime pool empty, generating a safe prime inline");
+                gen_certified_safe_prime(self.bits, &mut rand::thread_rng())
+            }
+        }
+    }
+
+    /// Bit length of the primes in the pool
+    pub fn bits(&self) -> usize {
+        self.bits
+    }
+
+    /// Amount of primes currently in stock
+    pub fn len(&self) -> usize {
+        self.receiver.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.receiver.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ramp_primes::{Generator, Verification};
+    use std::time::{Duration, Instant};
+
+    const PRIMES: [&str; 7] = [
+        "2",
+        "7919",
+        "2305843009213693951",
+        "18446744073709550147",
+        "618970019642690137449562111",
+        "170141183460469231731687303715884105727",
+        "170141183460469231731687303715884114527",
+    ];
+
+    // Carmichael numbers and strong pseudoprimes with no small factors
+    const PSEUDOPRIMES: [&str; 4] = [
+        "9624742921",
+        "11346205609",
+        "13079177569",
+        "3825123056546413051",
+    ];
+
+    fn int(s: &str) -> Int {
+        Int::from_str_radix(s, 10).unwrap()
+    }
+
+    #[test]
+    fn accepts_known_primes() {
+        let mut rng = StdRng::seed_from_u64(42);
+        for p in PRIMES.iter() {
+            assert!(is_baillie_psw(&int(p)));
+            assert!(is_probable_prime_with_rng(&int(p), 20, &mut rng));
+        }
+    }
+
+    #[test]
+    fn rejects_carmichael_numbers_and_pseudoprimes() {
+        let mut rng = StdRng::seed_from_u64(42);
+        for n in PSEUDOPRIMES.iter() {
+            assert!(!is_baillie_psw(&int(n)));
+            assert!(!is_probable_prime_with_rng(&int(n), 20, &mut rng));
+        }
+        for n in [0, 1, 4, 561, 1105, 1729, 2047].iter() {
+            assert!(!is_baillie_psw(&Int::from(*n)));
+        }
+    }
+
+    #[test]
+    fn u64_primality_matches_a_sieve() {
+        let bound = 1_000_000;
+        let mut sieve = vec![true; bound];
+        sieve[0] = false;
+        sieve[1] = false;
+        let mut i = 2;
+        while i * i < bound {
+            if sieve[i] {
+                (i * i..bound).step_by(i).for_each(|j| sieve[j] = false);
+            }
+            i += 1;
+        }
+        for (n, is_prime) in sieve.iter().enumerate() {
+            assert_eq!(is_prime_u64(n as u64), *is_prime);
+        }
+    }
+
+    #[test]
+    fn u64_primality_handles_strong_pseudoprimes() {
+        // Strong pseudoprimes to the first few prime bases
+        for n in
+            [3_215_031_751, 2_152_302_898_747, 3_825_123_056_546_413_051].iter()
+        {
+            assert!(!is_prime_u64(*n));
+            assert!(!is_probable_prime(&Int::from(*n), 1));
+        }
+        assert!(is_prime_u64(18_446_744_073_709_551_557));
+        assert!(is_prime_u64(2_305_843_009_213_693_951));
+        assert!(!is_prime_u64(u64::MAX));
+    }
+
+    #[test]
+    fn detects_safe_primes() {
+        for (p, q) in [(5, 2), (7, 3), (23, 11), (107, 53), (179, 89)].iter() {
+            assert!(is_safe_prime(&Int::from(*p)));
+            assert!(is_baillie_psw(&Int::from(*q)));
+        }
+        assert!(is_safe_prime(&int("18446744073709550147")));
+        assert!(is_safe_prime(&int(
+            "170141183460469231731687303715884114527"
+        )));
+
+        // Primes whose (p - 1) / 2 is composite
+        assert!(!is_safe_prime(&Int::from(13)));
+        assert!(!is_safe_prime(&Int::from(53)));
+        assert!(!is_safe_prime(&int(
+            "170141183460469231731687303715884105727"
+        )));
+    }
+
+    #[test]
+    fn generates_primes_of_the_requested_length() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for bits in [2, 8, 64, 65, 128].iter() {
+            let prime = gen_prime(*bits, &mut rng);
+            assert_eq!(prime.bit_length() as usize, *bits);
+            assert!(is_baillie_psw(&prime));
+        }
+    }
+
+    #[test]
+    fn generates_safe_primes_of_the_requested_length() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for bits in [3, 16, 64, 128].iter() {
+            let prime = gen_safe_prime(*bits, &mut rng);
+            assert_eq!(prime.bit_length() as usize, *bits);
+            assert!(is_baillie_psw(&prime));
+            assert!(is_baillie_psw(&((&prime - &*ONE) >> 1)));
+        }
+    }
+
+    #[test]
+    fn generation_is_reproducible_with_a_seed() {
+        let first = gen_safe_prime(128, &mut StdRng::seed_from_u64(1234));
+        let second = gen_safe_prime(128, &mut StdRng::seed_from_u64(1234));
+        let other = gen_safe_prime(128, &mut StdRng::seed_from_u64(4321));
+        assert_eq!(first, second);
+        assert_ne!(first, other);
+    }
+
+    #[test]
+    fn certifies_primes_with_smooth_predecessors() {
+        let mersenne = int("618970019642690137449562111");
+        let certificate = certify_prime(&mersenne).unwrap();
+        assert!(certificate.check(&mersenne));
+        assert!(
+            !certificate.check(&int("170141183460469231731687303715884105727"))
+        );
+
+        let small = Int::from(7919);
+        assert!(certify_prime(&small).unwrap().check(&small));
+    }
+
+    #[test]
+    fn generated_certificates_check() {
+        let mut rng = StdRng::seed_from_u64(11);
+        for bits in [3, 64, 65, 66, 128, 256].iter() {
+            let (prime, certificate) =
+                gen_certified_safe_prime(*bits, &mut rng);
+            assert_eq!(prime.bit_length() as usize, *bits);
+            assert!(is_safe_prime(&prime));
+            assert!(certificate.check(&prime));
+            assert_eq!(certificate.deserialize().serialize(), certificate);
+        }
+    }
+
+    #[test]
+    fn rejects_forged_certificates() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let (prime, certificate) = gen_certified_safe_prime(128, &mut rng);
+
+        let mut forged = certificate.clone();
+        forged.steps.last_mut().unwrap().witnesses[0] = Int::from(1);
+        assert!(!forged.check(&prime));
+
+        // Listing the same factor twice must not double its coverage
+        let mut forged = certificate.clone();
+        let last = forged.steps.last_mut().unwrap();
+        last.factors.push(last.factors[0].clone());
+        last.witnesses.push(last.witnesses[0].clone());
+        assert!(!forged.check(&prime));
+
+        // A step leaning on a factor no earlier step has proven
+        let mut forged = certificate.clone();
+        forged.steps.remove(0);
+        assert!(!forged.check(&prime));
+
+        assert!(!PrimeCertificate::default().check(&prime));
+    }
+
+    #[test]
+    fn composites_are_not_certified() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let (prime, certificate) = gen_certified_safe_prime(128, &mut rng);
+        let composite = &prime * &Int::from(3);
+
+        assert!(certify_prime(&composite).is_none());
+        assert!(certify_prime(&int("3825123056546413051")).is_none());
+        assert!(!certificate.check(&composite));
+    }
+
+    fn wait_for_stock(pool: &PrimePool, amount: usize) {
+        let timer = Instant::now();
+        while pool.len() < amount {
+            assert!(timer.elapsed() < Duration::from_secs(30));
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn prime_pool_refills_after_draining() {
+        let pool = PrimePool::new(32, 4);
+        wait_for_stock(&pool, 4);
+        for _ in 0..4 {
+            let prime = pool.take();
+            assert!(is_safe_prime(&prime));
+            assert_eq!(prime.bit_length(), 32);
+        }
+        wait_for_stock(&pool, 4);
+    }
+
+    #[test]
+    fn warm_prime_pool_takes_instantly() {
+        let pool = PrimePool::new(128, 2);
+        wait_for_stock(&pool, 1);
+        let timer = Instant::now();
+        let prime = pool.take();
+        assert!(timer.elapsed() < Duration::from_millis(1));
+        assert_eq!(prime.bit_length(), 128);
+    }
+
+    #[test]
+    fn agrees_with_ramp_primes_on_random_candidates() {
+        for _ in 0..200 {
+            let candidate = Generator::new_uint(128);
+            assert_eq!(
+                is_baillie_psw(&candidate),
+                Verification::verify_prime(candidate.clone())
+            );
+        }
+        for _ in 0..10 {
+            let prime = Generator::new_prime(128);
+            assert!(is_baillie_psw(&prime));
+            assert!(is_probable_prime(&prime, 20));
+        }
+    }
+}
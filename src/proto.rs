@@ -0,0 +1,317 @@
+//! Protobuf messages for proofs, generated from proto/proof.proto, and their
+//! conversions to and from the native types
+
+use crate::primality::{CertificateStep, PrimeCertificate};
+use crate::util;
+use crate::vdf::evaluation::VDFResult;
+use crate::vdf::proof::{
+    ProofType, VDFProof, DEFAULT_EXPONENT, DEFAULT_HASH_ALG,
+};
+use crate::vdf::InvalidProofError;
+use ramp::Int;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+
+pub mod messages {
+    include!(concat!(env!("OUT_DIR"), "/pol.proof.v1.rs"));
+}
+
+/// Longest integer accepted, in bytes. Fits moduli up to 8192 bits.
+pub const MAX_INT_BYTES: usize = util::MAX_MODULUS_BYTES;
+
+/// ProtoError is returned when a message can't be converted to a native type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProtoError {
+    MissingField(&'static str),
+    EmptyInt(&'static str),
+    IntTooLong(&'static str, usize),
+    LeadingZero(&'static str),
+    UnknownProofType(i32),
+    UnknownHashAlg(String),
+    InvalidProof(InvalidProofError),
+}
+
+impl fmt::Display for ProtoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtoError::MissingField(field) => {
+                write!(f, "The message has no {}", field)
+            }
+            ProtoError::EmptyInt(field) => write!(f, "{} is empty", field),
+            ProtoError::IntTooLong(field, len) => write!(
+                f,
+                "{} is {} bytes, the limit is {}",
+                field, len, MAX_INT_BYTES
+            ),
+            ProtoError::LeadingZero(field) => {
+                write!(f, "{} has leading zero bytes", field)
+            }
+            ProtoError::UnknownProofType(proof_type) => {
+                write!(f, "Unknown proof type {}", proof_type)
+            }
+            ProtoError::UnknownHashAlg(name) => {
+                write!(f, "Unknown hash function \"{}\"", name)
+            }
+            ProtoError::InvalidProof(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl Error for ProtoError {
+    fn description(&self) -> &str {
+        "Invalid protobuf message!"
+    }
+}
+
+/// Reads an integer in its minimal big-endian form
+fn int(field: &'static str, bytes: &[u8]) -> Result<Int, ProtoError> {
+    match bytes {
+        [] => Err(ProtoError::EmptyInt(field)),
+        [0, _, ..] => Err(ProtoError::LeadingZero(field)),
+        _ if bytes.len() > MAX_INT_BYTES => {
+            Err(ProtoError::IntTooLong(field, bytes.len()))
+        }
+        _ => Ok(util::int_from_be_bytes(bytes)),
+    }
+}
+
+fn ints(field: &'static str, all: &[Vec<u8>]) -> Result<Vec<Int>, ProtoError> {
+    all.iter().map(|bytes| int(field, bytes)).collect()
+}
+
+impl From<&VDFResult> for messages::VdfResult {
+    fn from(result: &VDFResult) -> Self {
+        Self {
+            result: util::int_to_be_bytes(&result.result),
+            iterations: result.iterations,
+        }
+    }
+}
+
+impl TryFrom<&messages::VdfResult> for VDFResult {
+    type Error = ProtoError;
+
+    fn try_from(result: &messages::VdfResult) -> Result<Self, ProtoError> {
+        Ok(Self::new(int("result", &result.result)?, result.iterations))
+    }
+}
+
+impl From<&PrimeCertificate> for messages::PrimeCertificate {
+    fn from(certificate: &PrimeCertificate) -> Self {
+        let all =
+            |ints: &[Int]| ints.iter().map(util::int_to_be_bytes).collect();
+        Self {
+            steps: certificate
+                .steps
+                .iter()
+                .map(|step| messages::CertificateStep {
+                    prime: util::int_to_be_bytes(&step.prime),
+                    factors: all(&step.factors),
+                    witnesses: all(&step.witnesses),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<&messages::PrimeCertificate> for PrimeCertificate {
+    type Error = ProtoError;
+
+    fn try_from(
+        certificate: &messages::PrimeCertificate,
+    ) -> Result<Self, ProtoError> {
+        let steps = certificate
+            .steps
+            .iter()
+            .map(|step| {
+                Ok(CertificateStep {
+                    prime: int("prime", &step.prime)?,
+                    factors: ints("factors", &step.factors)?,
+                    witnesses: ints("witnesses", &step.witnesses)?,
+                })
+            })
+            .collect::<Result<Vec<_>, ProtoError>>()?;
+        Ok(Self { steps })
+    }
+}
+
+impl From<&VDFProof> for messages::VdfProof {
+    fn from(proof: &VDFProof) -> Self {
+        let proof_type = match proof.proof_type {
+            ProofType::Sequential => messages::ProofType::Sequential,
+            ProofType::Parallel => messages::ProofType::Parallel,
+        };
+        Self {
+            modulus: util::int_to_be_bytes(&proof.modulus),
+            generator: util::int_to_be_bytes(&proof.generator),
+            output: Some((&proof.output).into()),
+            cap: util::int_to_be_bytes(&proof.cap),
+            pi: util::int_to_be_bytes(&proof.pi),
+            proof_type: proof_type as i32,
+            cap_certificate: proof.cap_certificate.as_ref().map(Into::into),
+            exponent: match proof.exponent {
+                DEFAULT_EXPONENT => 0,
+                exponent => exponent,
+            },
+            hash_alg: match proof.hash_alg {
+                DEFAULT_HASH_ALG => String::new(),
+                hash_alg => hash_alg.to_string(),
+            },
+        }
+    }
+}
+
+impl TryFrom<&messages::VdfProof> for VDFProof {
+    type Error = ProtoError;
+
+    fn try_from(proof: &messages::VdfProof) -> Result<Self, ProtoError> {
+        let output = proof
+            .output
+            .as_ref()
+            .ok_or(ProtoError::MissingField("output"))?;
+        let proof_type = match messages::ProofType::from_i32(proof.proof_type) {
+            Some(messages::ProofType::Sequential) => ProofType::Sequential,
+            Some(messages::ProofType::Parallel) => ProofType::Parallel,
+            None => return Err(ProtoError::UnknownProofType(proof.proof_type)),
+        };
+        let exponent = match proof.exponent {
+            0 => DEFAULT_EXPONENT,
+            1 => {
+                return Err(ProtoError::InvalidProof(
+                    InvalidProofError::ExponentTooSmall(1),
+                ))
+            }
+            exponent => exponent,
+        };
+        let hash_alg = match proof.hash_alg.as_str() {
+            "" => DEFAULT_HASH_ALG,
+            name => name
+                .parse()
+                .map_err(|_| ProtoError::UnknownHashAlg(name.into()))?,
+        };
+        let mut native = Self::new(
+            &int("modulus", &proof.modulus)?,
+            &int("generator", &proof.generator)?,
+            &VDFResult::try_from(output)?,
+            &int("cap", &proof.cap)?,
+            &proof_type,
+        )
+        .map_err(ProtoError::InvalidProof)?
+        .with_exponent(exponent)
+        .with_hash_alg(hash_alg);
+        native.pi = int("pi", &proof.pi)?;
+        native.cap_certificate = proof
+            .cap_certificate
+            .as_ref()
+            .map(PrimeCertificate::try_from)
+            .transpose()?;
+        Ok(native)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vdf::evaluation::VDF;
+    use crate::vdf::ModulusChoice;
+    use prost::Message;
+
+    fn proof() -> VDFProof {
+        let modulus = ModulusChoice::Rsa2048;
+        let generator =
+            util::hash_with_domain(util::SEED_DOMAIN, b"proto", modulus.int());
+        let (_, receiver) =
+            VDF::new(modulus, generator, 100, ProofType::Sequential)
+                .unwrap()
+                .with_cap_bits(80)
+                .run_vdf_worker();
+        receiver.recv().unwrap().unwrap()
+    }
+
+    #[test]
+    fn proofs_round_trip_and_still_verify() {
+        let proof = proof();
+        assert!(!proof.cap_certificate.as_ref().unwrap().steps.is_empty());
+
+        let encoded = messages::VdfProof::from(&proof).encode_to_vec();
+        let decoded = messages::VdfProof::decode(encoded.as_slice()).unwrap();
+        let native = VDFProof::try_from(&decoded).unwrap();
+        assert_eq!(native, proof);
+        assert!(native.verify());
+    }
+
+    #[test]
+    fn hash_functions_round_trip() {
+        let proof = proof();
+        assert!(messages::VdfProof::from(&proof).hash_alg.is_empty());
+
+        let blake3 = proof.with_hash_alg(util::HashAlg::Blake3);
+        let message = messages::VdfProof::from(&blake3);
+        assert_eq!(message.hash_alg, "blake3");
+        assert_eq!(VDFProof::try_from(&message), Ok(blake3));
+    }
+
+    #[test]
+    fn rejects_incomplete_messages() {
+        let message = messages::VdfProof::from(&proof());
+
+        let mut missing = message.clone();
+        missing.output = None;
+        assert_eq!(
+            VDFProof::try_from(&missing),
+            Err(ProtoError::MissingField("output"))
+        );
+
+        let mut empty = message.clone();
+        empty.cap.clear();
+        assert_eq!(
+            VDFProof::try_from(&empty),
+            Err(ProtoError::EmptyInt("cap"))
+        );
+
+        let mut long = message.clone();
+        long.pi = vec![1; MAX_INT_BYTES + 1];
+        assert_eq!(
+            VDFProof::try_from(&long),
+            Err(ProtoError::IntTooLong("pi", MAX_INT_BYTES + 1))
+        );
+
+        let mut padded = message.clone();
+        padded.generator.insert(0, 0);
+        assert_eq!(
+            VDFProof::try_from(&padded),
+            Err(ProtoError::LeadingZero("generator"))
+        );
+
+        let mut zero = message.clone();
+        zero.modulus = vec![0];
+        assert_eq!(
+            VDFProof::try_from(&zero),
+            Err(ProtoError::InvalidProof(InvalidProofError::ModulusTooSmall))
+        );
+
+        let mut linear = message.clone();
+        linear.exponent = 1;
+        assert_eq!(
+            VDFProof::try_from(&linear),
+            Err(ProtoError::InvalidProof(
+                InvalidProofError::ExponentTooSmall(1)
+            ))
+        );
+
+        let mut md5 = message.clone();
+        md5.hash_alg = "md5".into();
+        assert_eq!(
+            VDFProof::try_from(&md5),
+            Err(ProtoError::UnknownHashAlg("md5".into()))
+        );
+
+        let mut unknown = message;
+        unknown.proof_type = 7;
+        assert_eq!(
+            VDFProof::try_from(&unknown),
+            Err(ProtoError::UnknownProofType(7))
+        );
+    }
+}
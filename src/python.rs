@@ -0,0 +1,199 @@
+//! Python bindings, built into the proof_of_proximity extension module with
+//! maturin. Proofs cross the boundary in the canonical byte encoding.
+
+use crate::util;
+use crate::vdf::evaluation::VDF;
+use crate::vdf::proof::{ProofType, VDFProof};
+use crate::vdf::ModulusChoice;
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use std::convert::TryFrom;
+use std::time;
+
+create_exception!(proof_of_proximity, VdfError, PyException);
+
+fn decode(bytes: &[u8]) -> PyResult<VDFProof> {
+    VDFProof::from_bytes(bytes)
+        .map_err(|error| VdfError::new_err(error.to_string()))
+}
+
+fn generator(seed: &str) -> ramp::Int {
+    util::hash_with_domain(
+        util::SEED_DOMAIN,
+        seed.as_bytes(),
+        ModulusChoice::Rsa2048.int(),
+    )
+}
+
+/// A decoded proof
+#[pyclass(name = "Proof")]
+pub struct PyProof {
+    proof: VDFProof,
+}
+
+#[pymethods]
+impl PyProof {
+    #[new]
+    fn new(bytes: &[u8]) -> PyResult<Self> {
+        Ok(Self {
+            proof: decode(bytes)?,
+        })
+    }
+
+    #[getter]
+    fn iterations(&self) -> u32 {
+        self.proof.output.iterations
+    }
+
+    #[getter]
+    fn cap_hex(&self) -> String {
+        util::int_to_hex(&self.proof.cap)
+    }
+
+    #[getter]
+    fn result_hex(&self) -> String {
+        util::int_to_hex(&self.proof.output.result)
+    }
+
+    fn verify(&self, py: Python) -> bool {
+        let proof = &self.proof;
+        py.allow_threads(|| proof.verify())
+    }
+
+    fn to_bytes<'p>(&self, py: Python<'p>) -> &'p PyBytes {
+        PyBytes::new(py, &self.proof.to_bytes())
+    }
+}
+
+/// Measures how many squarings fit in ms milliseconds
+#[pyfunction]
+fn calibrate(py: Python, ms: u64) -> PyResult<u32> {
+    if ms == 0 {
+        return Err(VdfError::new_err("ms must be positive"));
+    }
+    let rate = py.allow_threads(|| {
+        VDF::new(
+            ModulusChoice::Rsa2048,
+            generator("calibrate"),
+            u32::MAX,
+            ProofType::Sequential,
+        )
+        .expect("The calibration parameters are valid")
+        .measure_rate(time::Duration::from_millis(ms))
+    });
+    let squarings = u128::from(rate) * u128::from(ms) / 1000;
+    Ok(u32::try_from(squarings).unwrap_or(u32::MAX))
+}
+
+/// Evaluates the VDF for upper_bound squarings and returns the proof's bytes.
+/// The cap is generated if it's not given.
+#[pyfunction(cap_hex = "None", seed = "\"pol\"")]
+fn prove<'p>(
+    py: Python<'p>,
+    upper_bound: u32,
+    cap_hex: Option<&str>,
+    seed: &str,
+) -> PyResult<&'p PyBytes> {
+    if upper_bound == 0 {
+        return Err(VdfError::new_err("upper_bound must be at least 1"));
+    }
+    let mut vdf = VDF::new(
+        ModulusChoice::Rsa2048,
+        generator(seed),
+        upper_bound,
+        ProofType::Sequential,
+    )
+    .map_err(|error| VdfError::new_err(error.to_string()))?;
+    if let Some(cap) = cap_hex {
+        let cap = util::int_from_hex(cap).map_err(|_| {
+            VdfError::new_err(format!("cap_hex is not hex: {}", cap))
+        })?;
+        vdf = vdf
+            .with_cap(cap)
+            .map_err(|error| VdfError::new_err(error.to_string()))?;
+    }
+
+    // The squaring loop runs in the worker, so Python threads can run
+    // until the proof arrives
+    let proof = py.allow_threads(|| {
+        let (_, receiver) = vdf.run_vdf_worker();
+        match receiver.recv() {
+            Ok(Ok(proof)) => Ok(proof),
+            Ok(Err(error)) => Err(error.to_string()),
+            Err(_) => Err("The VDF worker stopped without a proof".to_string()),
+        }
+    });
+    proof
+        .map(|proof| PyBytes::new(py, &proof.to_bytes()))
+        .map_err(VdfError::new_err)
+}
+
+/// Verifies the proof's bytes, raising VdfError if they can't be decoded
+#[pyfunction]
+fn verify(py: Python, proof_bytes: &[u8]) -> PyResult<bool> {
+    let proof = decode(proof_bytes)?;
+    Ok(py.allow_threads(|| proof.verify()))
+}
+
+#[pymodule]
+fn proof_of_proximity(py: Python, module: &PyModule) -> PyResult<()> {
+    module.add_class::<PyProof>()?;
+    module.add_function(wrap_pyfunction!(calibrate, module)?)?;
+    module.add_function(wrap_pyfunction!(prove, module)?)?;
+    module.add_function(wrap_pyfunction!(verify, module)?)?;
+    module.add("VdfError", py.get_type::<VdfError>())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pyo3::types::IntoPyDict;
+
+    fn run(script: &str) {
+        Python::with_gil(|py| {
+            let module = PyModule::new(py, "proof_of_proximity").unwrap();
+            proof_of_proximity(py, module).unwrap();
+            let locals = [("pol", module)].into_py_dict(py);
+            if let Err(error) = py.run(script, None, Some(locals)) {
+                error.print(py);
+                panic!("The Python test failed");
+            }
+        })
+    }
+
+    #[test]
+    fn prove_then_verify() {
+        run(r#"
+cap = "7fffffffffffffffffffffffffffffff"
+proof_bytes = pol.prove(100, cap_hex=cap)
+assert pol.verify(proof_bytes)
+
+proof = pol.Proof(proof_bytes)
+assert proof.iterations == 100
+assert proof.cap_hex == cap
+assert len(proof.result_hex) > 0
+assert proof.verify()
+assert proof.to_bytes() == proof_bytes
+"#);
+    }
+
+    #[test]
+    fn invalid_bytes_raise_vdf_error() {
+        run(r#"
+try:
+    pol.verify(b"not a proof")
+    raise AssertionError("verify accepted garbage")
+except pol.VdfError as error:
+    assert "Unknown proof encoding version" in str(error)
+
+try:
+    pol.prove(10, cap_hex="not hex")
+    raise AssertionError("prove accepted a bad cap")
+except pol.VdfError as error:
+    assert "cap_hex" in str(error)
+"#);
+    }
+}
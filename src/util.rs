@@ -0,0 +1,1045 @@
+extern crate blake3;
+use crate::primality;
+use blake3::Hash;
+use ramp::int::ParseIntError;
+use ramp::Int;
+use rkyv::{Archive, Deserialize, Serialize};
+use sha2::Sha256;
+use sha3::{Digest, Sha3_512, Shake256};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::str;
+use std::str::FromStr;
+use subtle::{Choice, ConstantTimeEq};
+use zeroize::Zeroizing;
+
+/// Extra bits of hash output drawn on top of the modulus size, keeping the
+/// statistical bias of the final modular reduction below 2^-128
+const HASH_SECURITY_MARGIN: u32 = 128;
+
+/// Domain for deriving the VDF generator from the negotiated seed
+pub const SEED_DOMAIN: &str = "pol/seed/v1";
+
+/// Domain for deriving caps
+pub const CAP_DOMAIN: &str = "pol/cap/v1";
+
+/// Domain for the Fiat-Shamir challenges of Pietrzak proofs
+pub const PIETRZAK_DOMAIN: &str = "pol/pietrzak/v1";
+
+/// Domain for deriving the VDF generator from a randomness beacon round
+pub const BEACON_DOMAIN: &str = "pol/beacon/v1";
+
+/// Domain for the candidates of hash_to_prime
+pub const PRIME_DOMAIN: &str = "pol/prime/v1";
+
+/// Byte length of the largest supported modulus, 8192 bits
+pub const MAX_MODULUS_BYTES: usize = 1024;
+
+/// Hashes the input with blake3 and converts to a (Big)Int
+pub fn hash_to_int(s: &str, bit_length: u32) -> Int {
+    let mut bytes = Vec::new();
+    for i in 0..(2 * bit_length / 512 + 1) {
+        let hash: Hash = blake3::hash(format!("{}{}", s, i).as_bytes());
+        bytes.extend_from_slice(hash.as_bytes());
+    }
+    int_from_be_bytes(&bytes)
+}
+
+/// The hash function that seeds and transcripts are expanded with. Both
+/// participants must use the same one, or they derive unrelated generators.
+#[derive(
+    Archive,
+    Deserialize,
+    Serialize,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[serde(try_from = "String", into = "String")]
+pub enum HashAlg {
+    Sha3_512,
+    Sha256,
+    Blake3,
+    /// Full-width output for any modulus in a single pass
+    Shake256,
+}
+
+impl Default for HashAlg {
+    fn default() -> Self {
+        ProtocolVersion::CURRENT.hash_alg()
+    }
+}
+
+/// Versions of the protocol that derive values from seeds and transcripts
+/// differently. Both participants must use the same one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ProtocolVersion {
+    /// Expands with SHA3-512 digests labeled with a block counter
+    V1_0_0,
+    /// Reads SHAKE256 to the width of the modulus, see hash_xof
+    V1_1_0,
+}
+
+impl ProtocolVersion {
+    /// The version new sessions derive with
+    pub const CURRENT: ProtocolVersion = ProtocolVersion::V1_1_0;
+
+    /// The hash function values are derived with in this version
+    pub const fn hash_alg(self) -> HashAlg {
+        match self {
+            ProtocolVersion::V1_0_0 => HashAlg::Sha3_512,
+            ProtocolVersion::V1_1_0 => HashAlg::Shake256,
+        }
+    }
+
+    /// Like util::hash_with_domain, with the hash function of this version
+    pub fn hash_with_domain(
+        self,
+        domain: &'static str,
+        input: &[u8],
+        modulus: &Int,
+    ) -> Int {
+        self.hash_alg().hash_with_domain(domain, input, modulus)
+    }
+}
+
+impl Default for ProtocolVersion {
+    fn default() -> Self {
+        ProtocolVersion::CURRENT
+    }
+}
+
+impl fmt::Display for ProtocolVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtocolVersion::V1_0_0 => write!(f, "1.0.0"),
+            ProtocolVersion::V1_1_0 => write!(f, "1.1.0"),
+        }
+    }
+}
+
+/// Hashes the big-endian block counter followed by the input with SHA3-512
+fn sha3_512_block(counter: u32, input: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha3_512::new();
+    hasher.update(&counter.to_be_bytes());
+    hasher.update(input);
+    hasher.finalize().to_vec()
+}
+
+/// Hashes the big-endian block counter followed by the input with SHA-256
+fn sha256_block(counter: u32, input: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(&counter.to_be_bytes());
+    hasher.update(input);
+    hasher.finalize().to_vec()
+}
+
+/// Hashes the big-endian block counter followed by the input with BLAKE3
+fn blake3_block(counter: u32, input: &[u8]) -> Vec<u8> {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&counter.to_be_bytes());
+    hasher.update(input);
+    hasher.finalize().as_bytes().to_vec()
+}
+
+/// Reads out_bits bits of SHAKE256 over the input followed by the big-endian
+/// out_bits, so that outputs of different lengths are unrelated. Bits above
+/// out_bits in the first byte are cleared.
+fn shake256(input: &[u8], out_bits: u32) -> Vec<u8> {
+    use sha3::digest::{ExtendableOutput, Update, XofReader};
+
+    let mut hasher = Shake256::default();
+    hasher.update(input);
+    hasher.update(&out_bits.to_be_bytes());
+    let mut output = vec![0; ((out_bits + 7) / 8) as usize];
+    hasher.finalize_xof().read(&mut output);
+    if out_bits % 8 != 0 {
+        output[0] &= 0xff >> (8 - out_bits % 8);
+    }
+    output
+}
+
+/// Prefixes the input with the length-prefixed domain. The input may be a
+/// secret transcript, so the copy is wiped once hashed.
+fn tag(domain: &str, input: &[u8]) -> Zeroizing<Vec<u8>> {
+    let mut tagged =
+        Zeroizing::new(Vec::with_capacity(4 + domain.len() + input.len()));
+    tagged.extend_from_slice(&(domain.len() as u32).to_be_bytes());
+    tagged.extend_from_slice(domain.as_bytes());
+    tagged.extend_from_slice(input);
+    tagged
+}
+
+/// Derives exactly out_bits bits, in ceil(out_bits / 8) big-endian bytes,
+/// from the domain and the input in a single SHAKE256 pass. The output length
+/// is hashed too, so a shorter output is not a prefix of a longer one.
+pub fn hash_xof(domain: &str, input: &[u8], out_bits: u32) -> Vec<u8> {
+    shake256(&tag(domain, input), out_bits)
+}
+
+impl HashAlg {
+    /// Expands the input to at least out_bits bits. The fixed-length digests
+    /// are concatenated, each labeled with a big-endian block counter, and
+    /// SHAKE256 is read to length in one pass.
+    fn expand(self, input: &[u8], out_bits: u32) -> Vec<u8> {
+        let (digest_bits, block): (u32, fn(u32, &[u8]) -> Vec<u8>) = match self
+        {
+            HashAlg::Sha3_512 => (512, sha3_512_block),
+            HashAlg::Sha256 => (256, sha256_block),
+            HashAlg::Blake3 => (256, blake3_block),
+            HashAlg::Shake256 => return shake256(input, out_bits),
+        };
+        let blocks = (out_bits + digest_bits - 1) / digest_bits;
+        let mut output =
+            Vec::with_capacity((blocks * digest_bits / 8) as usize);
+        for i in 0..blocks {
+            output.extend_from_slice(&block(i, input));
+        }
+        output
+    }
+
+    /// Reduces the expanded input to a member of the group, drawing enough
+    /// bits to cover the modulus plus a security margin
+    fn expand_to_mod(self, input: &[u8], modulus: &Int) -> Int {
+        let bytes =
+            self.expand(input, modulus.bit_length() + HASH_SECURITY_MARGIN);
+        int_from_be_bytes(&bytes) % modulus
+    }
+
+    /// Like util::hash_with_domain, with this hash function in place of
+    /// SHAKE256
+    pub fn hash_with_domain(
+        self,
+        domain: &'static str,
+        input: &[u8],
+        modulus: &Int,
+    ) -> Int {
+        self.expand_to_mod(&tag(domain, input), modulus)
+    }
+
+    /// Identifies the hash function in the canonical encoding of proofs
+    pub fn id(self) -> u8 {
+        match self {
+            HashAlg::Sha3_512 => 0,
+            HashAlg::Sha256 => 1,
+            HashAlg::Blake3 => 2,
+            HashAlg::Shake256 => 3,
+        }
+    }
+
+    /// The hash function with the id, if any
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(HashAlg::Sha3_512),
+            1 => Some(HashAlg::Sha256),
+            2 => Some(HashAlg::Blake3),
+            3 => Some(HashAlg::Shake256),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for HashAlg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HashAlg::Sha3_512 => write!(f, "sha3-512"),
+            HashAlg::Sha256 => write!(f, "sha256"),
+            HashAlg::Blake3 => write!(f, "blake3"),
+            HashAlg::Shake256 => write!(f, "shake256"),
+        }
+    }
+}
+
+/// Parses "sha3-512", "sha256", "blake3" or "shake256"
+impl FromStr for HashAlg {
+    type Err = UnknownHashAlgError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sha3-512" => Ok(HashAlg::Sha3_512),
+            "sha256" => Ok(HashAlg::Sha256),
+            "blake3" => Ok(HashAlg::Blake3),
+            "shake256" => Ok(HashAlg::Shake256),
+            _ => Err(UnknownHashAlgError(s.into())),
+        }
+    }
+}
+
+impl TryFrom<String> for HashAlg {
+    type Error = UnknownHashAlgError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<HashAlg> for String {
+    fn from(alg: HashAlg) -> Self {
+        alg.to_string()
+    }
+}
+
+/// UnknownHashAlgError is returned when a hash function name isn't supported
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownHashAlgError(pub String);
+
+impl fmt::Display for UnknownHashAlgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Unknown hash function \"{}\", expected sha3-512, sha256, \
+             blake3 or shake256",
+            self.0
+        )
+    }
+}
+
+impl Error for UnknownHashAlgError {
+    fn description(&self) -> &str {
+        "Unknown hash function!"
+    }
+}
+
+/// Deterministically hashes the input bytes to be a member of the
+/// multiplicative group of modulo mod. The digest is expanded to cover the
+/// modulus plus a security margin before reducing, so the output is
+/// practically uniform over the whole group. The input is prefixed with the
+/// length-prefixed domain, so the same input hashes to unrelated values in
+/// different contexts. Reads SHAKE256 through hash_xof, see
+/// ProtocolVersion::V1_0_0 for the SHA3-512 derivation of version 1.0.0.
+pub fn hash_with_domain(
+    domain: &'static str,
+    input: &[u8],
+    modulus: &Int,
+) -> Int {
+    let bytes =
+        hash_xof(domain, input, modulus.bit_length() + HASH_SECURITY_MARGIN);
+    int_from_be_bytes(&bytes) % modulus
+}
+
+/// Hashes the input to the group in the legacy, untagged domain. Kept for
+/// outputs derived before domain separation was introduced.
+#[deprecated(note = "use hash_with_domain with an explicit domain")]
+pub fn hash(input: &[u8], modulus: &Int) -> Int {
+    HashAlg::Sha3_512.expand_to_mod(input, modulus)
+}
+
+/// Deterministically hashes the input to a prime of the bit length of
+/// lower_bound and larger than it, with the given hash function. Candidates
+/// are expanded from the input and a big-endian attempt counter in
+/// PRIME_DOMAIN, with their top and bottom bits set, until one passes
+/// Baillie-PSW.
+pub fn hash_to_prime(alg: HashAlg, s: &str, lower_bound: &Int) -> Int {
+    let n = lower_bound.bit_length();
+    let bound = Int::one() << n as usize;
+    let mut attempt: u32 = 0;
+    loop {
+        let input = [s.as_bytes(), &attempt.to_be_bytes()].concat();
+        let bytes = alg.expand(&tag(PRIME_DOMAIN, &input), n);
+        let mut candidate = int_from_be_bytes(&bytes) % &bound;
+        candidate.set_bit(0, true);
+        candidate.set_bit(n - 1, true);
+        if &candidate > lower_bound && primality::is_baillie_psw(&candidate) {
+            return candidate;
+        }
+        attempt += 1;
+    }
+}
+
+/// UtilError is returned when an Int doesn't fit the bytes asked for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UtilError {
+    /// The Int is negative
+    NegativeInt,
+    /// The Int needs the first number of bytes, but only the second fit
+    IntTooLong(usize, usize),
+}
+
+impl fmt::Display for UtilError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UtilError::NegativeInt => write!(f, "The integer is negative"),
+            UtilError::IntTooLong(needed, len) => write!(
+                f,
+                "The integer needs {} bytes, only {} fit",
+                needed, len
+            ),
+        }
+    }
+}
+
+impl Error for UtilError {
+    fn description(&self) -> &str {
+        "The integer doesn't fit!"
+    }
+}
+
+/// ParseUintError is returned when a serialized number isn't a non-negative
+/// decimal integer
+#[derive(Debug)]
+pub enum ParseUintError {
+    Invalid(ParseIntError),
+    Negative,
+}
+
+impl fmt::Display for ParseUintError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseUintError::Invalid(error) => write!(f, "{}", error),
+            ParseUintError::Negative => write!(f, "The integer is negative"),
+        }
+    }
+}
+
+impl Error for ParseUintError {
+    fn description(&self) -> &str {
+        "The integer doesn't parse!"
+    }
+}
+
+/// Parses a non-negative Int in decimal, the form the serializable twins of
+/// the types hold their numbers in. Negative numbers are rejected here, as
+/// nothing downstream can encode them.
+pub fn uint_from_str(s: &str) -> Result<Int, ParseUintError> {
+    let i = Int::from_str_radix(s, 10).map_err(ParseUintError::Invalid)?;
+    if i < 0 {
+        return Err(ParseUintError::Negative);
+    }
+    Ok(i)
+}
+
+/// Converts a non-negative Int into its minimal big-endian byte
+/// representation. Zero is encoded as a single zero byte. Panics on negative
+/// Ints, see int_to_be_bytes_padded for a fallible version.
+pub fn int_to_be_bytes(i: &Int) -> Vec<u8> {
+    let mut hex = int_to_hex(i);
+    if hex.len() % 2 == 1 {
+        hex.insert(0, '0');
+    }
+    hex_to_bytes(&hex).expect("ramp writes valid hex")
+}
+
+/// Converts a non-negative Int into exactly len big-endian bytes, padded
+/// with leading zeros. Zero fits any length, even an empty one.
+pub fn int_to_be_bytes_padded(
+    i: &Int,
+    len: usize,
+) -> Result<Vec<u8>, UtilError> {
+    if i < &Int::zero() {
+        return Err(UtilError::NegativeInt);
+    }
+    let minimal = if i == &Int::zero() {
+        Vec::new()
+    } else {
+        int_to_be_bytes(i)
+    };
+    if minimal.len() > len {
+        return Err(UtilError::IntTooLong(minimal.len(), len));
+    }
+    let mut padded = vec![0u8; len - minimal.len()];
+    padded.extend_from_slice(&minimal);
+    Ok(padded)
+}
+
+/// Reads big-endian bytes into an Int. Leading zero bytes are ignored and an
+/// empty slice is zero. Goes through hex, which ramp parses in linear time,
+/// unlike shifting in one byte at a time.
+pub fn int_from_be_bytes(bytes: &[u8]) -> Int {
+    if bytes.is_empty() {
+        return Int::zero();
+    }
+    Int::from_str_radix(&bytes_to_hex(bytes), 16).unwrap()
+}
+
+/// Converts a non-negative Int into its minimal little-endian byte
+/// representation. Zero is encoded as a single zero byte.
+pub fn int_to_le_bytes(i: &Int) -> Vec<u8> {
+    let mut bytes = int_to_be_bytes(i);
+    bytes.reverse();
+    bytes
+}
+
+/// Converts a non-negative Int into exactly len little-endian bytes, padded
+/// with trailing zeros
+pub fn int_to_le_bytes_padded(
+    i: &Int,
+    len: usize,
+) -> Result<Vec<u8>, UtilError> {
+    let mut bytes = int_to_be_bytes_padded(i, len)?;
+    bytes.reverse();
+    Ok(bytes)
+}
+
+/// Reads little-endian bytes into an Int. Trailing zero bytes are ignored
+/// and an empty slice is zero.
+pub fn int_from_le_bytes(bytes: &[u8]) -> Int {
+    let mut bytes = bytes.to_vec();
+    bytes.reverse();
+    int_from_be_bytes(&bytes)
+}
+
+/// Encodes a non-negative Int as lowercase hex without a prefix
+pub fn int_to_hex(i: &Int) -> String {
+    i.to_str_radix(16, false)
+}
+
+/// Encodes bytes as lowercase hex without a prefix
+pub fn bytes_to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Decodes hex of either case into bytes. On failure returns the offset of
+/// the first character that isn't part of a complete byte.
+pub fn hex_to_bytes(s: &str) -> Result<Vec<u8>, usize> {
+    let digits = s.as_bytes();
+    let digit =
+        |offset: usize| (digits[offset] as char).to_digit(16).ok_or(offset);
+    let mut bytes = Vec::with_capacity(digits.len() / 2);
+    for offset in (0..digits.len()).step_by(2) {
+        let high = digit(offset)?;
+        if offset + 1 == digits.len() {
+            return Err(offset);
+        }
+        bytes.push((high << 4 | digit(offset + 1)?) as u8);
+    }
+    Ok(bytes)
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes bytes as padded base64 in the standard alphabet of RFC 4648
+pub fn bytes_to_base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, byte)| {
+            group | (*byte as u32) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (group >> (18 - 6 * i)) & 0x3f;
+                encoded.push(BASE64_ALPHABET[index as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Decodes padded base64 in the standard alphabet. Only the canonical
+/// encoding of each input is accepted, so unused bits must be zero. On
+/// failure returns the offset of the offending character, or of the
+/// incomplete group at the end.
+pub fn base64_to_bytes(s: &str) -> Result<Vec<u8>, usize> {
+    let chars = s.as_bytes();
+    if chars.len() % 4 != 0 {
+        return Err(chars.len() / 4 * 4);
+    }
+    let mut bytes = Vec::with_capacity(chars.len() / 4 * 3);
+    for (start, chunk) in (0..chars.len()).step_by(4).zip(chars.chunks(4)) {
+        let last = start + 4 == chars.len();
+        let mut group = 0u32;
+        let mut padding = 0;
+        for (i, c) in chunk.iter().enumerate() {
+            if *c == b'=' && last && i >= 2 {
+                padding += 1;
+                continue;
+            }
+            let value = BASE64_ALPHABET
+                .iter()
+                .position(|a| a == c)
+                .filter(|_| padding == 0)
+                .ok_or(start + i)?;
+            group |= (value as u32) << (18 - 6 * i);
+        }
+        // The bits of the last character that don't make up a whole byte
+        let unused = match padding {
+            0 => 0,
+            1 => group & 0xff,
+            _ => group & 0xffff,
+        };
+        if unused != 0 {
+            return Err(start + 3 - padding);
+        }
+        bytes.extend_from_slice(&group.to_be_bytes()[1..4 - padding]);
+    }
+    Ok(bytes)
+}
+
+/// Amount of bytes the Int takes in big-endian
+pub fn byte_length(i: &Int) -> usize {
+    ((i.bit_length() + 7) / 8) as usize
+}
+
+/// Compares two Ints in constant time as big-endian buffers of len bytes,
+/// usually the byte length of the modulus, so that the time depends on
+/// neither their values nor their lengths. Negative Ints and Ints longer
+/// than len aren't members of any group of that size, and are compared in
+/// variable time.
+pub fn ct_eq(a: &Int, b: &Int, len: usize) -> Choice {
+    match (
+        int_to_be_bytes_padded(a, len),
+        int_to_be_bytes_padded(b, len),
+    ) {
+        (Ok(a), Ok(b)) => {
+            let (a, b) = (Zeroizing::new(a), Zeroizing::new(b));
+            a.as_slice().ct_eq(b.as_slice())
+        }
+        _ => Choice::from(u8::from(a == b)),
+    }
+}
+
+/// Shortens the hex of an Int to its first head and last tail digits, leaving
+/// short numbers whole
+pub fn abbreviated_hex(i: &Int, head: usize, tail: usize) -> String {
+    let hex = int_to_hex(i);
+    if hex.len() <= head + tail {
+        return hex;
+    }
+    format!("{}…{}", &hex[..head], &hex[hex.len() - tail..])
+}
+
+/// Formats an Int for Debug output as `abcd1234…f00d (2048 bits)` instead of
+/// printing every digit
+pub struct TruncatedInt<'a>(pub &'a Int);
+
+impl fmt::Debug for TruncatedInt<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({} bits)",
+            abbreviated_hex(self.0, 8, 4),
+            self.0.bit_length()
+        )
+    }
+}
+
+/// Decodes an Int from hex, accepting an optional 0x prefix and either case
+pub fn int_from_hex(s: &str) -> Result<Int, ParseIntError> {
+    let digits = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s);
+    Int::from_str_radix(digits, 16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RSA_2048;
+    use proptest::prelude::*;
+    use ramp::Int;
+
+    proptest! {
+        #[test]
+        fn bytes_round_trip_in_both_orders(
+            bytes in proptest::collection::vec(any::<u8>(), 0..80),
+            extra in 0usize..4,
+        ) {
+            let i = int_from_be_bytes(&bytes);
+            let mut reversed = bytes.clone();
+            reversed.reverse();
+            prop_assert_eq!(int_from_le_bytes(&reversed), i.clone());
+
+            // Leading zeros are dropped from the minimal form, kept by
+            // padding
+            let len = bytes.len() + extra;
+            let padded = int_to_be_bytes_padded(&i, len).unwrap();
+            prop_assert_eq!(padded.len(), len);
+            prop_assert_eq!(&padded[extra..], &bytes[..]);
+            prop_assert_eq!(int_from_be_bytes(&padded), i.clone());
+            let mut little = int_to_le_bytes_padded(&i, len).unwrap();
+            little.reverse();
+            prop_assert_eq!(little, padded);
+
+            let minimal = int_to_be_bytes(&i);
+            prop_assert_eq!(int_from_be_bytes(&minimal), i.clone());
+            prop_assert!(minimal.len() == 1 || minimal[0] != 0);
+            let mut minimal_le = int_to_le_bytes(&i);
+            minimal_le.reverse();
+            prop_assert_eq!(minimal_le, minimal);
+        }
+
+        #[test]
+        fn output_is_in_group(modulus in 0u32..u32::MAX) {
+            let mod_int: Int = Int::from(modulus);
+            let test_string = "ASDFJKJÖGAGLELJ";
+            let output: Int =
+                hash_with_domain(SEED_DOMAIN, test_string.as_bytes(), &mod_int);
+            assert!(output > Int::zero());
+            assert!(output < mod_int);
+        }
+
+        #[test]
+        fn xof_lengths_are_not_prefixes(
+            input in proptest::collection::vec(any::<u8>(), 0..64),
+            short in 8u32..64,
+            extra in 1u32..64,
+        ) {
+            let short_bytes = hash_xof(SEED_DOMAIN, &input, short * 8);
+            let long_bytes =
+                hash_xof(SEED_DOMAIN, &input, (short + extra) * 8);
+            prop_assert_eq!(short_bytes.len(), short as usize);
+            prop_assert!(!long_bytes.starts_with(&short_bytes));
+        }
+    }
+
+    #[test]
+    fn hash_xof_matches_test_vectors() {
+        let input = b"proof of latency";
+        assert_eq!(hash_xof(SEED_DOMAIN, input, 12), vec![0x01, 0x5e]);
+        assert_eq!(
+            int_to_hex(&int_from_be_bytes(&hash_xof(SEED_DOMAIN, input, 256))),
+            "c844e35e44e5ef755003456a58e06c17db6d03cebd803ac44e8982f62833157a"
+        );
+
+        // Sized for a 3072-bit modulus plus the security margin
+        let wide = hash_xof(SEED_DOMAIN, input, 3072 + HASH_SECURITY_MARGIN);
+        assert_eq!(wide.len(), 400);
+        assert_eq!(
+            int_to_hex(&int_from_be_bytes(&wide[..32])),
+            "6dbe90d82a2ce925d5b7b8817d5e8448ef26b045d4fb4e9715f40290897f0d1a"
+        );
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn hash_matches_test_vectors() {
+        let modulus = Int::from(1_000_000_007);
+        assert_eq!(hash(b"proof of latency", &modulus), Int::from(471_175_681));
+        assert_eq!(hash(b"", &modulus), Int::from(796_080_650));
+
+        let rsa = Int::from_str_radix(RSA_2048, 10).unwrap();
+        assert_eq!(
+            hash(b"proof of latency", &rsa).to_str_radix(16, false),
+            "c71c3e600e92c8c606cc0625e4baf42c6351d4c37c14877830c254c7c725b597e2a9af91f312841c906cd0e9eb91718c13dabd48e7164d938c0269d0860d4aee4bbc4a6caecb0ac9bdb835af7aa1c0739bd90f806e1308ae7c159045d6704fcb9538b5b359442092bad0397f053bce170e1ee14eac1042c30143df35cc5d70b62828bd1b33b5a55fdb484b2666c406d373c4807c9a09b0a388e7e235b0c7e0694311f2ef767d2ef0549b5662cd27b2d264bdd644bc350c29a014b5451d8e24be658e5fd47eacdfcbcdff8469e2fe6f5a43bd3d31d8450688941f07da02de052f0dccd57352b3c15a51dcc5bdaa3eb200e341aa54095a46d430d9aafa5d62f191"
+        );
+    }
+
+    #[test]
+    fn hash_covers_the_whole_group() {
+        let modulus = Int::from_str_radix(RSA_2048, 10).unwrap();
+        let top_bit = modulus.bit_length() - 1;
+
+        // A uniform output has its top bit set with probability
+        // (N - 2^(k-1)) / N, which is roughly 0.36 for this modulus. A
+        // reduced 512-bit digest would never reach it.
+        let top_bit_set = (0u32..1000)
+            .filter(|i| {
+                hash_with_domain(SEED_DOMAIN, &i.to_be_bytes(), &modulus)
+                    .bit(top_bit)
+            })
+            .count();
+        assert!(top_bit_set > 300 && top_bit_set < 420);
+    }
+
+    #[test]
+    fn hash_with_domain_matches_test_vectors() {
+        let modulus = Int::from(1_000_000_007);
+        let input = b"proof of latency";
+        assert_eq!(
+            hash_with_domain(SEED_DOMAIN, input, &modulus),
+            Int::from(449_994_857)
+        );
+        assert_eq!(
+            hash_with_domain(CAP_DOMAIN, input, &modulus),
+            Int::from(200_947_153)
+        );
+
+        let rsa = Int::from_str_radix(RSA_2048, 10).unwrap();
+        assert_eq!(
+            hash_with_domain(SEED_DOMAIN, input, &rsa).to_str_radix(16, false),
+            "622d89d4e7b7ee2a89472c7f62d8e804954952ff5a1d1d676f371d1d8e1c126affe14b4e6efe95447e6fea97c61cc3a9086c0741aa3097e8f853129ad0428668aaf63ff25147c62466d271012a53fe2a2bf46e815e28b05c25d98bf99e838e6dfff783371ad46723cda0ba67eb5bf81e0c77365801bbdacd8ff0f03dbfc842a360bafadbfa5a91f08e77f17acf20d0413fd1cc7ca1f6a61c7e972ce06444b1c9e30d64b22e20dbc05e2a6eec1327ec5ea3e6e84bdec7fde57db965eb26db84f5fb0df171b011c84613fb8e9b4da7eeb8edf334af06b75c0523833d838f45bf4d450c14f866b9c3562c90fd25bcda92947f44aa8a042184e5965c74183f9fce27"
+        );
+    }
+
+    #[test]
+    fn protocol_versions_match_test_vectors() {
+        let modulus = Int::from(1_000_000_007);
+        let input = b"proof of latency";
+        let v1_0_0 = ProtocolVersion::V1_0_0;
+        assert_eq!(
+            v1_0_0.hash_with_domain(SEED_DOMAIN, input, &modulus),
+            Int::from(705_244_624)
+        );
+        assert_eq!(
+            v1_0_0.hash_with_domain(CAP_DOMAIN, input, &modulus),
+            Int::from(61_556_873)
+        );
+
+        let rsa = Int::from_str_radix(RSA_2048, 10).unwrap();
+        assert_eq!(
+            v1_0_0
+                .hash_with_domain(SEED_DOMAIN, input, &rsa)
+                .to_str_radix(16, false),
+            "96c9eba715569fb718270a4dd95619aeaf6a059ea545b30ff70151bb753a44a1cc006891bef1b21d5d72c433dd090608cc7d64067d248cbf3f1adcf09c53c9e78c85a52b51943bc18727b1254e36d7e6b18fbee45be495e5ed792f8568a77b114c82c2ae7e35d07bcb179393d0205a7e82de26c975f23ad9fcd83d5cb8ce32e4db6f72027169afd445c9cae38c90273d265169d827473d716de64daf166b67d9544135109b2bd76b511824dca0f224254a05c702419b148978210c9ebc50bc4f0d01e785891db38421f8b221df72810428896f8eec44ce1904dac81df5e9c70004011e9533745f1a4c8480da01372d06ca5c42d7dcc53ca32cc186b2c074539c"
+        );
+
+        // The current version derives like hash_with_domain
+        assert_eq!(ProtocolVersion::default(), ProtocolVersion::V1_1_0);
+        assert_eq!(
+            ProtocolVersion::CURRENT.hash_with_domain(SEED_DOMAIN, input, &rsa),
+            hash_with_domain(SEED_DOMAIN, input, &rsa)
+        );
+        assert_eq!(ProtocolVersion::V1_0_0.to_string(), "1.0.0");
+        assert_eq!(ProtocolVersion::V1_1_0.to_string(), "1.1.0");
+    }
+
+    #[test]
+    fn hash_algorithms_match_test_vectors() {
+        let modulus = Int::from(1_000_000_007);
+        let input = b"proof of latency";
+        let vectors = [
+            (HashAlg::Sha3_512, 705_244_624),
+            (HashAlg::Sha256, 57_087_706),
+            (HashAlg::Blake3, 471_607_910),
+            (HashAlg::Shake256, 449_994_857),
+        ];
+        for (alg, expected) in vectors.iter() {
+            assert_eq!(
+                alg.hash_with_domain(SEED_DOMAIN, input, &modulus),
+                Int::from(*expected),
+                "{}",
+                alg
+            );
+        }
+        assert_eq!(
+            hash_with_domain(SEED_DOMAIN, input, &modulus),
+            HashAlg::default().hash_with_domain(SEED_DOMAIN, input, &modulus)
+        );
+    }
+
+    #[test]
+    fn hash_algorithms_parse_by_name() {
+        let algs = [
+            HashAlg::Sha3_512,
+            HashAlg::Sha256,
+            HashAlg::Blake3,
+            HashAlg::Shake256,
+        ];
+        for alg in algs.iter() {
+            assert_eq!(alg.to_string().parse::<HashAlg>(), Ok(*alg));
+        }
+        assert_eq!(
+            "md5".parse::<HashAlg>(),
+            Err(UnknownHashAlgError("md5".into()))
+        );
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn domains_separate_outputs() {
+        let modulus = Int::from_str_radix(RSA_2048, 10).unwrap();
+        let input = b"same input";
+        let seed = hash_with_domain(SEED_DOMAIN, input, &modulus);
+        let cap = hash_with_domain(CAP_DOMAIN, input, &modulus);
+        let legacy = hash(input, &modulus);
+        assert_ne!(seed, cap);
+        assert_ne!(seed, legacy);
+        assert_ne!(cap, legacy);
+    }
+
+    #[test]
+    fn be_bytes_round_trip() {
+        let cases: [&[u8]; 4] =
+            [&[1], &[0xff, 0x00, 0x10], &[0x80; 33], &[0x12, 0x34, 0x56]];
+        for bytes in cases.iter() {
+            assert_eq!(int_to_be_bytes(&int_from_be_bytes(bytes)), *bytes);
+        }
+
+        let modulus = Int::from_str_radix(RSA_2048, 10).unwrap();
+        assert_eq!(int_from_be_bytes(&int_to_be_bytes(&modulus)), modulus);
+    }
+
+    #[test]
+    fn be_bytes_handle_leading_zeros_and_zero() {
+        assert_eq!(int_from_be_bytes(&[0, 0, 1, 0]), Int::from(256));
+        assert_eq!(int_to_be_bytes(&Int::from(256)), vec![1, 0]);
+        assert_eq!(int_from_be_bytes(&[]), Int::zero());
+        assert_eq!(int_from_be_bytes(&[0, 0]), Int::zero());
+        assert_eq!(int_to_be_bytes(&Int::zero()), vec![0]);
+        assert_eq!(int_to_be_bytes(&Int::from(15)), vec![15]);
+    }
+
+    #[test]
+    fn padding_fails_when_the_int_does_not_fit() {
+        assert_eq!(int_to_be_bytes_padded(&Int::zero(), 0), Ok(vec![]));
+        assert_eq!(int_to_be_bytes_padded(&Int::zero(), 2), Ok(vec![0, 0]));
+        assert_eq!(
+            int_to_be_bytes_padded(&Int::from(256), 3),
+            Ok(vec![0, 1, 0])
+        );
+        assert_eq!(
+            int_to_le_bytes_padded(&Int::from(256), 3),
+            Ok(vec![0, 1, 0])
+        );
+        assert_eq!(
+            int_to_be_bytes_padded(&Int::from(256), 1),
+            Err(UtilError::IntTooLong(2, 1))
+        );
+        assert_eq!(
+            int_to_le_bytes_padded(&Int::from(-1), 8),
+            Err(UtilError::NegativeInt)
+        );
+        assert_eq!(int_to_le_bytes(&Int::from(0x1234)), vec![0x34, 0x12]);
+        assert_eq!(int_from_le_bytes(&[0x34, 0x12, 0, 0]), Int::from(0x1234));
+        assert_eq!(int_from_le_bytes(&[]), Int::zero());
+    }
+
+    #[test]
+    fn hex_round_trip() {
+        let modulus = Int::from_str_radix(RSA_2048, 10).unwrap();
+        assert_eq!(int_from_hex(&int_to_hex(&modulus)).unwrap(), modulus);
+        assert_eq!(int_to_hex(&Int::from(255)), "ff");
+        assert_eq!(int_from_hex("0xFF").unwrap(), Int::from(255));
+        assert_eq!(int_from_hex("000a").unwrap(), Int::from(10));
+        assert!(int_from_hex("0xzz").is_err());
+    }
+
+    #[test]
+    fn hex_and_base64_match_rfc_4648() {
+        let vectors = [
+            ("", "", ""),
+            ("f", "66", "Zg=="),
+            ("fo", "666f", "Zm8="),
+            ("foo", "666f6f", "Zm9v"),
+            ("foob", "666f6f62", "Zm9vYg=="),
+            ("fooba", "666f6f6261", "Zm9vYmE="),
+            ("foobar", "666f6f626172", "Zm9vYmFy"),
+        ];
+        for (input, hex, base64) in vectors.iter() {
+            assert_eq!(bytes_to_hex(input.as_bytes()), *hex);
+            assert_eq!(hex_to_bytes(hex).unwrap(), input.as_bytes());
+            assert_eq!(bytes_to_base64(input.as_bytes()), *base64);
+            assert_eq!(base64_to_bytes(base64).unwrap(), input.as_bytes());
+        }
+        assert_eq!(hex_to_bytes("00FFfe").unwrap(), vec![0, 0xff, 0xfe]);
+        let all: Vec<u8> = (0..=255).collect();
+        assert_eq!(base64_to_bytes(&bytes_to_base64(&all)).unwrap(), all);
+    }
+
+    #[test]
+    fn malformed_hex_and_base64_report_the_offset() {
+        assert_eq!(hex_to_bytes("abcg"), Err(3));
+        assert_eq!(hex_to_bytes("abc"), Err(2));
+        assert_eq!(hex_to_bytes("0x12"), Err(1));
+        assert_eq!(base64_to_bytes("Zm9vY"), Err(4));
+        assert_eq!(base64_to_bytes("Zm9v!mFy"), Err(4));
+        assert_eq!(base64_to_bytes("Zg=a"), Err(3));
+        assert_eq!(base64_to_bytes("Zg==Zm9v"), Err(2));
+        assert_eq!(base64_to_bytes("=m9v"), Err(0));
+        // "Zh==" decodes to "f" too, with unused bits set
+        assert_eq!(base64_to_bytes("Zh=="), Err(1));
+    }
+
+    #[test]
+    fn ct_eq_matches_eq() {
+        let modulus = Int::from_str_radix(RSA_2048, 10).unwrap();
+        let ints = [
+            Int::zero(),
+            Int::one(),
+            Int::from(255),
+            Int::from(256),
+            Int::from(65_536),
+            modulus.clone(),
+            &modulus - &Int::one(),
+            modulus.clone() << 8,
+        ];
+        let len = byte_length(&modulus);
+        for a in ints.iter() {
+            for b in ints.iter() {
+                assert_eq!(
+                    bool::from(ct_eq(a, b, len)),
+                    a == b,
+                    "{} and {}",
+                    a,
+                    b
+                );
+            }
+        }
+
+        // Ints that don't fit the width are still compared, without
+        // panicking
+        let negative = -Int::one();
+        assert!(bool::from(ct_eq(&negative, &negative, len)));
+        assert!(!bool::from(ct_eq(&negative, &Int::one(), len)));
+        assert!(!bool::from(ct_eq(&Int::from(256), &Int::from(255), 1)));
+    }
+
+    #[test]
+    fn long_ints_are_abbreviated() {
+        let modulus = Int::from_str_radix(RSA_2048, 10).unwrap();
+        assert_eq!(abbreviated_hex(&modulus, 8, 4), "c7970cee…c7e5");
+        assert_eq!(abbreviated_hex(&Int::from(255), 8, 4), "ff");
+        assert_eq!(
+            format!("{:?}", TruncatedInt(&modulus)),
+            "c7970cee…c7e5 (2048 bits)"
+        );
+    }
+
+    #[test]
+    fn hash_to_prime_produces_unique_primes_that_are_larger_than_mod() {
+        let modulus = Int::from_str_radix(RSA_2048, 10).unwrap();
+        let input1 = "fhaehkuhalfehan";
+        let input2 = "hgkrusfejs";
+
+        let alg = HashAlg::default();
+
+        // Test that the prime hasher produces primes larger than modulus
+        let prime1 = hash_to_prime(alg, input1, &modulus);
+        assert!(primality::is_baillie_psw(&prime1));
+        assert!(prime1 > modulus);
+
+        // Test that the prime hasher produces deterministic output
+        let prime2 = hash_to_prime(alg, input1, &modulus);
+        assert!(prime1 == prime2);
+
+        // Test that the prime hasher produces unique output
+        let prime3 = hash_to_prime(alg, input2, &modulus);
+        assert!(prime3 != prime1);
+    }
+
+    #[test]
+    fn hash_to_prime_depends_on_the_hash_function() {
+        let lower_bound = (Int::one() << 255) + Int::from(1);
+        let algs = [
+            HashAlg::Sha3_512,
+            HashAlg::Sha256,
+            HashAlg::Blake3,
+            HashAlg::Shake256,
+        ];
+        let primes: Vec<Int> = algs
+            .iter()
+            .map(|alg| hash_to_prime(*alg, "fhaehkuhalfehan", &lower_bound))
+            .collect();
+        for (i, prime) in primes.iter().enumerate() {
+            assert!(primality::is_baillie_psw(prime), "{}", algs[i]);
+            assert!(prime > &lower_bound);
+            assert_eq!(prime.bit_length(), 256);
+            assert!(primes[..i].iter().all(|other| other != prime));
+        }
+    }
+
+    #[test]
+    fn hash_algs_have_unique_ids() {
+        let algs = [
+            HashAlg::Sha3_512,
+            HashAlg::Sha256,
+            HashAlg::Blake3,
+            HashAlg::Shake256,
+        ];
+        for alg in algs.iter() {
+            assert_eq!(HashAlg::from_id(alg.id()), Some(*alg));
+        }
+        assert_eq!(HashAlg::from_id(4), None);
+    }
+}
@@ -0,0 +1,339 @@
+//! The big integer arithmetic proofs are verified with. ramp, which the rest
+//! of the crate computes with, needs nightly and doesn't build for every
+//! target, so verification is written against the PolInt trait instead.
+//! Values move between the backends as big-endian bytes, the form Ints take
+//! in the canonical encoding.
+//!
+//! ramp implements PolInt, and num-bigint's BigUint does with the
+//! backend-num-bigint feature. Evaluation, hashing and primality still run on
+//! ramp only.
+
+use crate::primality;
+use crate::util;
+use ramp::Int;
+use std::fmt;
+
+/// Non-negative big integers with the operations verification needs. Send
+/// and Sync, so that batches can be verified on a thread pool.
+pub trait PolInt: Clone + Ord + fmt::Debug + Send + Sync + Sized {
+    fn from_u64(n: u64) -> Self;
+
+    /// Reads big-endian bytes. Leading zeros are allowed and an empty slice
+    /// is zero.
+    fn from_be_bytes(bytes: &[u8]) -> Self;
+
+    /// The minimal big-endian bytes, a single zero byte for zero
+    fn to_be_bytes(&self) -> Vec<u8>;
+
+    /// self - other, for other at most self
+    fn sub(&self, other: &Self) -> Self;
+
+    fn mul_mod(&self, other: &Self, modulus: &Self) -> Self;
+
+    fn pow_mod(&self, exponent: &Self, modulus: &Self) -> Self;
+
+    fn gcd(&self, other: &Self) -> Self;
+
+    fn bit_length(&self) -> u32;
+
+    fn is_even(&self) -> bool;
+
+    /// Whether the integer is prime, with a negligible chance of accepting
+    /// a composite even when it was chosen to fool the test
+    fn is_probable_prime(&self) -> bool;
+}
+
+/// Moves an integer to another backend through its big-endian bytes
+pub fn convert<A: PolInt, B: PolInt>(value: &A) -> B {
+    B::from_be_bytes(&value.to_be_bytes())
+}
+
+impl PolInt for Int {
+    fn from_u64(n: u64) -> Self {
+        Int::from(n)
+    }
+
+    fn from_be_bytes(bytes: &[u8]) -> Self {
+        util::int_from_be_bytes(bytes)
+    }
+
+    fn to_be_bytes(&self) -> Vec<u8> {
+        util::int_to_be_bytes(self)
+    }
+
+    fn sub(&self, other: &Self) -> Self {
+        self - other
+    }
+
+    fn mul_mod(&self, other: &Self, modulus: &Self) -> Self {
+        (self * other) % modulus
+    }
+
+    fn pow_mod(&self, exponent: &Self, modulus: &Self) -> Self {
+        Int::pow_mod(self, exponent, modulus)
+    }
+
+    fn gcd(&self, other: &Self) -> Self {
+        Int::gcd(self, other)
+    }
+
+    fn bit_length(&self) -> u32 {
+        Int::bit_length(self)
+    }
+
+    fn is_even(&self) -> bool {
+        Int::is_even(self)
+    }
+
+    fn is_probable_prime(&self) -> bool {
+        primality::is_baillie_psw(self)
+    }
+}
+
+#[cfg(feature = "backend-num-bigint")]
+mod num_bigint_backend {
+    use super::PolInt;
+    use num_bigint::BigUint;
+    use num_integer::Integer;
+    use rand::RngCore;
+
+    /// Rounds of Miller-Rabin with random bases. Each lets a composite
+    /// through with a probability of at most 1/4.
+    const MILLER_RABIN_ROUNDS: usize = 64;
+
+    /// Primes below 100, for trial division before Miller-Rabin
+    const SMALL_PRIMES: [u64; 25] = [
+        2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67,
+        71, 73, 79, 83, 89, 97,
+    ];
+
+    impl PolInt for BigUint {
+        fn from_u64(n: u64) -> Self {
+            BigUint::from(n)
+        }
+
+        fn from_be_bytes(bytes: &[u8]) -> Self {
+            BigUint::from_bytes_be(bytes)
+        }
+
+        fn to_be_bytes(&self) -> Vec<u8> {
+            self.to_bytes_be()
+        }
+
+        fn sub(&self, other: &Self) -> Self {
+            self - other
+        }
+
+        fn mul_mod(&self, other: &Self, modulus: &Self) -> Self {
+            (self * other) % modulus
+        }
+
+        fn pow_mod(&self, exponent: &Self, modulus: &Self) -> Self {
+            self.modpow(exponent, modulus)
+        }
+
+        fn gcd(&self, other: &Self) -> Self {
+            Integer::gcd(self, other)
+        }
+
+        fn bit_length(&self) -> u32 {
+            self.bits() as u32
+        }
+
+        fn is_even(&self) -> bool {
+            Integer::is_even(self)
+        }
+
+        fn is_probable_prime(&self) -> bool {
+            for prime in SMALL_PRIMES.iter() {
+                let prime = BigUint::from(*prime);
+                if *self == prime {
+                    return true;
+                }
+                if (self % &prime).bits() == 0 {
+                    return false;
+                }
+            }
+            if self.bits() <= 1 {
+                return false;
+            }
+
+            // self - 1 = d · 2^s with d odd
+            let one = BigUint::from(1u32);
+            let minus_one = self - &one;
+            let s = minus_one.trailing_zeros().unwrap_or(0);
+            let d = &minus_one >> s;
+
+            // Random bases in [2, self - 2], drawn fresh for every check so
+            // that a composite can't be built to pass them
+            let mut rng = rand::thread_rng();
+            let mut bytes = vec![0u8; self.to_bytes_be().len() + 8];
+            let range = self - BigUint::from(3u32);
+            'rounds: for _ in 0..MILLER_RABIN_ROUNDS {
+                rng.fill_bytes(&mut bytes);
+                let base = BigUint::from_bytes_be(&bytes) % &range + 2u32;
+                let mut x = base.modpow(&d, self);
+                if x == one || x == minus_one {
+                    continue;
+                }
+                for _ in 1..s {
+                    x = x.modpow(&BigUint::from(2u32), self);
+                    if x == minus_one {
+                        continue 'rounds;
+                    }
+                }
+                return false;
+            }
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vdf::proof::verify_raw_with_exponent;
+    use crate::vdf::ModulusChoice;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn ramp_round_trips_bytes(
+            bytes in proptest::collection::vec(any::<u8>(), 0..300),
+        ) {
+            let value = <Int as PolInt>::from_be_bytes(&bytes);
+            let minimal = PolInt::to_be_bytes(&value);
+            prop_assert!(minimal.len() == 1 || minimal[0] != 0);
+            prop_assert_eq!(<Int as PolInt>::from_be_bytes(&minimal), value);
+        }
+    }
+
+    #[test]
+    fn ramp_backend_matches_ramp() {
+        let modulus = ModulusChoice::Rsa2048.int().clone();
+        let base = Int::from(7);
+        let exponent = Int::from(65537);
+        assert_eq!(
+            PolInt::pow_mod(&base, &exponent, &modulus),
+            base.pow_mod(&exponent, &modulus)
+        );
+        assert_eq!(PolInt::gcd(&Int::from(84), &Int::from(36)), 12);
+        assert_eq!(PolInt::to_be_bytes(&Int::zero()), vec![0]);
+        assert!(PolInt::is_probable_prime(&Int::from(65537)));
+        assert!(!PolInt::is_probable_prime(&modulus));
+    }
+
+    #[cfg(feature = "backend-num-bigint")]
+    mod num_bigint_backend {
+        use super::*;
+        use crate::primality;
+        use crate::vdf::test_support;
+        use crate::vdf::VerifyError;
+        use num_bigint::BigUint;
+
+        fn big(value: &Int) -> BigUint {
+            convert(value)
+        }
+
+        proptest! {
+            #![proptest_config(ProptestConfig::with_cases(16))]
+
+            #[test]
+            fn backends_agree_on_bytes(
+                bytes in proptest::collection::vec(any::<u8>(), 0..300),
+            ) {
+                let ramp = <Int as PolInt>::from_be_bytes(&bytes);
+                let num = <BigUint as PolInt>::from_be_bytes(&bytes);
+                prop_assert_eq!(
+                    PolInt::to_be_bytes(&ramp),
+                    PolInt::to_be_bytes(&num)
+                );
+                prop_assert_eq!(convert::<BigUint, Int>(&num), ramp);
+            }
+
+            #[test]
+            fn backends_agree_on_arithmetic(
+                a in any::<u64>(),
+                b in any::<u64>(),
+                e in any::<u32>(),
+            ) {
+                let modulus = ModulusChoice::Rsa2048.int().clone();
+                let (a, b) = (Int::from(a), Int::from(b));
+                let e = Int::from(e);
+                prop_assert_eq!(
+                    big(&PolInt::pow_mod(&a, &e, &modulus)),
+                    PolInt::pow_mod(&big(&a), &big(&e), &big(&modulus))
+                );
+                prop_assert_eq!(
+                    big(&PolInt::mul_mod(&a, &b, &modulus)),
+                    PolInt::mul_mod(&big(&a), &big(&b), &big(&modulus))
+                );
+                prop_assert_eq!(
+                    big(&PolInt::gcd(&a, &b)),
+                    PolInt::gcd(&big(&a), &big(&b))
+                );
+                prop_assert_eq!(
+                    PolInt::bit_length(&a),
+                    PolInt::bit_length(&big(&a))
+                );
+                prop_assert_eq!(
+                    PolInt::is_even(&a),
+                    PolInt::is_even(&big(&a))
+                );
+            }
+
+            #[test]
+            fn backends_agree_on_primality(
+                prime in test_support::safe_prime(64),
+                other in test_support::safe_prime(64),
+            ) {
+                prop_assert!(PolInt::is_probable_prime(&big(&prime)));
+                let composite = &prime * &other;
+                prop_assert!(!PolInt::is_probable_prime(&big(&composite)));
+                prop_assert!(primality::is_baillie_psw(&prime));
+            }
+
+            #[test]
+            fn ramp_proofs_verify_under_num_bigint(
+                proof in test_support::honest_proof(100, 32),
+            ) {
+                let bytes = proof.to_bytes();
+                let decoded =
+                    crate::vdf::proof::VDFProof::from_bytes(&bytes).unwrap();
+                let verify = |result: &BigUint| {
+                    verify_raw_with_exponent(
+                        &big(&decoded.modulus),
+                        &big(&decoded.generator),
+                        result,
+                        u64::from(decoded.output.iterations),
+                        decoded.exponent,
+                        &big(&decoded.cap),
+                        &big(&decoded.pi),
+                    )
+                };
+                prop_assert_eq!(verify(&big(&decoded.output.result)), Ok(()));
+
+                let tampered = PolInt::mul_mod(
+                    &big(&decoded.output.result),
+                    &BigUint::from(2u32),
+                    &big(&decoded.modulus),
+                );
+                prop_assert_eq!(
+                    verify(&tampered),
+                    Err(VerifyError::EquationMismatch)
+                );
+            }
+        }
+
+        #[test]
+        fn carmichael_numbers_are_composite() {
+            for n in [561u64, 41041, 825265, 321197185].iter() {
+                assert!(!PolInt::is_probable_prime(&BigUint::from(*n)));
+            }
+            assert!(PolInt::is_probable_prime(&BigUint::from(2u32)));
+            assert!(PolInt::is_probable_prime(&BigUint::from(
+                (1u64 << 61) - 1
+            )));
+        }
+    }
+}
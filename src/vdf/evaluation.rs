@@ -1,23 +1,47 @@
+use crate::metrics::{
+    MetricsSink, CAP_REJECTIONS, PROOFS_GENERATED, SQUARINGS_PER_SECOND,
+    WORKER_PANICS,
+};
+use crate::primality::{self, PrimeCertificate, PrimePool};
+use crate::util;
 use crate::vdf;
+use crate::vdf::worker::{self, Broadcast, Outbox, VdfWorkerHandle};
 use crossbeam::channel::unbounded;
 use crossbeam::channel::{Receiver, Sender};
+use futures::channel::mpsc;
 use ramp::Int;
-use ramp_primes::Generator;
-use ramp_primes::Verification;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
 use rkyv::{Archive, Deserialize, Serialize};
 use std::cmp::Ordering;
-use std::time::Instant;
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
 use std::{thread, time};
+use subtle::ConstantTimeEq;
 
 /// The end result of the VDF which we still need to prove
-#[derive(Debug, Clone, Default)]
+#[derive(Clone)]
 pub struct VDFResult {
     pub result: Int,
     pub iterations: u32,
 }
 
 /// A deserializable VDFResult because ramp::Int is not deserializable
-#[derive(Archive, Debug, Deserialize, Serialize, Clone, Default)]
+#[derive(
+    Archive,
+    Debug,
+    Deserialize,
+    Serialize,
+    Clone,
+    Default,
+    serde::Serialize,
+    serde::Deserialize,
+)]
 pub struct DeserializableVDFResult {
     pub result: String,
     pub iterations: u32,
@@ -25,10 +49,15 @@ pub struct DeserializableVDFResult {
 
 impl DeserializableVDFResult {
     pub fn serialize(&self) -> VDFResult {
-        VDFResult {
-            result: Int::from_str_radix(&self.result, 10).unwrap(),
+        self.try_serialize().unwrap()
+    }
+
+    /// Like serialize, but fails if the result doesn't parse or is negative
+    pub fn try_serialize(&self) -> Result<VDFResult, util::ParseUintError> {
+        Ok(VDFResult {
+            result: util::uint_from_str(&self.result)?,
             iterations: self.iterations,
-        }
+        })
     }
 }
 
@@ -45,13 +74,22 @@ impl PartialOrd for VDFResult {
     }
 }
 
+/// Compares the results in constant time. A result doesn't know its
+/// modulus, so they're padded to the largest one supported.
 impl PartialEq for VDFResult {
     fn eq(&self, other: &Self) -> bool {
-        self.result == other.result && self.iterations == other.iterations
+        let equal =
+            util::ct_eq(&self.result, &other.result, util::MAX_MODULUS_BYTES)
+                & self.iterations.ct_eq(&other.iterations);
+        equal.into()
     }
 }
 
 impl VDFResult {
+    pub fn new(result: Int, iterations: u32) -> Self {
+        Self { result, iterations }
+    }
+
     pub fn deserialize(&self) -> DeserializableVDFResult {
         DeserializableVDFResult {
             result: self.result.to_str_radix(10, false),
@@ -62,6 +100,22 @@ impl VDFResult {
 
 impl Eq for VDFResult {}
 
+impl Hash for VDFResult {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        util::int_to_be_bytes(&self.result).hash(state);
+        self.iterations.hash(state);
+    }
+}
+
+impl fmt::Debug for VDFResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VDFResult")
+            .field("result", &util::TruncatedInt(&self.result))
+            .field("iterations", &self.iterations)
+            .finish()
+    }
+}
+
 impl PartialEq for DeserializableVDFResult {
     fn eq(&self, other: &Self) -> bool {
         self.result == other.result && self.iterations == other.iterations
@@ -70,6 +124,305 @@ impl PartialEq for DeserializableVDFResult {
 
 impl Eq for DeserializableVDFResult {}
 
+/// Local wall-clock times of a VDF run, for audits. They're whatever the
+/// prover's clock said, so they only cross-check the iterations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VdfTimings {
+    pub started_at: SystemTime,
+    /// None when the VDF ran to its upper bound without receiving a cap
+    pub cap_received_at: Option<SystemTime>,
+    pub result_ready_at: SystemTime,
+    pub proof_ready_at: SystemTime,
+}
+
+/// VdfTimings in nanoseconds since the Unix epoch
+#[derive(
+    Archive,
+    Debug,
+    Deserialize,
+    Serialize,
+    Clone,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub struct DeserializableVdfTimings {
+    pub started_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cap_received_at: Option<u64>,
+    pub result_ready_at: u64,
+    pub proof_ready_at: u64,
+}
+
+fn unix_nanos(at: SystemTime) -> u64 {
+    at.duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |since| since.as_nanos() as u64)
+}
+
+fn from_unix_nanos(nanos: u64) -> SystemTime {
+    SystemTime::UNIX_EPOCH + time::Duration::from_nanos(nanos)
+}
+
+impl DeserializableVdfTimings {
+    pub fn serialize(&self) -> VdfTimings {
+        VdfTimings {
+            started_at: from_unix_nanos(self.started_at),
+            cap_received_at: self.cap_received_at.map(from_unix_nanos),
+            result_ready_at: from_unix_nanos(self.result_ready_at),
+            proof_ready_at: from_unix_nanos(self.proof_ready_at),
+        }
+    }
+}
+
+impl VdfTimings {
+    /// Timings of a run that started at started_at and has just stopped
+    fn stopped(
+        started_at: SystemTime,
+        cap_received_at: Option<SystemTime>,
+    ) -> Self {
+        let now = SystemTime::now();
+        Self {
+            started_at,
+            cap_received_at,
+            result_ready_at: now,
+            proof_ready_at: now,
+        }
+    }
+
+    pub fn deserialize(&self) -> DeserializableVdfTimings {
+        DeserializableVdfTimings {
+            started_at: unix_nanos(self.started_at),
+            cap_received_at: self.cap_received_at.map(unix_nanos),
+            result_ready_at: unix_nanos(self.result_ready_at),
+            proof_ready_at: unix_nanos(self.proof_ready_at),
+        }
+    }
+
+    /// Whether the start, the cap, the result and the proof came in that
+    /// order, as they do unless the clock was changed mid-run
+    pub fn is_ordered(&self) -> bool {
+        let cap = self.cap_received_at.unwrap_or(self.started_at);
+        self.started_at <= cap
+            && cap <= self.result_ready_at
+            && self.result_ready_at <= self.proof_ready_at
+    }
+
+    /// Time from the start to the cap, to cross-check the latency derived
+    /// from the iterations against
+    pub fn until_cap(&self) -> Option<time::Duration> {
+        self.cap_received_at?.duration_since(self.started_at).ok()
+    }
+
+    /// Time the evaluation took, without the proof
+    pub fn evaluation(&self) -> Option<time::Duration> {
+        self.result_ready_at.duration_since(self.started_at).ok()
+    }
+
+    /// Iterations per second the evaluation ran at, to compare against the
+    /// rate the prover claims
+    pub fn implied_rate(&self, iterations: u32) -> Option<f64> {
+        let seconds = self.evaluation()?.as_secs_f64();
+        if seconds > 0.0 {
+            Some(f64::from(iterations) / seconds)
+        } else {
+            None
+        }
+    }
+}
+
+/// How far a VDF run has got, reported to VdfObserver::on_progress
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgressReport {
+    pub iterations: u32,
+    pub upper_bound: u32,
+    pub elapsed: time::Duration,
+    /// Iterations per second
+    pub rate: f64,
+    /// The upper bound, None when the VDF runs until it gets a cap
+    pub target_iterations: Option<u64>,
+    pub fraction_complete: Option<f64>,
+    /// Time left to the target at the rate
+    pub eta: Option<time::Duration>,
+}
+
+impl ProgressReport {
+    /// A report whose rate is the average since the start
+    pub fn new(
+        iterations: u32,
+        upper_bound: u32,
+        elapsed: time::Duration,
+    ) -> Self {
+        let seconds = elapsed.as_secs_f64();
+        let rate = if seconds > 0.0 {
+            f64::from(iterations) / seconds
+        } else {
+            0.0
+        };
+        Self::with_rate(iterations, upper_bound, elapsed, rate)
+    }
+
+    fn with_rate(
+        iterations: u32,
+        upper_bound: u32,
+        elapsed: time::Duration,
+        rate: f64,
+    ) -> Self {
+        // u32::MAX stands for no upper bound
+        let target_iterations = match upper_bound {
+            u32::MAX => None,
+            bound => Some(u64::from(bound)),
+        };
+        let remaining = target_iterations
+            .map(|target| target.saturating_sub(u64::from(iterations)));
+        Self {
+            iterations,
+            upper_bound,
+            elapsed,
+            rate,
+            target_iterations,
+            fraction_complete: target_iterations
+                .map(|target| f64::from(iterations) / target as f64),
+            eta: remaining.filter(|_| rate > 0.0).map(|remaining| {
+                time::Duration::from_secs_f64(remaining as f64 / rate)
+            }),
+        }
+    }
+}
+
+/// Reports kept for the rolling rate of ProgressEstimator
+pub const ROLLING_REPORTS: usize = 8;
+
+/// Builds the progress reports of a run, with the rate over the last
+/// ROLLING_REPORTS reports so that the ETA follows changes in speed
+#[derive(Debug, Clone)]
+pub struct ProgressEstimator {
+    upper_bound: u32,
+    samples: VecDeque<(u32, time::Duration)>,
+}
+
+impl ProgressEstimator {
+    pub fn new(upper_bound: u32) -> Self {
+        let mut samples = VecDeque::with_capacity(ROLLING_REPORTS + 1);
+        samples.push_back((0, time::Duration::ZERO));
+        Self {
+            upper_bound,
+            samples,
+        }
+    }
+
+    /// The report at iterations done in elapsed time
+    pub fn report(
+        &mut self,
+        iterations: u32,
+        elapsed: time::Duration,
+    ) -> ProgressReport {
+        let (since_iterations, since) = self.samples[0];
+        let seconds = elapsed.saturating_sub(since).as_secs_f64();
+        let rate = if seconds > 0.0 {
+            f64::from(iterations.saturating_sub(since_iterations)) / seconds
+        } else {
+            0.0
+        };
+        self.samples.push_back((iterations, elapsed));
+        if self.samples.len() > ROLLING_REPORTS {
+            self.samples.pop_front();
+        }
+        ProgressReport::with_rate(iterations, self.upper_bound, elapsed, rate)
+    }
+}
+
+/// Callbacks for VDF::run_with. Every hook does nothing by default, and
+/// closures taking a ProgressReport observe the progress.
+pub trait VdfObserver {
+    /// Called each time the cap source is polled
+    fn on_progress(&mut self, _report: &ProgressReport) {}
+
+    /// Called with the cap the proof is calculated against, before it's
+    /// validated
+    fn on_cap(&mut self, _cap: &Int) {}
+
+    /// Called when the evaluation has stopped and the proof calculation
+    /// starts
+    fn on_proof_start(&mut self) {}
+}
+
+impl<F: FnMut(&ProgressReport)> VdfObserver for F {
+    fn on_progress(&mut self, report: &ProgressReport) {
+        self(report)
+    }
+}
+
+/// Snapshots of a VDF evaluated on the calling thread, see VDF::snapshots
+#[derive(Debug, Clone)]
+pub struct Snapshots {
+    vdf: VDF,
+    every: usize,
+}
+
+impl Snapshots {
+    /// The VDF in its current state, to continue the evaluation and prove
+    /// it
+    pub fn into_vdf(self) -> VDF {
+        self.vdf
+    }
+}
+
+impl Iterator for Snapshots {
+    type Item = VDFResult;
+    fn next(&mut self) -> Option<VDFResult> {
+        let mut advanced = false;
+        for _ in 0..self.every {
+            match self.vdf.next() {
+                Some(_) => advanced = true,
+                None => break,
+            }
+        }
+        if advanced {
+            Some(self.vdf.result.clone())
+        } else {
+            None
+        }
+    }
+}
+
+/// Largest upper bound a VDF is built with, apart from u32::MAX for no
+/// bound. It takes over a quarter of an hour at a million squarings per
+/// second, far longer than any round trip worth measuring.
+pub const MAX_UPPER_BOUND: u32 = 1 << 30;
+
+/// The shortest round trip a cap could plausibly take, even between
+/// neighbours
+pub const FLOOR_RTT: time::Duration = time::Duration::from_micros(100);
+
+/// The iterations done in a round trip of rtt at the squaring rate, for
+/// VDF::with_min_iterations
+pub fn min_iterations_for_rtt(
+    rtt: time::Duration,
+    squarings_per_second: u64,
+) -> u32 {
+    let iterations =
+        rtt.as_nanos() * u128::from(squarings_per_second) / 1_000_000_000;
+    u32::try_from(iterations).unwrap_or(u32::MAX)
+}
+
+/// Largest checkpoint interval, as the proof calculation takes 2^(k+1)
+/// multiplications on top of the T/k for the checkpoints
+pub const MAX_PROOF_CHECKPOINT_INTERVAL: usize = 24;
+
+/// Intermediate proofs are calculated every interval iterations against a
+/// self-generated cap, each one starting from the result the previous one
+/// ended at
+#[derive(Debug, Clone)]
+struct IntermediateProofs {
+    interval: u32,
+    cap_bits: usize,
+    base: VDFResult,
+    sender: Sender<vdf::proof::VDFProof>,
+    receiver: Receiver<vdf::proof::VDFProof>,
+}
+
 /// VDF is an options struct for calculating VDFProofs
 #[derive(Debug, Clone)]
 pub struct VDF {
@@ -78,10 +431,32 @@ pub struct VDF {
     pub upper_bound: u32,
     pub cap: Int,
     pub result: VDFResult,
-    two: Int,
+    /// The exponent k of the map x ← x^k iterated, 2 for squaring. Set with
+    /// with_exponent, which keeps exponent_int in step.
+    pub exponent: u32,
+    /// The exponent as an Int, so that the iterations don't convert it
+    exponent_int: Int,
+    /// The hash function the generator was derived with, recorded in proofs
+    pub hash_alg: util::HashAlg,
     pub proof_type: vdf::proof::ProofType,
     proof_nudger: Option<Sender<bool>>,
     proof_receiver: Option<Receiver<vdf::proof::VDFProof>>,
+    cap_bits: usize,
+    rng: StdRng,
+    prime_pool: Option<Arc<PrimePool>>,
+    result_sender: Option<Sender<VDFResult>>,
+    snapshot_sender: Option<(u32, mpsc::UnboundedSender<VDFResult>)>,
+    poll_interval: u32,
+    subscriber_capacity: usize,
+    min_iterations: u32,
+    proof_checkpoints: Option<usize>,
+    checkpoints: Vec<Int>,
+    intermediate_proofs: Option<IntermediateProofs>,
+    metrics: Option<Arc<dyn MetricsSink>>,
+    session: Option<u64>,
+    /// Makes the worker panic at the iteration
+    #[cfg(test)]
+    pub(crate) panic_at: Option<u32>,
 }
 
 impl Iterator for VDF {
@@ -89,8 +464,15 @@ impl Iterator for VDF {
     fn next(&mut self) -> Option<VDFResult> {
         if self.result.iterations < self.upper_bound {
             self.result.iterations += 1;
-            self.result.result =
-                self.result.result.pow_mod(&self.two, &self.modulus);
+            self.result.result = self
+                .result
+                .result
+                .pow_mod(&self.exponent_int, &self.modulus);
+            if let Some(k) = self.proof_checkpoints {
+                if self.result.iterations as usize % k == 0 {
+                    self.checkpoints.push(self.result.result.clone());
+                }
+            }
             Some(self.result.clone())
         } else {
             None
@@ -99,97 +481,620 @@ impl Iterator for VDF {
 }
 
 fn calculate_and_send_proof(
-    modulus: &Int,
-    generator: &Int,
-    result: &VDFResult,
+    pending: vdf::proof::PendingProof,
     cap: &Int,
-    worker_sender: &Sender<Result<vdf::proof::VDFProof, vdf::InvalidCapError>>,
-) {
-    let proof = vdf::proof::VDFProof::new(
-        modulus,
-        generator,
-        result,
-        cap,
-        &vdf::proof::ProofType::Sequential,
-    )
-    .calculate();
+    cap_certificate: Option<PrimeCertificate>,
+    cap_received_at: u32,
+    timings: VdfTimings,
+    checkpoints: Option<(usize, &[Int])>,
+    outbox: &impl Outbox,
+) -> bool {
+    let timer = Instant::now();
+    let iterations = pending.result().iterations;
+    let proof = match checkpoints {
+        Some((k, checkpoints)) => {
+            pending.finish_with_checkpoints(cap, k, checkpoints)
+        }
+        None => pending.finish(cap),
+    };
 
     match proof {
-        None => error!("Failed to generate a proof!"),
-        Some(success) => {
+        None => {
+            error!("Failed to generate a proof!");
+            outbox.finish(Err(vdf::VdfError::ProofFailed));
+            false
+        }
+        Some(mut success) => {
+            success.cap_certificate = cap_certificate;
+            success.cap_received_at = u64::from(cap_received_at);
+            success.timings = Some(VdfTimings {
+                proof_ready_at: SystemTime::now(),
+                ..timings
+            });
+            info!(
+                iterations,
+                elapsed_ms = timer.elapsed().as_millis() as u64,
+                "proof generated"
+            );
             debug!("Proof generated! {:#?}", success);
 
             // Send proof to caller
-            if worker_sender.send(Ok(success)).is_err() {
-                error!("Failed to send the proof to caller!");
-            }
+            outbox.finish(Ok(success));
+            true
         }
     }
 }
 
+fn count(metrics: &Option<Arc<dyn MetricsSink>>, name: &str) {
+    if let Some(metrics) = metrics.as_ref() {
+        metrics.incr_counter(name, &[]);
+    }
+}
+
 impl VDF {
     /// VDF builder with default options. Can be chained with
-    /// estimate_upper_bound
+    /// estimate_upper_bound. The modulus must be odd and over 3, and pass
+    /// ModulusChoice::validate, the generator a unit between 1 and the
+    /// modulus, and the upper bound between 1 and MAX_UPPER_BOUND, or
+    /// u32::MAX for no bound.
     pub fn new(
-        modulus: Int,
+        modulus: vdf::ModulusChoice,
         generator: Int,
         upper_bound: u32,
         proof_type: vdf::proof::ProofType,
-    ) -> Self {
-        Self {
-            modulus,
+    ) -> Result<Self, vdf::VdfError> {
+        Self::build(modulus, generator, upper_bound, proof_type, true)
+    }
+
+    /// Like new, but accepts custom moduli that fail ModulusChoice::validate,
+    /// for tests in groups small enough to follow by hand
+    #[cfg(test)]
+    pub(crate) fn new_in_test_group(
+        modulus: vdf::ModulusChoice,
+        generator: Int,
+        upper_bound: u32,
+        proof_type: vdf::proof::ProofType,
+    ) -> Result<Self, vdf::VdfError> {
+        Self::build(modulus, generator, upper_bound, proof_type, false)
+    }
+
+    fn build(
+        modulus: vdf::ModulusChoice,
+        generator: Int,
+        upper_bound: u32,
+        proof_type: vdf::proof::ProofType,
+        validate_modulus: bool,
+    ) -> Result<Self, vdf::VdfError> {
+        let invalid = |parameter, reason| {
+            Err(vdf::VdfError::InvalidParameter { parameter, reason })
+        };
+        let n = modulus.int();
+        if n <= &Int::from(3) {
+            return invalid("modulus", "must be over 3");
+        }
+        if n.is_even() {
+            return invalid("modulus", "must be odd");
+        }
+        if validate_modulus {
+            match modulus.validate() {
+                Ok(()) => {}
+                Err(vdf::InvalidModulusError::Prime) => {
+                    return invalid("modulus", "is prime");
+                }
+                Err(_) => {
+                    return invalid("modulus", "is too short to be secure");
+                }
+            }
+        }
+        if generator <= 1 || &generator >= n {
+            return invalid("generator", "must be between 1 and the modulus");
+        }
+        if generator.gcd(n) != 1 {
+            return invalid("generator", "shares a factor with the modulus");
+        }
+        if upper_bound == 0 {
+            return invalid("upper_bound", "must be at least 1");
+        }
+        if upper_bound > MAX_UPPER_BOUND && upper_bound != u32::MAX {
+            return invalid(
+                "upper_bound",
+                "must be at most 2^30, or u32::MAX for no bound",
+            );
+        }
+
+        Ok(Self {
+            modulus: modulus.int().clone(),
             generator: generator.clone(),
             upper_bound,
             cap: Int::zero(),
-            result: VDFResult {
-                result: generator,
-                iterations: 0,
-            },
-            two: Int::from(2),
+            result: VDFResult::new(generator, 0),
+            exponent: vdf::proof::DEFAULT_EXPONENT,
+            exponent_int: Int::from(vdf::proof::DEFAULT_EXPONENT),
+            hash_alg: vdf::proof::DEFAULT_HASH_ALG,
             proof_type,
             proof_nudger: None,
             proof_receiver: None,
-        }
+            cap_bits: 128,
+            rng: StdRng::from_entropy(),
+            prime_pool: None,
+            result_sender: None,
+            snapshot_sender: None,
+            poll_interval: 1,
+            subscriber_capacity: worker::DEFAULT_SUBSCRIBER_CAPACITY,
+            min_iterations: 0,
+            proof_checkpoints: None,
+            checkpoints: Vec::new(),
+            intermediate_proofs: None,
+            metrics: None,
+            session: None,
+            #[cfg(test)]
+            panic_at: None,
+        })
+    }
+
+    /// Continues a chain of proofs: the new VDF starts from the output of the
+    /// previous proof in the same group, and by default runs as many
+    /// iterations as the previous one did
+    pub fn extend_from(
+        previous: &vdf::proof::VDFProof,
+    ) -> Result<Self, vdf::VdfError> {
+        Ok(Self::new(
+            vdf::ModulusChoice::Custom(previous.modulus.clone()),
+            previous.output.result.clone(),
+            previous.output.iterations,
+            previous.proof_type.clone(),
+        )?
+        .with_exponent(previous.exponent)
+        .with_hash_alg(previous.hash_alg))
     }
 
-    /// Add a precomputed cap to the VDF
-    pub fn with_cap(mut self, cap: Int) -> Self {
+    /// Sets the bit length of the caps the VDF generates for itself
+    pub fn with_cap_bits(mut self, cap_bits: usize) -> Self {
+        self.cap_bits = cap_bits;
+        self
+    }
+
+    /// Iterates x ← x^exponent instead of squaring. Must be set before the
+    /// cap for parallel proofs, and rules out Pietrzak proofs, which only
+    /// prove squaring.
+    pub fn with_exponent(mut self, exponent: u32) -> Self {
+        assert!(exponent >= 2, "The exponent must be at least 2");
+        self.exponent = exponent;
+        self.exponent_int = Int::from(exponent);
+        self
+    }
+
+    /// Records the hash function the generator was derived with in the
+    /// proofs, so that the peer can tell whether it derives the same one
+    pub fn with_hash_alg(mut self, hash_alg: util::HashAlg) -> Self {
+        self.hash_alg = hash_alg;
+        self
+    }
+
+    /// Seeds the randomness used for generating caps, making them
+    /// reproducible
+    pub fn with_rng(mut self, seed: u64) -> Self {
+        self.rng = StdRng::seed_from_u64(seed);
+        self
+    }
+
+    /// Starts the evaluation from the generator derived from a beacon round,
+    /// so that it can't have started before the round was published
+    #[cfg(feature = "beacon")]
+    pub fn with_beacon_seed(
+        mut self,
+        round: u64,
+        randomness: [u8; 32],
+    ) -> Self {
+        self.generator =
+            crate::beacon::derive_base(round, &randomness, &self.modulus);
+        self.result = VDFResult::new(self.generator.clone(), 0);
+        self
+    }
+
+    /// Add a precomputed cap to the VDF, failing if it isn't prime
+    pub fn with_cap(mut self, cap: Int) -> Result<Self, vdf::VdfError> {
+        if !self.validate_cap(&cap) {
+            count(&self.metrics, CAP_REJECTIONS);
+            return Err(vdf::VdfError::InvalidCap);
+        }
         let (proof_nudger, proof_receiver): (
             Option<Sender<bool>>,
             Option<Receiver<vdf::proof::VDFProof>>,
         ) = match self.proof_type {
             vdf::proof::ProofType::Sequential => (None, None),
             vdf::proof::ProofType::Parallel => {
-                if cap.gt(&Int::zero()) {
-                    let mut proof = vdf::proof::VDFProof::new(
-                        &self.modulus,
-                        &self.generator,
-                        &self.result,
-                        &cap,
-                        &self.proof_type,
-                    );
-                    let (nudger, receiver) = proof.calculate_parallel();
-                    (Some(nudger), Some(receiver))
-                } else {
-                    (None, None)
+                match vdf::proof::VDFProof::new(
+                    &self.modulus,
+                    &self.generator,
+                    &self.result,
+                    &cap,
+                    &self.proof_type,
+                ) {
+                    Ok(proof) => {
+                        let mut proof = proof
+                            .with_exponent(self.exponent)
+                            .with_hash_alg(self.hash_alg);
+                        let (nudger, receiver) = proof.calculate_parallel();
+                        (Some(nudger), Some(receiver))
+                    }
+                    Err(_) => (None, None),
                 }
             }
         };
         self.proof_nudger = proof_nudger;
         self.proof_receiver = proof_receiver;
         self.cap = cap;
+        Ok(self)
+    }
+
+    /// Draws the caps the VDF generates for itself from a shared pool of
+    /// pre-generated primes
+    pub fn with_prime_pool(mut self, prime_pool: Arc<PrimePool>) -> Self {
+        self.prime_pool = Some(prime_pool);
+        self
+    }
+
+    /// Sends the VDFResult to the given channel as soon as the evaluation
+    /// stops, before the proof is calculated. The result is enough for
+    /// estimating the latency, and the proof follows for validating it.
+    pub fn with_result_sender(mut self, sender: Sender<VDFResult>) -> Self {
+        self.result_sender = Some(sender);
+        self
+    }
+
+    /// Makes the worker send its state every every iterations, for watching
+    /// the residue evolve. The receiving end of the channel is a Stream,
+    /// which ends when the worker stops.
+    pub fn with_snapshot_sender(
+        mut self,
+        every: u32,
+        sender: mpsc::UnboundedSender<VDFResult>,
+    ) -> Self {
+        assert!(every > 0, "The snapshot interval must be positive");
+        self.snapshot_sender = Some((every, sender));
+        self
+    }
+
+    /// Polls the cap source of run_with, and reports the progress of
+    /// spawn_worker, every interval iterations instead of on each one
+    pub fn with_poll_interval(mut self, interval: u32) -> Self {
+        assert!(interval > 0, "The poll interval must be positive");
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Keeps up to capacity unreceived messages for each subscriber of
+    /// spawn_worker, dropping the oldest ones past that
+    pub fn with_subscriber_capacity(mut self, capacity: usize) -> Self {
+        assert!(capacity > 0, "The subscriber capacity must be positive");
+        self.subscriber_capacity = capacity;
+        self
+    }
+
+    /// Holds caps the worker receives before n iterations until it has done
+    /// n, and proves against them then. The proof records where the cap
+    /// arrived, see VDFProof::cap_arrived_early.
+    pub fn with_min_iterations(mut self, n: u32) -> Self {
+        self.min_iterations = n;
+        self
+    }
+
+    /// Evaluates the VDF lazily on the calling thread, every iterations per
+    /// snapshot. The last snapshot is at the upper bound, even if fewer
+    /// iterations are left. Snapshots::into_vdf resumes the evaluation.
+    pub fn snapshots(self, every: usize) -> Snapshots {
+        assert!(every > 0, "The snapshot interval must be positive");
+        Snapshots { vdf: self, every }
+    }
+
+    /// Stores every k-th intermediate result during the evaluation, taking
+    /// about T/k group elements of memory, and uses them to calculate the
+    /// sequential proof in about T/k + 2^(k+1) multiplications instead of T.
+    /// Must be set before the evaluation starts.
+    pub fn with_proof_checkpoints(mut self, k: usize) -> Self {
+        assert!(
+            (1..=MAX_PROOF_CHECKPOINT_INTERVAL).contains(&k),
+            "The checkpoint interval must be between 1 and {}",
+            MAX_PROOF_CHECKPOINT_INTERVAL
+        );
+        self.proof_checkpoints = Some(k);
+        self.checkpoints = vec![self.result.result.clone()];
+        self
+    }
+
+    /// Makes the worker prove its progress every interval iterations on the
+    /// way to the final proof, against caps of cap_bits bits or from the
+    /// prime pool. The proofs form a chain from the generator, and are
+    /// received from intermediate_proofs.
+    pub fn with_intermediate_proofs(
+        mut self,
+        interval: u32,
+        cap_bits: usize,
+    ) -> Self {
+        assert!(interval > 0, "The proof interval must be positive");
+        let (sender, receiver) = unbounded();
+        self.intermediate_proofs = Some(IntermediateProofs {
+            interval,
+            cap_bits,
+            base: self.result.clone(),
+            sender,
+            receiver,
+        });
         self
     }
 
+    /// The stream of intermediate proofs, which ends when the worker stops
+    pub fn intermediate_proofs(
+        &self,
+    ) -> Option<Receiver<vdf::proof::VDFProof>> {
+        self.intermediate_proofs
+            .as_ref()
+            .map(|intermediate| intermediate.receiver.clone())
+    }
+
+    /// Pauses the evaluation to prove the iterations since the previous
+    /// intermediate proof, if there's been interval of them
+    fn send_intermediate_proof(&mut self) {
+        let (cap_bits, due) = match self.intermediate_proofs.as_ref() {
+            Some(intermediate) => (
+                intermediate.cap_bits,
+                self.result.iterations - intermediate.base.iterations
+                    >= intermediate.interval,
+            ),
+            None => return,
+        };
+        if !due {
+            return;
+        }
+        let (cap, certificate) = match self.prime_pool.as_ref() {
+            Some(pool) => pool.take_certified(),
+            None => {
+                primality::gen_certified_safe_prime(cap_bits, &mut self.rng)
+            }
+        };
+
+        let intermediate = self.intermediate_proofs.as_mut().unwrap();
+        let segment = VDFResult::new(
+            self.result.result.clone(),
+            self.result.iterations - intermediate.base.iterations,
+        );
+        let proof = vdf::proof::VDFProof::start(
+            &self.modulus,
+            &intermediate.base.result,
+            &segment,
+            &vdf::proof::ProofType::Sequential,
+        )
+        .with_exponent(self.exponent)
+        .with_hash_alg(self.hash_alg)
+        .finish(&cap);
+        match proof {
+            None => error!("Failed to generate an intermediate proof!"),
+            Some(mut proof) => {
+                proof.cap_certificate = Some(certificate);
+                if intermediate.sender.send(proof).is_err() {
+                    error!("Couldn't send the intermediate proof!");
+                }
+            }
+        }
+        intermediate.base = self.result.clone();
+    }
+
+    /// The sequential proof of the current result, waiting for its cap
+    fn pending_proof(&self) -> vdf::proof::PendingProof {
+        vdf::proof::VDFProof::start(
+            &self.modulus,
+            &self.generator,
+            &self.result,
+            &vdf::proof::ProofType::Sequential,
+        )
+        .with_exponent(self.exponent)
+        .with_hash_alg(self.hash_alg)
+    }
+
+    fn checkpoints(&self) -> Option<(usize, &[Int])> {
+        self.proof_checkpoints
+            .map(|k| (k, self.checkpoints.as_slice()))
+    }
+
+    /// Proves the current result with a Pietrzak proof, looking up the first
+    /// midpoint from the proof checkpoints when they're stored. Returns None
+    /// unless the VDF squares.
+    pub fn pietrzak_proof(&self) -> Option<vdf::pietrzak::PietrzakProof> {
+        if self.exponent != vdf::proof::DEFAULT_EXPONENT {
+            return None;
+        }
+        vdf::pietrzak::prove(
+            &self.generator,
+            &self.modulus,
+            self.result.iterations,
+            &self.result.result,
+            self.checkpoints(),
+        )
+    }
+
+    /// Evaluates the VDF up to the upper bound on the calling thread
+    pub fn evaluate(&mut self) -> &VDFResult {
+        while self.next().is_some() {}
+        &self.result
+    }
+
+    /// Evaluates the VDF and proves the result on the calling thread, against
+    /// the cap or one generated like in the worker. Returns None if the cap
+    /// isn't prime or the upper bound is zero.
+    pub fn run_sync(mut self) -> Option<vdf::proof::VDFProof> {
+        self.evaluate();
+        let (cap, certificate) = if self.cap == Int::zero() {
+            let (cap, certificate) = self.generate_cap();
+            (cap, Some(certificate))
+        } else if self.validate_cap(&self.cap) {
+            (self.cap.clone(), None)
+        } else {
+            return None;
+        };
+        let pending = vdf::proof::VDFProof::start(
+            &self.modulus,
+            &self.generator,
+            &self.result,
+            &self.proof_type,
+        )
+        .with_exponent(self.exponent)
+        .with_hash_alg(self.hash_alg);
+        let mut proof = match self.checkpoints() {
+            Some((k, checkpoints)) => {
+                pending.finish_with_checkpoints(&cap, k, checkpoints)
+            }
+            None => pending.finish(&cap),
+        }?;
+        proof.cap_certificate = certificate;
+        Some(proof)
+    }
+
+    /// Runs the VDF on the calling thread like the worker, with callbacks in
+    /// place of channels. The cap source is polled every poll interval
+    /// iterations, and the evaluation stops at the first cap it returns. At
+    /// the upper bound the VDF proves against its own cap, or generates one.
+    pub fn run_with<O: VdfObserver + ?Sized>(
+        mut self,
+        observer: &mut O,
+        mut cap_source: impl FnMut() -> Option<Int>,
+    ) -> Result<vdf::proof::VDFProof, vdf::VdfError> {
+        let timer = Instant::now();
+        let started_at = SystemTime::now();
+        let mut progress = ProgressEstimator::new(self.upper_bound);
+        let mut received = None;
+        while self.next().is_some() {
+            if self.result.iterations % self.poll_interval != 0 {
+                continue;
+            }
+            observer.on_progress(
+                &progress.report(self.result.iterations, timer.elapsed()),
+            );
+            received = cap_source();
+            if received.is_some() {
+                break;
+            }
+        }
+
+        // with_cap has already validated a cap set beforehand
+        let from_source = received.is_some();
+        let cap_received_at = received.as_ref().map(|_| SystemTime::now());
+        let (cap, certificate) = match received {
+            Some(cap) => (cap, None),
+            None if self.cap != Int::zero() => (self.cap.clone(), None),
+            None => {
+                let (cap, certificate) = self.generate_cap();
+                (cap, Some(certificate))
+            }
+        };
+        observer.on_cap(&cap);
+        if from_source && !self.validate_cap(&cap) {
+            count(&self.metrics, CAP_REJECTIONS);
+            return Err(vdf::VdfError::InvalidCap);
+        }
+        self.send_result();
+        self.report_rate(timer.elapsed());
+        let timings = VdfTimings::stopped(started_at, cap_received_at);
+
+        observer.on_proof_start();
+        let pending = self.pending_proof();
+        let mut proof = match self.checkpoints() {
+            Some((k, checkpoints)) => {
+                pending.finish_with_checkpoints(&cap, k, checkpoints)
+            }
+            None => pending.finish(&cap),
+        }
+        .ok_or(vdf::VdfError::ProofFailed)?;
+        proof.cap_certificate = certificate;
+        proof.timings = Some(VdfTimings {
+            proof_ready_at: SystemTime::now(),
+            ..timings
+        });
+        count(&self.metrics, PROOFS_GENERATED);
+        Ok(proof)
+    }
+
+    /// Names the worker thread after the session running the VDF
+    pub fn with_session(mut self, session: u64) -> Self {
+        self.session = Some(session);
+        self
+    }
+
+    /// Reports the evaluation's progress and proofs to the sink
+    pub fn with_metrics(mut self, metrics: Arc<dyn MetricsSink>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    fn report_rate(&self, elapsed: time::Duration) {
+        if let Some(metrics) = self.metrics.as_ref() {
+            let seconds = elapsed.as_secs_f64();
+            if seconds > 0.0 {
+                metrics.observe(
+                    SQUARINGS_PER_SECOND,
+                    f64::from(self.result.iterations) / seconds,
+                    &[],
+                );
+            }
+        }
+    }
+
+    fn send_snapshot(&self) {
+        if let Some((every, sender)) = self.snapshot_sender.as_ref() {
+            if self.result.iterations % every == 0
+                && sender.unbounded_send(self.result.clone()).is_err()
+            {
+                debug!("The snapshot stream was dropped");
+            }
+        }
+    }
+
+    fn send_result(&self) {
+        if let Some(sender) = self.result_sender.as_ref() {
+            if sender.send(self.result.clone()).is_err() {
+                error!("Couldn't send the VDF result to the listener!");
+            }
+        }
+    }
+
+    /// Generates a cap for the VDF itself along with a certificate of its
+    /// primality, taking it from the prime pool when one is set
+    fn generate_cap(&mut self) -> (Int, PrimeCertificate) {
+        match self.prime_pool.as_ref() {
+            Some(pool) => pool.take_certified(),
+            None => primality::gen_certified_safe_prime(
+                self.cap_bits,
+                &mut self.rng,
+            ),
+        }
+    }
+
     /// Validates that cap is prime.
     fn validate_cap(&self, cap: &Int) -> bool {
-        Verification::verify_prime(cap.clone())
+        primality::is_baillie_psw(cap)
+    }
+
+    /// Evaluates a copy of the VDF on the calling thread for the duration and
+    /// returns the iterations done per second, for min_iterations_for_rtt.
+    /// Unlike estimate_upper_bound, no proof is calculated.
+    pub fn measure_rate(&self, duration: time::Duration) -> u64 {
+        let mut vdf = self.clone();
+        vdf.upper_bound = u32::MAX;
+        vdf.proof_checkpoints = None;
+        let timer = Instant::now();
+        let mut iterations: u64 = 0;
+        while timer.elapsed() < duration && vdf.next().is_some() {
+            iterations += 1;
+        }
+        let elapsed = timer.elapsed().as_nanos().max(1);
+        u64::try_from(u128::from(iterations) * 1_000_000_000 / elapsed)
+            .unwrap_or(u64::MAX)
     }
 
     /// Estimates the maximum number of sequential calculations that can fit in
     /// the fiven ms_bound millisecond threshold.
     pub fn estimate_upper_bound(mut self, ms_bound: u64) -> Self {
-        let cap: Int = Generator::new_prime(128);
+        let (cap, _) = self.generate_cap();
         let (capper, receiver) = self.clone().run_vdf_worker();
 
         let sleep_time = time::Duration::from_millis(ms_bound);
@@ -205,117 +1110,232 @@ impl VDF {
     /// A worker that does the actual calculation in a VDF. Returns a VDFProof
     /// based on initial parameters in the VDF.
     pub fn run_vdf_worker(
-        mut self,
+        self,
     ) -> (
         Sender<Int>,
         Receiver<Result<vdf::proof::VDFProof, vdf::InvalidCapError>>,
     ) {
+        let (worker_sender, caller_receiver) = unbounded();
+        let (caller_sender, _) = self.spawn(worker_sender);
+        (caller_sender, caller_receiver)
+    }
+
+    /// Runs the worker like run_vdf_worker, sending progress reports every
+    /// poll interval iterations and the final result to each subscriber of
+    /// the handle
+    pub fn spawn_worker(self) -> VdfWorkerHandle {
+        let outbox =
+            Broadcast::new(self.subscriber_capacity, self.metrics.clone());
+        let (capper, thread) = self.spawn(outbox.clone());
+        VdfWorkerHandle::new(capper, outbox, thread)
+    }
+
+    fn spawn<B: Outbox>(
+        mut self,
+        outbox: B,
+    ) -> (Sender<Int>, thread::JoinHandle<()>) {
         let (caller_sender, worker_receiver): (Sender<Int>, Receiver<Int>) =
             unbounded();
-        let (worker_sender, caller_receiver) = unbounded();
 
         let timer = Instant::now();
-        thread::spawn(move || loop {
-            match self.next() {
-                None => {
-                    // Upper bound reached, stops iteration
-                    // and calculates the proof
-                    debug!(
+        let started_at = SystemTime::now();
+        let span = info_span!("vdf_worker", upper_bound = self.upper_bound);
+        let dispatch = tracing::dispatcher::get_default(|d| d.clone());
+        let name = match self.session {
+            Some(session) => format!("pol-vdf-worker-{}", session),
+            None => "pol-vdf-worker".to_string(),
+        };
+        let worker = move || {
+            let _dispatch = tracing::dispatcher::set_default(&dispatch);
+            let _span = span.enter();
+            // A panic is sent as the result, so that the caller knows why
+            // the worker stopped
+            let mut early_cap: Option<(Int, u32, SystemTime)> = None;
+            let mut progress = ProgressEstimator::new(self.upper_bound);
+            let run = panic::catch_unwind(AssertUnwindSafe(|| loop {
+                match self.next() {
+                    None => {
+                        // Upper bound reached, stops iteration
+                        // and calculates the proof
+                        debug!(
                         "Upper bound of {:?} reached in {:?} milliseconds, generating proof.",
                         self.result.iterations, timer.elapsed().as_millis()
                     );
 
-                    // Copy pregenerated cap
-                    let mut self_cap: Int = self.cap.clone();
-
-                    // Check if default, check for primality if else
-                    if self_cap == Int::zero() {
-                        self_cap = Generator::new_safe_prime(128);
-                        debug!("Cap generated: {:?}", self_cap);
-                    } else if !self.validate_cap(&self_cap) {
-                        if worker_sender
-                            .send(Err(vdf::InvalidCapError))
-                            .is_err()
-                        {
-                            error!("Cap not correct!");
+                        // Copy pregenerated cap
+                        let mut self_cap: Int = self.cap.clone();
+                        let mut self_cap_certificate = None;
+
+                        self.send_result();
+                        self.report_rate(timer.elapsed());
+                        let timings = VdfTimings::stopped(started_at, None);
+                        // Generate a cap if none was set. with_cap has
+                        // already validated a set one.
+                        if self_cap == Int::zero() {
+                            let (cap, certificate) = self.generate_cap();
+                            self_cap = cap;
+                            self_cap_certificate = Some(certificate);
+                            debug!("Cap generated: {:?}", self_cap);
                         }
-                        break;
-                    }
 
-                    match self.proof_receiver {
-                        None => calculate_and_send_proof(
-                            &self.modulus,
-                            &self.generator,
-                            &self.result,
-                            &self_cap,
-                            &worker_sender,
-                        ),
-                        Some(receiver) => {
-                            debug!("Waiting for proof receiver");
-
-                            if let Some(nudger) = self.proof_nudger.as_ref() {
-                                if nudger.send(false).is_err() {
-                                    error!("Couldn't nudge the parallel proof!")
+                        match self.proof_receiver {
+                            None => {
+                                if calculate_and_send_proof(
+                                    self.pending_proof(),
+                                    &self_cap,
+                                    self_cap_certificate,
+                                    self.result.iterations,
+                                    timings,
+                                    self.checkpoints(),
+                                    &outbox,
+                                ) {
+                                    count(&self.metrics, PROOFS_GENERATED);
                                 }
                             }
-                            match receiver.recv() {
-                                Ok(mut proof) => {
-                                    // Clone our result to the received proof
-                                    proof.output = self.result.clone();
-                                    debug!("Received proof from parallel proof calculator! {:?}", proof);
-                                    if worker_sender.send(Ok(proof)).is_err() {
-                                        error!("Couldn't send proof to worker listener!");
+                            Some(receiver) => {
+                                debug!("Waiting for proof receiver");
+
+                                if let Some(nudger) = self.proof_nudger.as_ref()
+                                {
+                                    if nudger.send(false).is_err() {
+                                        error!("Couldn't nudge the parallel proof!")
                                     }
                                 }
-                                Err(_) => {
-                                    error!("Error with parallel proof calculation!");
+                                match receiver.recv() {
+                                    Ok(mut proof) => {
+                                        // Clone our result to the received
+                                        // proof
+                                        proof.output = self.result.clone();
+                                        proof.cap_received_at =
+                                            u64::from(self.result.iterations);
+                                        proof.timings = Some(VdfTimings {
+                                            proof_ready_at: SystemTime::now(),
+                                            ..timings
+                                        });
+                                        debug!("Received proof from parallel proof calculator! {:?}", proof);
+                                        count(&self.metrics, PROOFS_GENERATED);
+                                        outbox.finish(Ok(proof));
+                                    }
+                                    Err(_) => {
+                                        error!("Error with parallel proof calculation!");
+                                        outbox.finish(Err(
+                                            vdf::VdfError::ProofFailed,
+                                        ));
+                                    }
                                 }
                             }
                         }
+
+                        break;
                     }
+                    Some(result) => {
+                        self.result = result;
+                        #[cfg(test)]
+                        {
+                            if self.panic_at == Some(self.result.iterations) {
+                                panic!(
+                                    "Panicking at {}",
+                                    self.result.iterations
+                                );
+                            }
+                        }
+                        self.send_snapshot();
+                        self.send_intermediate_proof();
+                        if self.result.iterations % self.poll_interval == 0 {
+                            outbox.progress(&progress.report(
+                                self.result.iterations,
+                                timer.elapsed(),
+                            ));
+                        }
 
-                    break;
-                }
-                Some(result) => {
-                    self.result = result;
+                        if let Some(nudger) = self.proof_nudger.as_ref() {
+                            if nudger.try_send(true).is_err() {
+                                error!("Couldn't nudge the parallel proof!")
+                            }
+                        }
 
-                    if let Some(nudger) = self.proof_nudger.as_ref() {
-                        if nudger.try_send(true).is_err() {
-                            error!("Couldn't nudge the parallel proof!")
+                        // Try receiving a cap from the other participant on
+                        // each iteration
+                        if early_cap.is_none() {
+                            if let Ok(cap) = worker_receiver.try_recv() {
+                                info!(
+                                    iterations = self.result.iterations,
+                                    elapsed_ms =
+                                        timer.elapsed().as_millis() as u64,
+                                    "cap received"
+                                );
+                                debug!("Received the cap {:?}", cap);
+                                early_cap = Some((
+                                    cap,
+                                    self.result.iterations,
+                                    SystemTime::now(),
+                                ));
+                            }
                         }
-                    }
 
-                    // Try receiving a cap from the other participant on
-                    // each iteration
-                    if let Ok(cap) = worker_receiver.try_recv() {
-                        // Cap received
-                        debug!("Received the cap {:?} after {:?} milliseconds, generating proof.", cap, timer.elapsed().as_millis());
+                        // A cap that arrives before the minimum iterations
+                        // is held until they're done
+                        let minimum = self.min_iterations.min(self.upper_bound);
+                        let (cap, received_at, arrived) = match early_cap.take()
+                        {
+                            Some((cap, received_at, arrived))
+                                if self.result.iterations >= minimum =>
+                            {
+                                (cap, received_at, arrived)
+                            }
+                            held => {
+                                early_cap = held;
+                                continue;
+                            }
+                        };
+                        if received_at < self.result.iterations {
+                            warn!(
+                                received_at,
+                                iterations = self.result.iterations,
+                                "cap arrived before the minimum iterations"
+                            );
+                        }
 
                         // Check for primality
                         if self.validate_cap(&cap) {
+                            self.send_result();
+                            self.report_rate(timer.elapsed());
+                            let timings =
+                                VdfTimings::stopped(started_at, Some(arrived));
                             match self.proof_receiver {
-                                None => calculate_and_send_proof(
-                                    &self.modulus,
-                                    &self.generator,
-                                    &self.result,
-                                    &cap,
-                                    &worker_sender,
-                                ),
+                                None => {
+                                    if calculate_and_send_proof(
+                                        self.pending_proof(),
+                                        &cap,
+                                        None,
+                                        received_at,
+                                        timings,
+                                        self.checkpoints(),
+                                        &outbox,
+                                    ) {
+                                        count(&self.metrics, PROOFS_GENERATED);
+                                    }
+                                }
                                 Some(receiver) => match receiver.recv() {
                                     Ok(mut proof) => {
                                         // Clone our result to the received
                                         // proof
                                         proof.output = self.result.clone();
+                                        proof.cap_received_at =
+                                            u64::from(received_at);
+                                        proof.timings = Some(VdfTimings {
+                                            proof_ready_at: SystemTime::now(),
+                                            ..timings
+                                        });
                                         debug!("Received proof from parallel proof calculator! {:?}", proof);
-                                        if worker_sender
-                                            .send(Ok(proof))
-                                            .is_err()
-                                        {
-                                            error!("Couldn't send proof to worker listener!");
-                                        }
+                                        count(&self.metrics, PROOFS_GENERATED);
+                                        outbox.finish(Ok(proof));
                                     }
                                     Err(_) => {
                                         error!("Error with parallel proof calculation!");
+                                        outbox.finish(Err(
+                                            vdf::VdfError::ProofFailed,
+                                        ));
                                     }
                                 },
                             }
@@ -323,23 +1343,25 @@ impl VDF {
                             error!("Received cap was not a prime!");
                             // Received cap was not a prime, send error to
                             // caller
-                            if worker_sender
-                                .send(Err(vdf::InvalidCapError))
-                                .is_err()
-                            {
-                                error!(
-                                    "Error sending InvalidCapError to caller!"
-                                );
-                            }
+                            count(&self.metrics, CAP_REJECTIONS);
+                            outbox.finish(Err(vdf::VdfError::InvalidCap));
                         }
                         break;
-                    } else {
-                        continue;
                     }
                 }
+            }));
+            if let Err(payload) = run {
+                let message = worker::panic_message(payload.as_ref());
+                error!(%message, "VDF worker panicked");
+                count(&self.metrics, WORKER_PANICS);
+                outbox.finish(Err(vdf::VdfError::WorkerPanicked { message }));
             }
-        });
+        };
+        let thread = thread::Builder::new()
+            .name(name)
+            .spawn(worker)
+            .expect("Couldn't spawn the VDF worker thread");
 
-        (caller_sender, caller_receiver)
+        (caller_sender, thread)
     }
 }
@@ -1,92 +1,2383 @@
 #![allow(soft_unstable)]
+use crate::primality;
+use crate::util;
+use ramp::Int;
+use rkyv::{Archive, Deserialize, Serialize};
+use std::convert::TryFrom;
 use std::error::Error;
 use std::fmt;
+use std::str::FromStr;
 
+pub mod bigint;
 pub mod evaluation;
+pub mod moduli;
+pub mod pietrzak;
 pub mod proof;
-pub mod util;
+#[cfg(test)]
+pub mod test_support;
+pub mod throttling;
+pub mod worker;
+
+#[cfg(feature = "parallel")]
+pub use proof::{
+    check_batch_parallel, verify_batch_parallel, PARALLEL_BATCH_THRESHOLD,
+};
+pub use proof::{verify_batch, verify_raw, verify_raw_with_exponent};
+
+/// The proof construction used for a VDF, agreed on by both participants
+#[derive(
+    Archive,
+    Debug,
+    Deserialize,
+    Serialize,
+    Clone,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub enum ProofScheme {
+    /// A single group element against a prime cap from the peer
+    Wesolowski,
+    /// About log2(T) midpoints with Fiat-Shamir challenges, no cap needed
+    Pietrzak,
+}
+
+impl Default for ProofScheme {
+    fn default() -> Self {
+        ProofScheme::Wesolowski
+    }
+}
+
+/// ChainError is returned when consecutive proofs don't form a chain. Carries
+/// the index of the first broken link.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainError {
+    Empty,
+    ModulusChanged(usize),
+    BaseMismatch(usize),
+    NoProgress(usize),
+    InvalidProof(usize),
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChainError::Empty => write!(f, "The chain has no proofs"),
+            ChainError::ModulusChanged(index) => {
+                write!(f, "Link {} changes the modulus", index)
+            }
+            ChainError::BaseMismatch(index) => write!(
+                f,
+                "Link {} doesn't start from the previous output",
+                index
+            ),
+            ChainError::NoProgress(index) => {
+                write!(f, "Link {} has no iterations", index)
+            }
+            ChainError::InvalidProof(index) => {
+                write!(f, "Link {} doesn't verify", index)
+            }
+        }
+    }
+}
+
+impl Error for ChainError {
+    fn description(&self) -> &str {
+        "Broken proof chain!"
+    }
+}
+
+/// Smallest custom modulus accepted, in bits
+pub const MIN_MODULUS_BITS: u32 = 1024;
+
+/// The RSA modulus a VDF is evaluated in
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+#[serde(try_from = "String")]
+pub enum ModulusChoice {
+    Rsa2048,
+    Custom(Int),
+}
+
+impl Default for ModulusChoice {
+    fn default() -> Self {
+        ModulusChoice::Rsa2048
+    }
+}
+
+impl ModulusChoice {
+    pub fn int(&self) -> &Int {
+        match self {
+            ModulusChoice::Rsa2048 => &*moduli::RSA_2048_INT,
+            ModulusChoice::Custom(modulus) => modulus,
+        }
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.int().bit_length()
+    }
+
+    /// Checks that a custom modulus could be an RSA modulus: odd, not prime
+    /// and at least MIN_MODULUS_BITS long. The embedded moduli are always
+    /// valid.
+    pub fn validate(&self) -> Result<(), InvalidModulusError> {
+        let modulus = match self {
+            ModulusChoice::Custom(modulus) => modulus,
+            _ => return Ok(()),
+        };
+        if modulus.bit_length() < MIN_MODULUS_BITS {
+            return Err(InvalidModulusError::TooSmall(modulus.bit_length()));
+        }
+        if modulus.is_even() {
+            return Err(InvalidModulusError::Even);
+        }
+        if primality::is_baillie_psw(modulus) {
+            return Err(InvalidModulusError::Prime);
+        }
+        Ok(())
+    }
+}
+
+/// Parses "rsa-2048", or a custom modulus in decimal or 0x-prefixed hex
+impl FromStr for ModulusChoice {
+    type Err = InvalidModulusError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let choice = match s {
+            "rsa-2048" => ModulusChoice::Rsa2048,
+            _ if s.starts_with("0x") || s.starts_with("0X") => {
+                ModulusChoice::Custom(
+                    util::int_from_hex(s)
+                        .map_err(|_| InvalidModulusError::Unknown(s.into()))?,
+                )
+            }
+            _ if !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) => {
+                ModulusChoice::Custom(Int::from_str_radix(s, 10).unwrap())
+            }
+            _ => return Err(InvalidModulusError::Unknown(s.into())),
+        };
+        choice.validate()?;
+        Ok(choice)
+    }
+}
+
+impl TryFrom<String> for ModulusChoice {
+    type Error = InvalidModulusError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// InvalidModulusError is returned when a modulus can't be used for a VDF
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvalidModulusError {
+    Unknown(String),
+    TooSmall(u32),
+    Even,
+    Prime,
+}
+
+impl fmt::Display for InvalidModulusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidModulusError::Unknown(name) => write!(
+                f,
+                "Unknown modulus \"{}\", expected rsa-2048 or a custom \
+                 modulus in decimal or 0x-prefixed hex",
+                name
+            ),
+            InvalidModulusError::TooSmall(bits) => write!(
+                f,
+                "The modulus must be at least {} bits, got {}",
+                MIN_MODULUS_BITS, bits
+            ),
+            InvalidModulusError::Even => write!(f, "The modulus is even"),
+            InvalidModulusError::Prime => write!(f, "The modulus is prime"),
+        }
+    }
+}
+
+impl Error for InvalidModulusError {
+    fn description(&self) -> &str {
+        "Invalid modulus!"
+    }
+}
+
+/// InvalidCapError is returned when a non-prime cap is received in the
+/// vdf_worker
+#[derive(Debug)]
+pub struct InvalidCapError;
+
+impl fmt::Display for InvalidCapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid cap value encountered!")
+    }
+}
+
+impl Error for InvalidCapError {
+    fn description(&self) -> &str {
+        "Invalid cap value encountered!"
+    }
+}
+
+/// VdfError is returned when a run of the VDF ends without a proof
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VdfError {
+    InvalidParameter {
+        parameter: &'static str,
+        reason: &'static str,
+    },
+    InvalidCap,
+    ProofFailed,
+    WorkerPanicked {
+        message: String,
+    },
+    /// The peer's proof derives with another hash function than ours
+    HashAlgMismatch {
+        expected: util::HashAlg,
+        received: util::HashAlg,
+    },
+}
+
+impl fmt::Display for VdfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VdfError::InvalidParameter { parameter, reason } => {
+                write!(f, "Invalid {}: it {}", parameter, reason)
+            }
+            VdfError::InvalidCap => write!(f, "The cap is not a prime"),
+            VdfError::ProofFailed => {
+                write!(f, "The proof couldn't be calculated")
+            }
+            VdfError::WorkerPanicked { message } => {
+                write!(f, "The VDF worker panicked: {}", message)
+            }
+            VdfError::HashAlgMismatch { expected, received } => write!(
+                f,
+                "The proof derives with {}, expected {}",
+                received, expected
+            ),
+        }
+    }
+}
+
+impl Error for VdfError {
+    fn description(&self) -> &str {
+        "The VDF run failed!"
+    }
+}
+
+/// InvalidProofError is returned when a proof is constructed from values no
+/// VDF evaluation could have produced
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvalidProofError {
+    ModulusTooSmall,
+    CapTooSmall,
+    ExponentTooSmall(u32),
+}
+
+impl fmt::Display for InvalidProofError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidProofError::ModulusTooSmall => {
+                write!(f, "The modulus must be at least 3")
+            }
+            InvalidProofError::CapTooSmall => {
+                write!(f, "The cap must be at least 2")
+            }
+            InvalidProofError::ExponentTooSmall(exponent) => {
+                write!(f, "The exponent must be at least 2, got {}", exponent)
+            }
+        }
+    }
+}
+
+impl Error for InvalidProofError {
+    fn description(&self) -> &str {
+        "Invalid proof!"
+    }
+}
+
+/// VerifyError says why a proof doesn't verify
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyError {
+    /// The proof isn't an element of the group
+    ProofOutOfRange,
+    /// The cap isn't prime, or its certificate doesn't prove it
+    CapNotPrime,
+    /// The values are fine but don't satisfy the verification equation
+    EquationMismatch,
+    /// The named value would let the equation hold for too many choices of
+    /// the others
+    DegenerateValue(&'static str),
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::ProofOutOfRange => {
+                write!(f, "The proof is outside the group")
+            }
+            VerifyError::CapNotPrime => write!(f, "The cap is not prime"),
+            VerifyError::EquationMismatch => {
+                write!(f, "The proof doesn't satisfy the verification equation")
+            }
+            VerifyError::DegenerateValue(value) => {
+                write!(f, "The {} is degenerate", value)
+            }
+        }
+    }
+}
+
+impl Error for VerifyError {
+    fn description(&self) -> &str {
+        "The proof doesn't verify!"
+    }
+}
+
+/// DecodeError is returned when bytes aren't a proof in the canonical
+/// encoding
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    UnknownVersion(u8),
+    Truncated(usize),
+    UnknownProofType(u8),
+    UnknownFlag(u8),
+    TrailingBytes(usize),
+    InvalidProof(InvalidProofError),
+    InvalidHex(usize),
+    InvalidBase64(usize),
+    RedundantExponent(usize),
+    UnknownHashAlg(u8),
+    RedundantHashAlg(usize),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::UnknownVersion(version) => {
+                write!(f, "Unknown proof encoding version {}", version)
+            }
+            DecodeError::Truncated(len) => {
+                write!(f, "The proof ends abruptly at byte {}", len)
+            }
+            DecodeError::UnknownProofType(proof_type) => {
+                write!(f, "Unknown proof type {}", proof_type)
+            }
+            DecodeError::UnknownFlag(flag) => {
+                write!(f, "Unknown certificate flag {}", flag)
+            }
+            DecodeError::TrailingBytes(offset) => {
+                write!(f, "Unexpected bytes after the proof at byte {}", offset)
+            }
+            DecodeError::InvalidProof(error) => write!(f, "{}", error),
+            DecodeError::InvalidHex(offset) => {
+                write!(f, "Invalid hex at character {}", offset)
+            }
+            DecodeError::InvalidBase64(offset) => {
+                write!(f, "Invalid base64 at character {}", offset)
+            }
+            DecodeError::RedundantExponent(offset) => write!(
+                f,
+                "The exponent 2 at byte {} belongs in the version 1 encoding",
+                offset
+            ),
+            DecodeError::UnknownHashAlg(id) => {
+                write!(f, "Unknown hash function {}", id)
+            }
+            DecodeError::RedundantHashAlg(offset) => write!(
+                f,
+                "The default hash function at byte {} belongs in the version \
+                 1 or 2 encoding",
+                offset
+            ),
+        }
+    }
+}
+
+impl Error for DecodeError {
+    fn description(&self) -> &str {
+        "Invalid proof encoding!"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primality::PrimePool;
+    use crossbeam::channel::unbounded;
+    use proptest::prelude::*;
+    use ramp::Int;
+    use std::sync::{Arc, Mutex};
+    use std::{thread, time};
+    use test::Bencher;
+    use tracing::field::{Field, Visit};
+    use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+    use tracing_subscriber::Registry;
+
+    #[test]
+    fn moduli_have_one_source_of_truth() {
+        assert_eq!(crate::RSA_2048, moduli::RSA_2048);
+        assert_eq!(
+            ModulusChoice::Rsa2048.int(),
+            &Int::from_str_radix(crate::RSA_2048, 10).unwrap()
+        );
+        assert_eq!(ModulusChoice::Rsa2048.bits(), 2048);
+        assert_eq!("rsa-2048".parse(), Ok(ModulusChoice::Rsa2048));
+    }
+
+    #[test]
+    fn custom_moduli_are_validated() {
+        let rsa = ModulusChoice::Rsa2048.int().clone();
+        let custom = ModulusChoice::Custom(rsa.clone());
+        assert_eq!(custom.validate(), Ok(()));
+        assert_eq!(rsa.to_str_radix(10, false).parse(), Ok(custom.clone()));
+        assert_eq!(format!("0x{}", util::int_to_hex(&rsa)).parse(), Ok(custom));
+
+        // 2^1279 - 1 is a Mersenne prime
+        let prime = (Int::from(1) << 1279) - Int::from(1);
+        assert_eq!(
+            ModulusChoice::Custom(prime).validate(),
+            Err(InvalidModulusError::Prime)
+        );
+        assert_eq!(
+            ModulusChoice::Custom(&rsa + Int::from(1)).validate(),
+            Err(InvalidModulusError::Even)
+        );
+        assert_eq!(
+            ModulusChoice::Custom(Int::from(3233)).validate(),
+            Err(InvalidModulusError::TooSmall(12))
+        );
+        assert_eq!(
+            "rsa-1024".parse::<ModulusChoice>(),
+            Err(InvalidModulusError::Unknown("rsa-1024".to_string()))
+        );
+    }
+
+    #[test]
+    fn is_deterministic() {
+        let modulus = ModulusChoice::Rsa2048;
+        let prime = primality::gen_safe_prime(128, &mut rand::thread_rng());
+        let root_hashed = util::hash_with_domain(
+            util::SEED_DOMAIN,
+            prime.to_string().as_bytes(),
+            modulus.int(),
+        );
+
+        // Create two VDFs with same inputs to check if they end up in the same
+        // result
+        let cap = primality::gen_safe_prime(128, &mut rand::thread_rng());
+        let verifiers_vdf = evaluation::VDF::new(
+            modulus.clone(),
+            root_hashed.clone(),
+            256,
+            proof::ProofType::Sequential,
+        )
+        .unwrap()
+        .with_cap(cap.clone())
+        .unwrap();
+        let provers_vdf = evaluation::VDF::new(
+            modulus,
+            root_hashed,
+            256,
+            proof::ProofType::Parallel,
+        )
+        .unwrap()
+        .with_cap(cap)
+        .unwrap();
+
+        let (_, receiver) = verifiers_vdf.run_vdf_worker();
+        let (_, receiver2) = provers_vdf.run_vdf_worker();
+
+        if let Ok(Ok(proof)) = receiver.recv() {
+            assert!(proof.verify());
+
+            let our_proof = proof;
+
+            if let Ok(Ok(proof2)) = receiver2.recv() {
+                assert!(proof2.verify());
+                let their_proof = proof2;
+                assert_eq!(our_proof.output.result, their_proof.output.result);
+                assert!(our_proof.pi > Int::from(1));
+                assert_eq!(our_proof.pi, their_proof.pi);
+            }
+        }
+    }
+
+    #[test]
+    fn result_arrives_before_the_proof() {
+        let modulus = ModulusChoice::Rsa2048;
+        let generator =
+            util::hash_with_domain(util::SEED_DOMAIN, b"early", modulus.int());
+        let (result_sender, results) = unbounded();
+        let vdf = evaluation::VDF::new(
+            modulus,
+            generator,
+            u32::MAX,
+            proof::ProofType::Sequential,
+        )
+        .unwrap()
+        .with_result_sender(result_sender);
+
+        let (capper, receiver) = vdf.run_vdf_worker();
+        thread::sleep(time::Duration::from_millis(200));
+        capper
+            .send(primality::gen_safe_prime(64, &mut rand::thread_rng()))
+            .unwrap();
+
+        let result = results.recv().unwrap();
+        assert!(receiver.is_empty());
+        let proof = receiver.recv().unwrap().unwrap();
+        assert_eq!(proof.output, result);
+        assert!(proof.verify());
+    }
+
+    #[test]
+    fn pending_proof_matches_the_direct_construction() {
+        let modulus = ModulusChoice::Rsa2048;
+        let generator = util::hash_with_domain(
+            util::SEED_DOMAIN,
+            b"pending",
+            modulus.int(),
+        );
+        let mut vdf = evaluation::VDF::new(
+            modulus,
+            generator,
+            500,
+            proof::ProofType::Sequential,
+        )
+        .unwrap();
+        let result = vdf.by_ref().last().unwrap();
+        let cap = primality::gen_safe_prime(64, &mut rand::thread_rng());
+
+        let pending = proof::VDFProof::start(
+            &vdf.modulus,
+            &vdf.generator,
+            &result,
+            &proof::ProofType::Sequential,
+        );
+        assert_eq!(pending.result(), &result);
+        let proof = pending.finish(&cap).unwrap();
+        assert!(proof.verify());
+        assert_eq!(
+            Some(proof),
+            proof::VDFProof::new(
+                &vdf.modulus,
+                &vdf.generator,
+                &result,
+                &cap,
+                &proof::ProofType::Sequential,
+            )
+            .unwrap()
+            .calculate()
+        );
+    }
+
+    fn evaluate_with_checkpoints(
+        iterations: u32,
+        k: usize,
+    ) -> (evaluation::VDF, evaluation::VDFResult) {
+        let modulus = ModulusChoice::Rsa2048;
+        let generator = util::hash_with_domain(
+            util::SEED_DOMAIN,
+            b"checkpoints",
+            modulus.int(),
+        );
+        let mut vdf = evaluation::VDF::new(
+            modulus,
+            generator,
+            iterations,
+            proof::ProofType::Sequential,
+        )
+        .unwrap()
+        .with_proof_checkpoints(k);
+        let result = vdf.by_ref().last().unwrap();
+        (vdf, result)
+    }
+
+    #[test]
+    fn checkpointed_proof_matches_the_naive_proof() {
+        let cap = primality::gen_safe_prime(128, &mut rand::thread_rng());
+        let (vdf, result) = evaluate_with_checkpoints(10_000, 1);
+        assert_eq!(result.iterations, 10_000);
+        let naive = proof::VDFProof::start(
+            &vdf.modulus,
+            &vdf.generator,
+            &result,
+            &proof::ProofType::Sequential,
+        )
+        .finish(&cap)
+        .unwrap();
+        assert!(naive.verify());
+
+        for k in [1, 2, 7, 10, 13].iter() {
+            // The evaluation is already finished, so the worker goes
+            // straight to proving from the stored checkpoints
+            let (vdf, result) = evaluate_with_checkpoints(10_000, *k);
+            let (_, receiver) =
+                vdf.with_cap(cap.clone()).unwrap().run_vdf_worker();
+            let proof = receiver.recv().unwrap().unwrap();
+            assert_eq!(proof.output, result);
+            assert_eq!(proof.deserialize(), naive.deserialize());
+        }
+    }
+
+    fn quick_proof(modulus: &ModulusChoice, seed: &[u8]) -> proof::VDFProof {
+        let generator =
+            util::hash_with_domain(util::SEED_DOMAIN, seed, modulus.int());
+        let vdf = evaluation::VDF::new(
+            modulus.clone(),
+            generator,
+            64,
+            proof::ProofType::Sequential,
+        )
+        .unwrap()
+        .with_cap(primality::gen_safe_prime(64, &mut rand::thread_rng()))
+        .unwrap();
+        let (_, receiver) = vdf.run_vdf_worker();
+        receiver.recv().unwrap().unwrap()
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(16))]
+
+        #[test]
+        fn proofs_verify_with_any_safe_prime_cap(
+            cap in test_support::safe_prime(32),
+            iterations in 1u32..200,
+        ) {
+            let proof = test_support::prove(b"safe prime", iterations, cap);
+            prop_assert!(proof.verify());
+        }
+
+        #[test]
+        fn tampering_any_field_breaks_verify(
+            proof in test_support::honest_proof(200, 32),
+            field in test_support::proof_field(),
+            delta in 1i64..1000,
+        ) {
+            prop_assert!(proof.verify());
+            let tampered = test_support::mutate_proof(&proof, field, delta);
+            prop_assert!(!tampered.verify(), "{:?} + {} verified", field, delta);
+        }
+    }
+
+    /// Every field is bound by the verification equation: the modulus as it
+    /// reduces both sides, the cap, the iterations and the exponent k through
+    /// the remainder k^T mod cap and the exponent of pi, and the rest directly
+    #[test]
+    fn perturbing_any_field_breaks_verify() {
+        let proof = test_support::prove(
+            b"mutation",
+            50,
+            primality::gen_safe_prime(64, &mut rand::thread_rng()),
+        );
+        assert!(proof.verify());
+        for field in test_support::PROOF_FIELDS.iter() {
+            for delta in [-1, 1].iter() {
+                let mutated =
+                    test_support::mutate_proof(&proof, *field, *delta);
+                assert!(!mutated.verify(), "{:?} {:+} verified", field, delta);
+            }
+        }
+
+        let mut swapped = proof.clone();
+        std::mem::swap(&mut swapped.output.result, &mut swapped.pi);
+        assert!(!swapped.verify());
+    }
+
+    #[test]
+    fn degenerate_proofs_fail() {
+        let modulus = ModulusChoice::Rsa2048;
+        let honest = quick_proof(&modulus, b"degenerate");
+        assert!(honest.verify());
+
+        let n = modulus.int().clone();
+        let minus_one = &n - &Int::one();
+        let tampered = |tamper: &dyn Fn(&mut proof::VDFProof)| {
+            let mut proof = honest.clone();
+            tamper(&mut proof);
+            proof
+        };
+        let degenerate = [Int::zero(), Int::one(), minus_one.clone(), n];
+        let mut proofs = Vec::new();
+        for value in degenerate.iter() {
+            proofs.push(tampered(&|proof| proof.pi = value.clone()));
+            proofs.push(tampered(&|proof| proof.output.result = value.clone()));
+            proofs.push(tampered(&|proof| proof.generator = value.clone()));
+        }
+        // Equations that hold trivially: 0 = 0^l * g^r and 1 = 1^l * 1^r
+        proofs.push(tampered(&|proof| {
+            proof.pi = Int::zero();
+            proof.output.result = Int::zero();
+        }));
+        proofs.push(tampered(&|proof| {
+            proof.pi = Int::one();
+            proof.generator = Int::one();
+            proof.output.result = Int::one();
+        }));
+        // (-1)^odd = -1 for any odd cap
+        proofs.push(tampered(&|proof| {
+            proof.pi = minus_one.clone();
+            proof.generator = Int::one();
+            proof.output.result = minus_one.clone();
+        }));
+        proofs.push(tampered(&|proof| proof.output.iterations = 0));
+        proofs.push(tampered(&|proof| proof.cap = &proof.cap + Int::one()));
+        proofs.push(tampered(&|proof| {
+            proof.cap = Int::from(7);
+            proof.cap_certificate = None;
+        }));
+
+        for (index, proof) in proofs.iter().enumerate() {
+            assert!(!proof.verify(), "Degenerate proof {} verified", index);
+        }
+        assert_eq!(verify_batch(&proofs), vec![false; proofs.len()]);
+    }
+
+    #[test]
+    fn verify_raw_says_why_a_proof_fails() {
+        let modulus = ModulusChoice::Rsa2048;
+        let honest = quick_proof(&modulus, b"raw");
+        let n = modulus.int().clone();
+        let raw = |tamper: &dyn Fn(&mut proof::VDFProof)| {
+            let mut proof = honest.clone();
+            tamper(&mut proof);
+            verify_raw(
+                &proof.modulus,
+                &proof.generator,
+                &proof.output.result,
+                u64::from(proof.output.iterations),
+                &proof.cap,
+                &proof.pi,
+            )
+        };
+        let degenerate = VerifyError::DegenerateValue;
+        assert_eq!(raw(&|_| {}), Ok(()));
+        assert_eq!(
+            raw(&|proof| proof.pi = n.clone()),
+            Err(VerifyError::ProofOutOfRange)
+        );
+        assert_eq!(
+            raw(&|proof| proof.pi = Int::from(-2)),
+            Err(VerifyError::ProofOutOfRange)
+        );
+        assert_eq!(
+            raw(&|proof| proof.pi = Int::one()),
+            Err(degenerate("proof"))
+        );
+        assert_eq!(
+            raw(&|proof| proof.generator = Int::one()),
+            Err(degenerate("base"))
+        );
+        assert_eq!(
+            raw(&|proof| proof.output.result = &n - &Int::one()),
+            Err(degenerate("result"))
+        );
+        assert_eq!(
+            raw(&|proof| proof.output.iterations = 0),
+            Err(degenerate("iterations"))
+        );
+        assert_eq!(
+            raw(&|proof| proof.modulus = Int::from(2)),
+            Err(degenerate("modulus"))
+        );
+        assert_eq!(
+            raw(&|proof| proof.cap = Int::from(7)),
+            Err(degenerate("cap"))
+        );
+        assert_eq!(
+            raw(&|proof| proof.cap = &proof.cap + Int::one()),
+            Err(VerifyError::CapNotPrime)
+        );
+        assert_eq!(
+            raw(&|proof| proof.cap = proof.cap.clone() * Int::from(3)),
+            Err(VerifyError::CapNotPrime)
+        );
+        assert_eq!(
+            raw(&|proof| proof.output.iterations += 1),
+            Err(VerifyError::EquationMismatch)
+        );
+
+        // VDFProof::check gives the same reasons, and also checks the
+        // exponent and the certificate
+        let mut proof = honest.clone();
+        proof.output.iterations += 1;
+        assert_eq!(proof.check(), Err(VerifyError::EquationMismatch));
+        assert!(!proof.verify());
+        proof = honest.clone();
+        proof.exponent = 1;
+        assert_eq!(proof.check(), Err(degenerate("exponent")));
+        let certified = inline_vdf(10).run_sync().unwrap();
+        let mut proof = certified.clone();
+        proof.cap_certificate.as_mut().unwrap().steps.clear();
+        assert_eq!(proof.check(), Err(VerifyError::CapNotPrime));
+        assert_eq!(certified.check(), Ok(()));
+
+        // Proofs of other exponents only verify with their exponent
+        let cubed = power_vdf(3, proof::ProofType::Sequential)
+            .with_cap(primality::gen_safe_prime(64, &mut rand::thread_rng()))
+            .unwrap()
+            .run_sync()
+            .unwrap();
+        let raw_cubed = |exponent| {
+            verify_raw_with_exponent(
+                &cubed.modulus,
+                &cubed.generator,
+                &cubed.output.result,
+                u64::from(cubed.output.iterations),
+                exponent,
+                &cubed.cap,
+                &cubed.pi,
+            )
+        };
+        assert_eq!(cubed.check(), Ok(()));
+        assert_eq!(raw_cubed(3), Ok(()));
+        assert_eq!(raw_cubed(2), Err(VerifyError::EquationMismatch));
+        assert_eq!(raw_cubed(1), Err(degenerate("exponent")));
+        assert_eq!(
+            VerifyError::EquationMismatch.to_string(),
+            "The proof doesn't satisfy the verification equation"
+        );
+    }
+
+    #[test]
+    fn batch_verification_finds_the_corrupted_proof() {
+        let modulus = ModulusChoice::Rsa2048;
+        let mut proofs: Vec<proof::VDFProof> = [b"a", b"a", b"b", b"c", b"a"]
+            .iter()
+            .map(|seed| quick_proof(&modulus, *seed))
+            .collect();
+        assert_eq!(verify_batch(&proofs), vec![true; 5]);
+
+        proofs[3].pi = &proofs[3].pi + Int::from(1);
+        assert_eq!(verify_batch(&proofs), vec![true, true, true, false, true]);
+
+        // The output may not hide outside the group
+        proofs[3] = quick_proof(&modulus, b"c");
+        proofs[1].output.result = &proofs[1].output.result + modulus.int();
+        let expected: Vec<bool> = proofs.iter().map(|p| p.verify()).collect();
+        assert_eq!(expected, vec![true, false, true, true, true]);
+        assert_eq!(verify_batch(&proofs), expected);
+    }
+
+    /// (N - y)^w = -(y^w) for odd w, so two negated outputs cancel out in a
+    /// product of weighted equations
+    #[test]
+    fn batch_verification_rejects_negated_outputs() {
+        let modulus = ModulusChoice::Rsa2048;
+        let mut proofs: Vec<proof::VDFProof> = [b"a", b"b", b"c"]
+            .iter()
+            .map(|seed| quick_proof(&modulus, *seed))
+            .collect();
+        for proof in proofs.iter_mut().take(2) {
+            proof.output.result = modulus.int() - &proof.output.result;
+        }
+        let expected: Vec<bool> = proofs.iter().map(|p| p.verify()).collect();
+        assert_eq!(expected, vec![false, false, true]);
+        for _ in 0..8 {
+            assert_eq!(verify_batch(&proofs), expected);
+        }
+    }
+
+    #[test]
+    fn batch_verification_mixes_moduli() {
+        // Any odd composite will do as a second group
+        let other =
+            ModulusChoice::Custom(ModulusChoice::Rsa2048.int() * Int::from(3));
+        let proofs = vec![
+            quick_proof(&ModulusChoice::Rsa2048, b"a"),
+            quick_proof(&other, b"a"),
+            quick_proof(&ModulusChoice::Rsa2048, b"b"),
+        ];
+        assert_eq!(verify_batch(&proofs), vec![true; 3]);
+        assert!(verify_batch(&[]).is_empty());
+
+        let mut forged = proofs.clone();
+        forged[1].cap = Int::from(15);
+        assert_eq!(verify_batch(&forged), vec![true, false, true]);
+    }
+
+    /// Short proofs from distinct generators sharing one cap
+    #[cfg(feature = "parallel")]
+    fn unrelated_proofs(count: usize) -> Vec<proof::VDFProof> {
+        let modulus = ModulusChoice::Rsa2048;
+        let cap = primality::gen_safe_prime(64, &mut rand::thread_rng());
+        (0..count)
+            .map(|index| {
+                let generator = util::hash_with_domain(
+                    util::SEED_DOMAIN,
+                    &index.to_be_bytes(),
+                    modulus.int(),
+                );
+                evaluation::VDF::new(
+                    modulus.clone(),
+                    generator,
+                    16,
+                    proof::ProofType::Sequential,
+                )
+                .unwrap()
+                .with_cap(cap.clone())
+                .unwrap()
+                .run_sync()
+                .unwrap()
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_batches_match_verifying_each_proof() {
+        let mut proofs = unrelated_proofs(300);
+        for proof in proofs.iter_mut().step_by(7) {
+            proof.pi = &proof.pi + Int::one();
+        }
+        let expected: Vec<bool> = proofs.iter().map(|p| p.verify()).collect();
+        assert_eq!(expected.iter().filter(|valid| !**valid).count(), 43);
+        assert_eq!(verify_batch_parallel(&proofs), expected);
+        assert!(verify_batch_parallel(&[]).is_empty());
+
+        // Small batches stay on the calling thread with the same results
+        let small = &proofs[..PARALLEL_BATCH_THRESHOLD - 1];
+        assert_eq!(
+            verify_batch_parallel(small),
+            &expected[..PARALLEL_BATCH_THRESHOLD - 1]
+        );
+        let checked = check_batch_parallel(&proofs);
+        assert_eq!(checked[0], Err(VerifyError::EquationMismatch));
+        assert_eq!(checked[1], Ok(()));
+    }
+
+    fn evaluate(iterations: u32) -> evaluation::VDF {
+        let modulus = ModulusChoice::Rsa2048;
+        let generator = util::hash_with_domain(
+            util::SEED_DOMAIN,
+            b"pietrzak",
+            modulus.int(),
+        );
+        let mut vdf = evaluation::VDF::new(
+            modulus,
+            generator,
+            iterations,
+            proof::ProofType::Sequential,
+        )
+        .unwrap();
+        vdf.result = vdf.by_ref().last().unwrap();
+        vdf
+    }
+
+    #[test]
+    fn pietrzak_proofs_verify_for_any_number_of_iterations() {
+        for iterations in [1, 2, 3, 7, 64, 1000, 1023, 1025].iter() {
+            let vdf = evaluate(*iterations);
+            let proof = vdf.pietrzak_proof().unwrap();
+            assert!(proof.verify(), "T = {}", iterations);
+            assert_eq!(proof.output, vdf.result);
+            assert!(proof.midpoints.len() <= 11);
+        }
+        assert!(pietrzak::prove(
+            &Int::from(3),
+            ModulusChoice::Rsa2048.int(),
+            0,
+            &Int::from(3),
+            None
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn pietrzak_proof_uses_checkpoints() {
+        let (vdf, _) = evaluate_with_checkpoints(1000, 10);
+        let proof = vdf.pietrzak_proof().unwrap();
+        assert!(proof.verify());
+
+        let plain = pietrzak::prove(
+            &vdf.generator,
+            &vdf.modulus,
+            vdf.result.iterations,
+            &vdf.result.result,
+            None,
+        )
+        .unwrap();
+        assert_eq!(proof, plain);
+    }
+
+    #[test]
+    fn tampered_pietrzak_proofs_fail() {
+        let proof = evaluate(1000).pietrzak_proof().unwrap();
+
+        for i in 0..proof.midpoints.len() {
+            let mut tampered = proof.clone();
+            tampered.midpoints[i] =
+                &tampered.midpoints[i] * Int::from(2) % &tampered.modulus;
+            assert!(!tampered.verify(), "midpoint {}", i);
+        }
+
+        let mut truncated = proof.clone();
+        truncated.midpoints.pop();
+        assert!(!truncated.verify());
+
+        let mut extended = proof.clone();
+        extended.midpoints.push(Int::from(4));
+        assert!(!extended.verify());
+
+        let mut wrong_output = proof.clone();
+        wrong_output.output.result = &proof.output.result + Int::from(1);
+        assert!(!wrong_output.verify());
+
+        let mut wrong_iterations = proof;
+        wrong_iterations.output.iterations += 1;
+        assert!(!wrong_iterations.verify());
+    }
+
+    fn prove_with_cap(vdf: evaluation::VDF) -> proof::VDFProof {
+        let (_, receiver) = vdf
+            .with_cap(primality::gen_safe_prime(64, &mut rand::thread_rng()))
+            .unwrap()
+            .run_vdf_worker();
+        receiver.recv().unwrap().unwrap()
+    }
+
+    fn chain_of_three() -> Vec<proof::VDFProof> {
+        let mut chain = vec![quick_proof(&ModulusChoice::Rsa2048, b"chain")];
+        for _ in 0..2 {
+            let next =
+                evaluation::VDF::extend_from(chain.last().unwrap()).unwrap();
+            chain.push(prove_with_cap(next));
+        }
+        chain
+    }
+
+    #[test]
+    fn proofs_chain_from_the_previous_output() {
+        let chain = chain_of_three();
+        assert_eq!(chain[1].generator, chain[0].output.result);
+        assert_eq!(chain[2].generator, chain[1].output.result);
+        assert!(proof::VDFProof::verify_chain(&chain));
+        assert_eq!(proof::VDFProof::check_chain(&chain), Ok(3 * 64));
+        assert_eq!(proof::VDFProof::check_chain(&[]), Err(ChainError::Empty));
+    }
+
+    #[test]
+    fn broken_chains_report_the_failing_link() {
+        let chain = chain_of_three();
+
+        let mut invalid = chain.clone();
+        invalid[2].pi = &invalid[2].pi + Int::from(1);
+        assert_eq!(
+            proof::VDFProof::check_chain(&invalid),
+            Err(ChainError::InvalidProof(2))
+        );
+
+        // A valid proof that starts from somewhere else
+        let mut rebased = chain.clone();
+        rebased[1] = quick_proof(&ModulusChoice::Rsa2048, b"elsewhere");
+        assert_eq!(
+            proof::VDFProof::check_chain(&rebased),
+            Err(ChainError::BaseMismatch(1))
+        );
+
+        // A valid proof from the right base, but in another group
+        let mut moved = chain.clone();
+        let other =
+            ModulusChoice::Custom(ModulusChoice::Rsa2048.int() * Int::from(3));
+        moved[2] = prove_with_cap(
+            evaluation::VDF::new(
+                other,
+                chain[1].output.result.clone(),
+                64,
+                proof::ProofType::Sequential,
+            )
+            .unwrap(),
+        );
+        assert!(moved[2].verify());
+        assert_eq!(
+            proof::VDFProof::check_chain(&moved),
+            Err(ChainError::ModulusChanged(2))
+        );
+
+        // A link that claims the previous output after zero iterations
+        let mut stalled = chain;
+        stalled[1].output.iterations = 0;
+        stalled[1].output.result = stalled[1].generator.clone();
+        assert_eq!(
+            proof::VDFProof::check_chain(&stalled),
+            Err(ChainError::NoProgress(1))
+        );
+    }
+
+    #[test]
+    fn intermediate_proofs_chain_up_to_the_final_proof() {
+        let modulus = ModulusChoice::Rsa2048;
+        let generator = util::hash_with_domain(
+            util::SEED_DOMAIN,
+            b"intermediate",
+            modulus.int(),
+        );
+        let vdf = evaluation::VDF::new(
+            modulus,
+            generator,
+            30000,
+            proof::ProofType::Sequential,
+        )
+        .unwrap()
+        .with_intermediate_proofs(10000, 64)
+        .with_cap(primality::gen_safe_prime(64, &mut rand::thread_rng()))
+        .unwrap();
+        let intermediate = vdf.intermediate_proofs().unwrap();
+
+        let (_, receiver) = vdf.run_vdf_worker();
+        let last = receiver.recv().unwrap().unwrap();
+        let proofs: Vec<proof::VDFProof> = intermediate.iter().collect();
+
+        assert_eq!(proofs.len(), 3);
+        for proof in proofs.iter() {
+            assert_eq!(proof.output.iterations, 10000);
+            assert!(proof.cap_certificate.is_some());
+        }
+        assert_eq!(proof::VDFProof::check_chain(&proofs), Ok(30000));
+        assert!(last.verify());
+        assert_eq!(last.generator, proofs[0].generator);
+        assert_eq!(last.output.result, proofs[2].output.result);
+    }
+
+    #[test]
+    fn proofs_round_trip_through_bytes() {
+        let modulus = ModulusChoice::Rsa2048;
+        let generator =
+            util::hash_with_domain(util::SEED_DOMAIN, b"bytes", modulus.int());
+        let (_, receiver) = evaluation::VDF::new(
+            modulus,
+            generator,
+            100,
+            proof::ProofType::Sequential,
+        )
+        .unwrap()
+        .with_cap_bits(64)
+        .run_vdf_worker();
+        let proof = receiver.recv().unwrap().unwrap();
+        assert!(proof.cap_certificate.is_some());
+
+        let bytes = proof.to_bytes();
+        let decoded = proof::VDFProof::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded, proof);
+        assert!(decoded.verify());
+
+        assert_eq!(
+            proof::VDFProof::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(DecodeError::Truncated(bytes.len() - 1))
+        );
+        let mut longer = bytes.clone();
+        longer.push(0);
+        assert_eq!(
+            proof::VDFProof::from_bytes(&longer),
+            Err(DecodeError::TrailingBytes(bytes.len()))
+        );
+        let mut version = bytes;
+        version[0] = 3;
+        assert_eq!(
+            proof::VDFProof::from_bytes(&version),
+            Err(DecodeError::UnknownVersion(3))
+        );
+        assert_eq!(
+            proof::VDFProof::from_bytes(&[]),
+            Err(DecodeError::Truncated(0))
+        );
+    }
+
+    fn snapshot_vdf() -> evaluation::VDF {
+        let modulus = ModulusChoice::Rsa2048;
+        let generator = util::hash_with_domain(
+            util::SEED_DOMAIN,
+            b"snapshots",
+            modulus.int(),
+        );
+        evaluation::VDF::new(
+            modulus,
+            generator,
+            55,
+            proof::ProofType::Sequential,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn snapshots_match_the_intermediate_states() {
+        let vdf = snapshot_vdf();
+        let modulus = vdf.modulus.clone();
+        let generator = vdf.generator.clone();
+        let snapshots: Vec<evaluation::VDFResult> = vdf.snapshots(10).collect();
+
+        let iterations: Vec<u32> = snapshots
+            .iter()
+            .map(|snapshot| snapshot.iterations)
+            .collect();
+        assert_eq!(iterations, vec![10, 20, 30, 40, 50, 55]);
+        for snapshot in snapshots.iter() {
+            let power = Int::from(2).pow(snapshot.iterations as usize);
+            assert_eq!(snapshot.result, generator.pow_mod(&power, &modulus));
+        }
+    }
+
+    #[test]
+    fn snapshots_resume_into_a_proof() {
+        let cap = primality::gen_safe_prime(64, &mut rand::thread_rng());
+        let uninterrupted = snapshot_vdf()
+            .with_cap(cap.clone())
+            .unwrap()
+            .run_sync()
+            .unwrap();
+
+        let mut snapshots = snapshot_vdf().snapshots(10);
+        assert_eq!(snapshots.nth(2).unwrap().iterations, 30);
+        let resumed = snapshots
+            .into_vdf()
+            .with_cap(cap)
+            .unwrap()
+            .run_sync()
+            .unwrap();
+        assert!(resumed.verify());
+        assert_eq!(resumed, uninterrupted);
+    }
+
+    #[test]
+    fn the_worker_streams_snapshots() {
+        use futures::StreamExt;
+
+        let (sender, stream) = futures::channel::mpsc::unbounded();
+        let (_, receiver) = snapshot_vdf()
+            .with_snapshot_sender(10, sender)
+            .with_cap(primality::gen_safe_prime(64, &mut rand::thread_rng()))
+            .unwrap()
+            .run_vdf_worker();
+        let proof = receiver.recv().unwrap().unwrap();
+
+        let snapshots: Vec<evaluation::VDFResult> =
+            futures::executor::block_on(stream.collect());
+        let iterations: Vec<u32> = snapshots
+            .iter()
+            .map(|snapshot| snapshot.iterations)
+            .collect();
+        assert_eq!(iterations, vec![10, 20, 30, 40, 50]);
+        assert_eq!(snapshots.last().unwrap().result, {
+            let mut rest = snapshot_vdf();
+            rest.upper_bound = 50;
+            rest.evaluate().result.clone()
+        });
+        assert_eq!(proof.output.iterations, 55);
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        progress: Vec<u32>,
+        caps: Vec<Int>,
+        proof_started: bool,
+    }
+
+    impl evaluation::VdfObserver for Recorder {
+        fn on_progress(&mut self, report: &evaluation::ProgressReport) {
+            assert!(!self.proof_started);
+            self.progress.push(report.iterations);
+        }
+
+        fn on_cap(&mut self, cap: &Int) {
+            self.caps.push(cap.clone());
+        }
+
+        fn on_proof_start(&mut self) {
+            self.proof_started = true;
+        }
+    }
+
+    fn inline_vdf(upper_bound: u32) -> evaluation::VDF {
+        let modulus = ModulusChoice::Rsa2048;
+        let generator =
+            util::hash_with_domain(util::SEED_DOMAIN, b"inline", modulus.int());
+        evaluation::VDF::new(
+            modulus,
+            generator,
+            upper_bound,
+            proof::ProofType::Sequential,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn run_with_stops_at_the_first_cap() {
+        let cap = primality::gen_safe_prime(64, &mut rand::thread_rng());
+        let mut recorder = Recorder::default();
+        let mut polls = 0;
+        let proof = inline_vdf(u32::MAX)
+            .run_with(&mut recorder, || {
+                polls += 1;
+                if polls == 1000 {
+                    Some(cap.clone())
+                } else {
+                    None
+                }
+            })
+            .unwrap();
+
+        assert!(proof.verify());
+        assert_eq!(proof.output.iterations, 1000);
+        assert_eq!(recorder.progress.len(), 1000);
+        assert!(recorder.progress.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(recorder.caps, vec![cap]);
+        assert!(recorder.proof_started);
+    }
+
+    #[test]
+    fn run_with_takes_closures_and_generates_caps() {
+        let mut reported = Vec::new();
+        let proof = inline_vdf(450)
+            .with_cap_bits(64)
+            .with_poll_interval(100)
+            .run_with(
+                &mut |report: &evaluation::ProgressReport| {
+                    reported.push(report.iterations)
+                },
+                || None,
+            )
+            .unwrap();
+        assert_eq!(reported, vec![100, 200, 300, 400]);
+        assert_eq!(proof.output.iterations, 450);
+        assert!(proof.cap_certificate.is_some());
+        assert!(proof.verify());
+
+        let rejected = inline_vdf(u32::MAX)
+            .run_with(&mut Recorder::default(), || Some(Int::from(15)));
+        assert_eq!(rejected.unwrap_err(), VdfError::InvalidCap);
+    }
+
+    #[test]
+    fn worker_handles_broadcast_to_every_subscriber() {
+        use worker::WorkerMessage;
+
+        let handle =
+            inline_vdf(u32::MAX).with_poll_interval(1000).spawn_worker();
+        let first = handle.subscribe();
+        let second = handle.subscribe();
+        drop(handle.subscribe());
+        let report = match first.recv().unwrap() {
+            WorkerMessage::Progress(report) => report,
+            message => panic!("Unexpected {:?}", message),
+        };
+        assert_eq!(report.iterations, 1000);
+        assert_eq!(second.recv().unwrap(), WorkerMessage::Progress(report));
+        assert!(handle
+            .send_cap(primality::gen_safe_prime(64, &mut rand::thread_rng())));
+
+        let first: Vec<WorkerMessage> = first.iter().collect();
+        let second: Vec<WorkerMessage> = second.iter().collect();
+        assert_eq!(first, second);
+        let (finished, progress) = first.split_last().unwrap();
+        for (i, message) in progress.iter().enumerate() {
+            match message {
+                WorkerMessage::Progress(report) => {
+                    assert_eq!(report.iterations, 1000 * (i as u32 + 2))
+                }
+                message => panic!("Unexpected {:?}", message),
+            }
+        }
+        let proof = match finished {
+            WorkerMessage::Finished(Ok(proof)) => proof.clone(),
+            message => panic!("Unexpected {:?}", message),
+        };
+        assert!(proof.verify());
 
-/// InvalidCapError is returned when a non-prime cap is received in the
-/// vdf_worker
-#[derive(Debug)]
-pub struct InvalidCapError;
+        let late: Vec<WorkerMessage> = handle.subscribe().iter().collect();
+        assert_eq!(late, vec![finished.clone()]);
+        assert_eq!(handle.join(), Ok(proof));
 
-impl fmt::Display for InvalidCapError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "Invalid cap value encountered!")
+        let rejected = inline_vdf(u32::MAX).spawn_worker();
+        let subscriber = rejected.subscribe();
+        rejected.send_cap(Int::from(15));
+        assert_eq!(
+            subscriber.iter().last(),
+            Some(WorkerMessage::Finished(Err(VdfError::InvalidCap)))
+        );
     }
-}
 
-impl Error for InvalidCapError {
-    fn description(&self) -> &str {
-        "Invalid cap value encountered!"
+    #[test]
+    fn slow_subscribers_lose_the_oldest_messages() {
+        use worker::{Outbox, WorkerMessage};
+
+        let metrics = Arc::new(crate::metrics::InMemoryMetrics::default());
+        let outbox = worker::Broadcast::new(16, Some(metrics.clone()));
+        let slow = outbox.subscribe();
+        drop(outbox.subscribe());
+        for iterations in 1..=100_000 {
+            outbox.progress(&report(iterations, 0));
+            assert!(slow.len() <= 16);
+        }
+        assert_eq!(slow.len(), 16);
+        assert_eq!(slow.dropped(), 100_000 - 16);
+        assert_eq!(
+            metrics.counter(crate::metrics::SUBSCRIBER_DROPS, &[]),
+            100_000 - 16
+        );
+
+        outbox.finish(Err(VdfError::ProofFailed));
+        let messages: Vec<WorkerMessage> = slow.iter().collect();
+        assert_eq!(messages.len(), 16);
+        assert_eq!(messages[0], WorkerMessage::Progress(report(99_986, 0)));
+        assert_eq!(
+            messages.last(),
+            Some(&WorkerMessage::Finished(Err(VdfError::ProofFailed)))
+        );
+        assert_eq!(slow.dropped(), 100_000 - 15);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use ramp::Int;
-    use ramp_primes::Generator;
-    use std::{thread, time};
-    use test::Bencher;
+    #[test]
+    fn worker_panics_arrive_as_errors() {
+        let metrics = Arc::new(crate::metrics::InMemoryMetrics::default());
+        let mut vdf = inline_vdf(u32::MAX)
+            .with_session(7)
+            .with_metrics(metrics.clone());
+        vdf.panic_at = Some(10);
+        let handle = vdf.spawn_worker();
+        let subscriber = handle.subscribe();
+
+        let panicked = Err(VdfError::WorkerPanicked {
+            message: "Panicking at 10".to_string(),
+        });
+        assert_eq!(
+            subscriber.iter().last(),
+            Some(worker::WorkerMessage::Finished(panicked.clone()))
+        );
+        assert_eq!(handle.join(), panicked);
+        assert_eq!(metrics.counter(crate::metrics::WORKER_PANICS, &[]), 1);
+
+        let mut vdf = inline_vdf(u32::MAX);
+        vdf.panic_at = Some(10);
+        let (_, receiver) = vdf.run_vdf_worker();
+        assert!(receiver.recv().is_err());
+    }
+
+    fn report(iterations: u32, ms: u64) -> evaluation::ProgressReport {
+        evaluation::ProgressReport::new(
+            iterations,
+            u32::MAX,
+            time::Duration::from_millis(ms),
+        )
+    }
+
+    #[test]
+    fn progress_reports_estimate_the_time_left() {
+        // 1000 iterations per second towards 10000, every 500ms
+        let mut progress = evaluation::ProgressEstimator::new(10_000);
+        let mut reports = Vec::new();
+        for step in 1..=4 {
+            reports.push(progress.report(
+                step * 500,
+                time::Duration::from_millis(u64::from(step) * 500),
+            ));
+        }
+        let last = reports.last().unwrap();
+        assert_eq!(last.target_iterations, Some(10_000));
+        assert_eq!(last.fraction_complete, Some(0.2));
+        assert_eq!(last.rate, 1000.0);
+        assert_eq!(last.eta, Some(time::Duration::from_secs(8)));
+        assert_eq!(reports[0].eta, Some(time::Duration::from_millis(9500)));
+
+        // Slowing down to 250 per second, the rolling rate forgets the
+        // faster reports once they're out of the window
+        let mut iterations = 2000;
+        let mut ms = 2000;
+        for _ in 0..evaluation::ROLLING_REPORTS {
+            iterations += 125;
+            ms += 500;
+            progress.report(iterations, time::Duration::from_millis(ms));
+        }
+        iterations += 125;
+        ms += 500;
+        let slow = progress.report(iterations, time::Duration::from_millis(ms));
+        assert_eq!(slow.rate, 250.0);
+        assert_eq!(slow.eta, Some(time::Duration::from_millis(27_500)));
 
-    const RSA_2048: &str = "2519590847565789349402718324004839857142928212620403202777713783604366202070759555626401852588078440691829064124951508218929855914917618450280848912007284499268739280728777673597141834727026189637501497182469116507761337985909570009733045974880842840179742910064245869181719511874612151517265463228221686998754918242243363725908514186546204357679842338718477444792073993423658482382428119816381501067481045166037730605620161967625613384414360383390441495263443219011465754445417842402092461651572335077870774981712577246796292638635637328991215483143816789988504044536402352738195137863656439121201039712282120720357";
+        // Waiting for a cap, there is no target to estimate towards
+        let mut open_ended = evaluation::ProgressEstimator::new(u32::MAX);
+        let report = open_ended.report(500, time::Duration::from_millis(500));
+        assert_eq!(report.rate, 1000.0);
+        assert_eq!(report.target_iterations, None);
+        assert_eq!(report.fraction_complete, None);
+        assert_eq!(report.eta, None);
+        assert_eq!(report.elapsed, time::Duration::from_millis(500));
+    }
 
     #[test]
-    fn is_deterministic() {
-        let modulus = Int::from_str_radix(RSA_2048, 10).unwrap();
-        let prime = Generator::new_safe_prime(128);
-        let root_hashed = util::hash_to_mod(&prime.to_string(), &modulus);
+    fn throttling_is_detected_after_the_window() {
+        // 1000 iterations per second, halved from 1s on
+        let mut detector = throttling::ThrottlingDetector::new(1000.0)
+            .with_window(time::Duration::from_millis(600));
+        let mut anomalies = Vec::new();
+        for ms in (0..=3000).step_by(250) {
+            let iterations = if ms <= 1000 {
+                ms
+            } else {
+                1000 + (ms - 1000) / 2
+            };
+            anomalies.extend(detector.observe(&report(iterations, ms as u64)));
+        }
 
-        // Create two VDFs with same inputs to check if they end up in the same
-        // result
-        let cap = Generator::new_safe_prime(128);
-        let verifiers_vdf = evaluation::VDF::new(
-            modulus.clone(),
-            root_hashed.clone(),
-            256,
+        let anomaly = throttling::RateAnomaly {
+            iterations: 1375,
+            since: time::Duration::from_millis(1000),
+            rate: 500.0,
+            ratio: 0.5,
+        };
+        assert_eq!(anomalies, vec![anomaly.clone()]);
+        assert_eq!(detector.anomaly(), Some(&anomaly));
+        assert!(detector.throttling_suspected());
+        assert_eq!(detector.uncertainty_factor(), 2.0);
+    }
+
+    #[test]
+    fn short_slowdowns_are_not_throttling() {
+        let mut detector = throttling::ThrottlingDetector::new(1000.0)
+            .with_window(time::Duration::from_millis(300));
+        let mut iterations = 0;
+        for ms in (100..=2000).step_by(100) {
+            // Every fifth interval runs at a tenth of the rate
+            iterations += if ms % 500 == 0 { 10 } else { 100 };
+            assert_eq!(detector.observe(&report(iterations, ms)), None);
+        }
+        assert!(!detector.throttling_suspected());
+        assert_eq!(detector.uncertainty_factor(), 1.0);
+    }
+
+    #[test]
+    fn invalid_constructions_say_which_parameter_failed() {
+        let rsa = ModulusChoice::Rsa2048;
+        let n = rsa.int().clone();
+        let invalid = |parameter, reason| {
+            Err::<(), _>(VdfError::InvalidParameter { parameter, reason })
+        };
+        let cases = vec![
+            (
+                ModulusChoice::Custom(Int::from(3)),
+                Int::from(2),
+                10,
+                invalid("modulus", "must be over 3"),
+            ),
+            (
+                ModulusChoice::Custom(Int::from(16)),
+                Int::from(3),
+                10,
+                invalid("modulus", "must be odd"),
+            ),
+            (
+                rsa.clone(),
+                Int::zero(),
+                10,
+                invalid("generator", "must be between 1 and the modulus"),
+            ),
+            (
+                rsa.clone(),
+                Int::from(1),
+                10,
+                invalid("generator", "must be between 1 and the modulus"),
+            ),
+            (
+                rsa.clone(),
+                n.clone(),
+                10,
+                invalid("generator", "must be between 1 and the modulus"),
+            ),
+            (
+                ModulusChoice::Custom(Int::from(15)),
+                Int::from(7),
+                10,
+                invalid("modulus", "is too short to be secure"),
+            ),
+            (
+                ModulusChoice::Custom((Int::one() << 1279) - Int::one()),
+                Int::from(3),
+                10,
+                invalid("modulus", "is prime"),
+            ),
+            (
+                ModulusChoice::Custom(&n * Int::from(3)),
+                Int::from(6),
+                10,
+                invalid("generator", "shares a factor with the modulus"),
+            ),
+            (
+                rsa.clone(),
+                Int::from(2),
+                0,
+                invalid("upper_bound", "must be at least 1"),
+            ),
+            (
+                rsa.clone(),
+                Int::from(2),
+                evaluation::MAX_UPPER_BOUND + 1,
+                invalid(
+                    "upper_bound",
+                    "must be at most 2^30, or u32::MAX for no bound",
+                ),
+            ),
+            (
+                rsa.clone(),
+                Int::from(2),
+                u32::MAX - 1,
+                invalid(
+                    "upper_bound",
+                    "must be at most 2^30, or u32::MAX for no bound",
+                ),
+            ),
+            (rsa.clone(), Int::from(2), 1, Ok(())),
+            (
+                rsa.clone(),
+                Int::from(2),
+                evaluation::MAX_UPPER_BOUND,
+                Ok(()),
+            ),
+            (rsa.clone(), Int::from(2), u32::MAX, Ok(())),
+            (
+                ModulusChoice::Custom(&n * Int::from(3)),
+                Int::from(7),
+                1,
+                Ok(()),
+            ),
+        ];
+        for (modulus, generator, upper_bound, expected) in cases {
+            let vdf = evaluation::VDF::new(
+                modulus,
+                generator,
+                upper_bound,
+                proof::ProofType::Sequential,
+            );
+            assert_eq!(vdf.map(|_| ()), expected);
+        }
+
+        // Small groups are only for tests, which have to ask for them
+        let small = evaluation::VDF::new_in_test_group(
+            ModulusChoice::Custom(Int::from(15)),
+            Int::from(7),
+            1,
+            proof::ProofType::Sequential,
+        );
+        assert!(small.is_ok());
+        let shared = evaluation::VDF::new_in_test_group(
+            ModulusChoice::Custom(Int::from(15)),
+            Int::from(6),
+            1,
+            proof::ProofType::Sequential,
+        );
+        assert_eq!(
+            shared.map(|_| ()),
+            invalid("generator", "shares a factor with the modulus")
+        );
+
+        let error = evaluation::VDF::new(
+            rsa.clone(),
+            n,
+            10,
             proof::ProofType::Sequential,
         )
-        .with_cap(cap.clone());
-        let provers_vdf = evaluation::VDF::new(
-            modulus,
-            root_hashed,
-            256,
-            proof::ProofType::Parallel,
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid generator: it must be between 1 and the modulus"
+        );
+
+        // Caps are checked right away instead of in the worker
+        let vdf = evaluation::VDF::new(
+            rsa,
+            Int::from(2),
+            10,
+            proof::ProofType::Sequential,
         )
-        .with_cap(cap);
+        .unwrap();
+        assert_eq!(
+            vdf.with_cap(Int::from(15)).map(|_| ()),
+            Err(VdfError::InvalidCap)
+        );
+    }
 
-        let (_, receiver) = verifiers_vdf.run_vdf_worker();
-        let (_, receiver2) = provers_vdf.run_vdf_worker();
+    #[test]
+    fn early_caps_are_held_until_the_minimum_iterations() {
+        let (capper, receiver) = inline_vdf(u32::MAX)
+            .with_min_iterations(50)
+            .run_vdf_worker();
+        capper
+            .send(primality::gen_safe_prime(64, &mut rand::thread_rng()))
+            .unwrap();
+        let proof = receiver.recv().unwrap().unwrap();
 
-        if let Ok(Ok(proof)) = receiver.recv() {
-            assert!(proof.verify());
+        assert_eq!(proof.output.iterations, 50);
+        assert!(proof.cap_arrived_early());
+        assert!(proof.cap_received_at < 50);
+        assert!(proof.verify());
 
-            let our_proof = proof;
+        // The arrival survives the JSON form but not the comparison
+        let json = proof.deserialize();
+        assert_eq!(json.cap_received_at, Some(proof.cap_received_at));
+        let mut parsed = json.try_serialize().unwrap();
+        assert_eq!(parsed.cap_received_at, proof.cap_received_at);
+        parsed.cap_received_at = 50;
+        assert!(!parsed.cap_arrived_early());
+        assert_eq!(parsed, proof);
+    }
 
-            if let Ok(Ok(proof2)) = receiver2.recv() {
-                assert!(proof2.verify());
-                let their_proof = proof2;
-                assert_eq!(our_proof.output.result, their_proof.output.result);
-                assert!(our_proof.pi > Int::from(1));
-                assert_eq!(our_proof.pi, their_proof.pi);
+    #[test]
+    fn worker_proofs_carry_ordered_timings() {
+        let (capper, receiver) = inline_vdf(u32::MAX).run_vdf_worker();
+        thread::sleep(time::Duration::from_millis(20));
+        capper
+            .send(primality::gen_safe_prime(64, &mut rand::thread_rng()))
+            .unwrap();
+        let proof = receiver.recv().unwrap().unwrap();
+        let timings = proof.timings.unwrap();
+        assert!(timings.is_ordered());
+        assert!(
+            timings.until_cap().unwrap() >= time::Duration::from_millis(20)
+        );
+        assert!(timings.until_cap() <= timings.evaluation());
+        assert!(timings.implied_rate(proof.output.iterations).unwrap() > 0.0);
+
+        let json = serde_json::to_string(&proof.deserialize()).unwrap();
+        let parsed: proof::DeserializableVDFProof =
+            serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.try_serialize().unwrap().timings, Some(timings));
+
+        // No cap arrives when the VDF runs to its upper bound
+        let (_, receiver) = inline_vdf(100).run_vdf_worker();
+        let proof = receiver.recv().unwrap().unwrap();
+        let timings = proof.timings.unwrap();
+        assert!(timings.is_ordered());
+        assert_eq!(timings.until_cap(), None);
+        let json = serde_json::to_value(&proof.deserialize()).unwrap();
+        assert!(json["timings"].get("cap_received_at").is_none());
+
+        let backwards = evaluation::VdfTimings {
+            proof_ready_at: timings.started_at,
+            ..timings
+        };
+        assert!(!backwards.is_ordered());
+        assert_eq!(formatting_fixture().deserialize().timings, None);
+    }
+
+    #[test]
+    fn min_iterations_scale_with_the_rtt() {
+        let rate = 1_000_000;
+        assert_eq!(
+            evaluation::min_iterations_for_rtt(evaluation::FLOOR_RTT, rate),
+            100
+        );
+        assert_eq!(
+            evaluation::min_iterations_for_rtt(time::Duration::ZERO, rate),
+            0
+        );
+        assert_eq!(
+            evaluation::min_iterations_for_rtt(
+                time::Duration::from_secs(u64::MAX),
+                rate
+            ),
+            u32::MAX
+        );
+    }
+
+    #[test]
+    fn measuring_the_rate_leaves_the_vdf_untouched() {
+        let vdf = inline_vdf(10);
+        assert!(vdf.measure_rate(time::Duration::from_millis(10)) > 0);
+        assert_eq!(vdf.result.iterations, 0);
+        assert_eq!(vdf.upper_bound, 10);
+    }
+
+    fn power_vdf(
+        exponent: u32,
+        proof_type: proof::ProofType,
+    ) -> evaluation::VDF {
+        let modulus = ModulusChoice::Rsa2048;
+        let generator =
+            util::hash_with_domain(util::SEED_DOMAIN, b"power", modulus.int());
+        evaluation::VDF::new(modulus, generator, 60, proof_type)
+            .unwrap()
+            .with_exponent(exponent)
+    }
+
+    #[test]
+    fn proofs_of_other_exponents_verify() {
+        let cap = primality::gen_safe_prime(64, &mut rand::thread_rng());
+        for exponent in [2u32, 3, 5].iter() {
+            let proof = power_vdf(*exponent, proof::ProofType::Sequential)
+                .with_cap(cap.clone())
+                .unwrap()
+                .run_sync()
+                .unwrap();
+            assert_eq!(proof.exponent, *exponent);
+            assert!(proof.verify(), "k = {}", exponent);
+
+            // The output is the generator raised to k^T
+            let power = Int::from(*exponent).pow(60);
+            assert_eq!(
+                proof.output.result,
+                proof.generator.pow_mod(&power, &proof.modulus)
+            );
+
+            // The other proof calculations agree
+            let (_, receiver) =
+                power_vdf(*exponent, proof::ProofType::Parallel)
+                    .with_cap(cap.clone())
+                    .unwrap()
+                    .run_vdf_worker();
+            let parallel = receiver.recv().unwrap().unwrap();
+            assert_eq!(parallel.pi, proof.pi);
+            let checkpointed =
+                power_vdf(*exponent, proof::ProofType::Sequential)
+                    .with_proof_checkpoints(4)
+                    .with_cap(cap.clone())
+                    .unwrap()
+                    .run_sync()
+                    .unwrap();
+            assert_eq!(checkpointed.pi, proof.pi);
+
+            let decoded =
+                proof::VDFProof::from_bytes(&proof.to_bytes()).unwrap();
+            assert_eq!(decoded, proof);
+            let json = serde_json::to_string(&proof.deserialize()).unwrap();
+            assert_eq!(json.contains("exponent"), *exponent != 2);
+            let parsed: proof::DeserializableVDFProof =
+                serde_json::from_str(&json).unwrap();
+            assert_eq!(parsed.serialize(), proof);
+
+            let mut squared = proof.clone();
+            squared.exponent = 2;
+            assert_eq!(squared.verify(), *exponent == 2);
+        }
+
+        let cubed = power_vdf(3, proof::ProofType::Sequential);
+        assert_eq!(cubed.clone().evaluate().iterations, 60);
+        assert!(cubed.pietrzak_proof().is_none());
+    }
+
+    #[test]
+    fn squaring_keeps_the_original_encoding() {
+        let proof = quick_proof(&ModulusChoice::Rsa2048, b"squaring");
+        let explicit = proof.clone().with_exponent(2);
+        assert_eq!(explicit.to_bytes(), proof.to_bytes());
+        assert_eq!(proof.to_bytes()[0], proof::ENCODING_VERSION);
+        assert_eq!(
+            serde_json::to_value(&proof.deserialize()).unwrap()["exponent"],
+            serde_json::Value::Null
+        );
+
+        // The exponent follows the iterations in the second version
+        let cubed = proof.with_exponent(3);
+        let mut bytes = cubed.to_bytes();
+        assert_eq!(bytes[0], proof::EXPONENT_ENCODING_VERSION);
+        let offset = 1
+            + [&cubed.modulus, &cubed.generator, &cubed.output.result]
+                .iter()
+                .map(|int| 4 + util::int_to_be_bytes(int).len())
+                .sum::<usize>()
+            + 4;
+        assert_eq!(bytes[offset..offset + 4], 3u32.to_be_bytes());
+
+        bytes[offset + 3] = 2;
+        assert_eq!(
+            proof::VDFProof::from_bytes(&bytes),
+            Err(DecodeError::RedundantExponent(offset))
+        );
+        bytes[offset + 3] = 1;
+        assert_eq!(
+            proof::VDFProof::from_bytes(&bytes),
+            Err(DecodeError::InvalidProof(
+                InvalidProofError::ExponentTooSmall(1)
+            ))
+        );
+        bytes[offset + 3] = 0;
+        assert!(proof::VDFProof::from_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn proofs_differing_in_any_field_are_unequal() {
+        let proof = formatting_fixture();
+        assert_eq!(proof, proof.clone());
+
+        let mut output = proof.clone();
+        output.output.result += Int::one();
+        assert_ne!(output, proof);
+        let mut iterations = proof.clone();
+        iterations.output.iterations += 1;
+        assert_ne!(iterations, proof);
+        assert_ne!(proof.clone().with_hash_alg(util::HashAlg::Blake3), proof);
+
+        let certificate = primality::PrimeCertificate {
+            steps: vec![primality::CertificateStep {
+                prime: Int::from(7),
+                factors: vec![Int::from(2), Int::from(3)],
+                witnesses: vec![Int::from(3), Int::from(3)],
+            }],
+        };
+        let mut certified = proof.clone();
+        certified.cap_certificate = Some(certificate.clone());
+        assert_ne!(certified, proof);
+        let mut other = certified.clone();
+        other.cap_certificate.as_mut().unwrap().steps[0].witnesses[1] =
+            Int::from(5);
+        assert_ne!(other, certified);
+        other.cap_certificate = Some(certificate);
+        assert_eq!(other, certified);
+    }
+
+    #[test]
+    fn hash_functions_other_than_the_default_are_encoded() {
+        let proof = quick_proof(&ModulusChoice::Rsa2048, b"hash_alg");
+        let explicit = proof.clone().with_hash_alg(proof::DEFAULT_HASH_ALG);
+        assert_eq!(explicit.to_bytes(), proof.to_bytes());
+        assert_eq!(
+            serde_json::to_value(&proof.deserialize()).unwrap()["hash_alg"],
+            serde_json::Value::Null
+        );
+
+        // The exponent and the id of the hash function follow the
+        // iterations in the third version, even for squaring
+        let blake3 = proof.with_hash_alg(util::HashAlg::Blake3);
+        let mut bytes = blake3.to_bytes();
+        assert_eq!(bytes[0], proof::HASH_ALG_ENCODING_VERSION);
+        assert_eq!(proof::VDFProof::from_bytes(&bytes), Ok(blake3.clone()));
+        assert_ne!(blake3.fingerprint(), proof.fingerprint());
+        let json = serde_json::to_value(&blake3.deserialize()).unwrap();
+        assert_eq!(json["hash_alg"], "blake3");
+        let offset = 1
+            + [&blake3.modulus, &blake3.generator, &blake3.output.result]
+                .iter()
+                .map(|int| 4 + util::int_to_be_bytes(int).len())
+                .sum::<usize>()
+            + 8;
+        assert_eq!(bytes[offset - 4..offset], 2u32.to_be_bytes());
+        assert_eq!(bytes[offset], util::HashAlg::Blake3.id());
+
+        bytes[offset] = proof::DEFAULT_HASH_ALG.id();
+        assert_eq!(
+            proof::VDFProof::from_bytes(&bytes),
+            Err(DecodeError::RedundantHashAlg(offset))
+        );
+        bytes[offset] = 0xff;
+        assert_eq!(
+            proof::VDFProof::from_bytes(&bytes),
+            Err(DecodeError::UnknownHashAlg(0xff))
+        );
+    }
+
+    #[test]
+    fn proofs_from_peers_with_another_hash_function_are_rejected() {
+        let modulus = ModulusChoice::Rsa2048;
+        let seed = b"negotiation";
+        let ours = util::HashAlg::Sha256;
+        let theirs = util::HashAlg::Blake3;
+        let vdf = evaluation::VDF::new(
+            modulus.clone(),
+            theirs.hash_with_domain(util::SEED_DOMAIN, seed, modulus.int()),
+            64,
+            proof::ProofType::Sequential,
+        )
+        .unwrap()
+        .with_hash_alg(theirs);
+        let their_proof = prove_with_cap(vdf);
+
+        assert!(their_proof.verify());
+        assert_eq!(their_proof.check_hash_alg(theirs), Ok(()));
+        assert_eq!(
+            their_proof.check_hash_alg(ours),
+            Err(VdfError::HashAlgMismatch {
+                expected: ours,
+                received: theirs,
+            })
+        );
+    }
+
+    #[test]
+    fn all_zero_blobs_are_not_proofs() {
+        assert_eq!(
+            proof::VDFProof::from_bytes(&[0; 27]),
+            Err(DecodeError::UnknownVersion(0))
+        );
+
+        // Every Int empty, zero iterations, a sequential proof and no
+        // certificate
+        let mut zeros = [0; 27];
+        zeros[0] = proof::ENCODING_VERSION;
+        assert_eq!(
+            proof::VDFProof::from_bytes(&zeros),
+            Err(DecodeError::InvalidProof(
+                InvalidProofError::ModulusTooSmall
+            ))
+        );
+    }
+
+    #[test]
+    fn proofs_need_a_usable_modulus_and_cap() {
+        let modulus = ModulusChoice::Rsa2048.int().clone();
+        let result = evaluation::VDFResult::new(Int::from(4), 1);
+        let new = |modulus: &Int, cap: &Int| {
+            proof::VDFProof::new(
+                modulus,
+                &Int::from(2),
+                &result,
+                cap,
+                &proof::ProofType::Sequential,
+            )
+        };
+        assert_eq!(
+            new(&Int::zero(), &Int::from(7)),
+            Err(InvalidProofError::ModulusTooSmall)
+        );
+        assert_eq!(
+            new(&modulus, &Int::one()),
+            Err(InvalidProofError::CapTooSmall)
+        );
+        assert!(new(&modulus, &Int::from(7)).is_ok());
+    }
+
+    /// A fixed proof to pin the formatting, not a valid one
+    fn formatting_fixture() -> proof::VDFProof {
+        let hex = |digits: &str| util::int_from_hex(digits).unwrap();
+        proof::VDFProof {
+            modulus: ModulusChoice::Rsa2048.int().clone(),
+            generator: hex(&"deadbeef".repeat(32)),
+            output: evaluation::VDFResult::new(
+                hex(&"c0ffee00".repeat(32)),
+                100,
+            ),
+            exponent: proof::DEFAULT_EXPONENT,
+            hash_alg: proof::DEFAULT_HASH_ALG,
+            cap: hex(&format!("7{}", "f".repeat(31))),
+            pi: hex(&"f00dcafe".repeat(32)),
+            proof_type: proof::ProofType::Sequential,
+            cap_certificate: None,
+            cap_received_at: 100,
+            timings: None,
+        }
+    }
+
+    #[test]
+    fn proofs_format_for_logs() {
+        let proof = formatting_fixture();
+        assert_eq!(
+            proof.to_string(),
+            "Sequential proof of 100 iterations in a 2048-bit group: \
+             result c0ffee00…c0ffee00, pi f00dcafe…f00dcafe, \
+             cap 7fffffff…ffffffff (127 bits), no cap certificate"
+        );
+        assert_eq!(
+            format!("{:?}", proof),
+            "VDFProof { modulus: c7970cee…c7e5 (2048 bits), \
+             generator: deadbeef…beef (1024 bits), \
+             output: VDFResult { result: c0ffee00…ee00 (1024 bits), \
+             iterations: 100 }, exponent: 2, hash_alg: Shake256, \
+             cap: 7fffffff…ffff (127 bits), \
+             pi: f00dcafe…cafe (1024 bits), proof_type: Sequential, \
+             cap_certificate_steps: None, cap_received_at: 100, \
+             timings: None }"
+        );
+        assert_eq!(proof.fingerprint(), "e41d2158126e10ed");
+    }
+
+    /// Variants of the fixture, with few enough iteration counts that ties
+    /// are common
+    fn ordered_proof() -> impl Strategy<Value = proof::VDFProof> {
+        (0u32..3, any::<u64>()).prop_map(|(iterations, pi)| {
+            let mut proof = formatting_fixture();
+            proof.output.iterations = iterations;
+            proof.pi = Int::from(pi);
+            proof
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn proof_order_is_total(
+            a in ordered_proof(),
+            b in ordered_proof(),
+            c in ordered_proof(),
+        ) {
+            prop_assert_eq!(a.cmp(&b), b.cmp(&a).reverse());
+            prop_assert_eq!(a.cmp(&b) == std::cmp::Ordering::Equal, a == b);
+            if a <= b && b <= c {
+                prop_assert!(a <= c);
+            }
+            prop_assert!(a.clone() <= a);
+        }
+    }
+
+    #[test]
+    fn proofs_go_in_sets() {
+        use std::collections::{BTreeSet, HashSet};
+
+        let proof = formatting_fixture();
+        let mut longer = proof.clone();
+        longer.output.iterations += 1;
+        let mut other = proof.clone();
+        other.pi = &other.pi + Int::one();
+
+        let proofs = vec![longer, proof.clone(), other, proof.clone()];
+        let sorted: BTreeSet<_> = proofs.iter().cloned().collect();
+        assert_eq!(sorted.len(), 3);
+        assert_eq!(sorted.iter().next_back().unwrap().output.iterations, 101);
+        let hashed: HashSet<_> = proofs.iter().cloned().collect();
+        assert_eq!(hashed.len(), 3);
+        assert!(hashed.contains(&proof));
+
+        let results: HashSet<_> =
+            proofs.iter().map(|proof| proof.output.clone()).collect();
+        assert_eq!(results.len(), 2);
+    }
+
+    #[test]
+    fn proofs_round_trip_through_strings() {
+        let proof = formatting_fixture();
+        let hex = proof.to_hex();
+        assert!(hex.starts_with("01000001"));
+        assert_eq!(proof::VDFProof::from_hex(&hex).unwrap(), proof);
+        assert_eq!(
+            proof::VDFProof::from_hex(&hex.to_uppercase()).unwrap(),
+            proof
+        );
+        assert_eq!(
+            proof::VDFProof::from_base64(&proof.to_base64()).unwrap(),
+            proof
+        );
+
+        let pasted = format!("{:#}", proof);
+        assert!(pasted.starts_with("polproof1AQAAAQ"));
+        assert_eq!(pasted.parse::<proof::VDFProof>().unwrap(), proof);
+        assert_eq!(hex.parse::<proof::VDFProof>().unwrap(), proof);
+    }
+
+    #[test]
+    fn malformed_proof_strings_report_the_offset() {
+        let proof = formatting_fixture();
+        let parse = |s: &str| s.parse::<proof::VDFProof>().unwrap_err();
+
+        let mut hex = proof.to_hex();
+        hex.replace_range(10..11, "g");
+        assert_eq!(parse(&hex), DecodeError::InvalidHex(10));
+        assert_eq!(parse("0x01"), DecodeError::InvalidHex(1));
+
+        let mut base64 = format!("{:#}", proof);
+        base64.replace_range(20..21, "*");
+        assert_eq!(parse(&base64), DecodeError::InvalidBase64(20));
+        assert_eq!(
+            parse(&base64).to_string(),
+            "Invalid base64 at character 20"
+        );
+
+        let hex = proof.to_hex();
+        assert_eq!(parse(&hex[..20]), DecodeError::Truncated(10));
+    }
+
+    #[test]
+    fn negative_json_numbers_are_rejected() {
+        let mut proof = formatting_fixture();
+        proof.cap_certificate = Some(primality::PrimeCertificate {
+            steps: vec![primality::CertificateStep {
+                prime: Int::from(23),
+                factors: vec![Int::from(2), Int::from(11)],
+                witnesses: vec![Int::from(5), Int::from(5)],
+            }],
+        });
+        let json = serde_json::to_value(&proof.deserialize()).unwrap();
+        let paths = [
+            "/modulus",
+            "/generator",
+            "/output/result",
+            "/cap",
+            "/pi",
+            "/cap_certificate/steps/0/prime",
+            "/cap_certificate/steps/0/witnesses/1",
+        ];
+        for path in paths.iter() {
+            let mut negated = json.clone();
+            let number = negated.pointer_mut(path).unwrap();
+            *number = format!("-{}", number.as_str().unwrap()).into();
+            let parsed: proof::DeserializableVDFProof =
+                serde_json::from_value(negated).unwrap();
+            assert!(
+                matches!(
+                    parsed.try_serialize(),
+                    Err(util::ParseUintError::Negative)
+                ),
+                "{}",
+                path
+            );
+        }
+
+        let parsed: proof::DeserializableVDFProof =
+            serde_json::from_value(json).unwrap();
+        assert_eq!(parsed.try_serialize().unwrap(), proof);
+    }
+
+    #[cfg(feature = "cid")]
+    #[test]
+    fn cids_hash_the_canonical_encoding() {
+        let cid = formatting_fixture().cid();
+        assert_eq!(
+            cid.to_string(),
+            "bafkrmiheduqvqetocdw5dmznsbsclm3romq76twaqnfxsxcwygjpsjyfka"
+        );
+        assert_eq!(cid::Cid::try_from(cid.to_string()).unwrap(), cid);
+
+        // The JSON form decodes to the same proof and so the same CID
+        let json = serde_json::to_string(&formatting_fixture().deserialize());
+        let decoded: proof::DeserializableVDFProof =
+            serde_json::from_str(&json.unwrap()).unwrap();
+        assert_eq!(decoded.serialize().cid(), cid);
+    }
+
+    /// Collects the messages of every event
+    struct Capture(Arc<Mutex<Vec<String>>>);
+
+    impl<S: tracing::Subscriber> Layer<S> for Capture {
+        fn on_event(&self, event: &tracing::Event<'_>, _: Context<'_, S>) {
+            struct Message(String);
+            impl Visit for Message {
+                fn record_debug(
+                    &mut self,
+                    field: &Field,
+                    value: &dyn fmt::Debug,
+                ) {
+                    if field.name() == "message" {
+                        self.0 = format!("{:?}", value);
+                    }
+                }
             }
+            let mut message = Message(String::new());
+            event.record(&mut message);
+            self.0.lock().unwrap().push(message.0);
+        }
+    }
+
+    #[test]
+    fn worker_emits_structured_events() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let subscriber = Registry::default().with(Capture(events.clone()));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let modulus = ModulusChoice::Rsa2048;
+            let generator = util::hash_with_domain(
+                util::SEED_DOMAIN,
+                b"events",
+                modulus.int(),
+            );
+            let (capper, receiver) = evaluation::VDF::new(
+                modulus,
+                generator,
+                u32::MAX,
+                proof::ProofType::Sequential,
+            )
+            .unwrap()
+            .run_vdf_worker();
+            capper
+                .send(primality::gen_safe_prime(64, &mut rand::thread_rng()))
+                .unwrap();
+            assert!(receiver.recv().unwrap().unwrap().verify());
+        });
+
+        let events = events.lock().unwrap();
+        for expected in &["cap received", "proof generated", "proof verified"] {
+            assert!(
+                events.iter().any(|event| event == expected),
+                "No {:?} event in {:?}",
+                expected,
+                events
+            );
         }
     }
 
+    #[test]
+    fn generated_cap_is_reproducible_with_a_seed() {
+        let modulus = ModulusChoice::Rsa2048;
+        let generator =
+            util::hash_with_domain(util::SEED_DOMAIN, b"seeded", modulus.int());
+        let run = |seed: u64| {
+            let vdf = evaluation::VDF::new(
+                modulus.clone(),
+                generator.clone(),
+                64,
+                proof::ProofType::Sequential,
+            )
+            .unwrap()
+            .with_cap_bits(64)
+            .with_rng(seed);
+            let (_, receiver) = vdf.run_vdf_worker();
+            receiver.recv().unwrap().unwrap()
+        };
+
+        let proof = run(99);
+        assert!(proof.verify());
+        assert_eq!(proof.cap.bit_length(), 64);
+        assert_eq!(proof, run(99));
+    }
+
+    #[test]
+    fn generated_cap_comes_with_a_certificate() {
+        let modulus = ModulusChoice::Rsa2048;
+        let generator = util::hash_with_domain(
+            util::SEED_DOMAIN,
+            b"certified",
+            modulus.int(),
+        );
+        let vdf = evaluation::VDF::new(
+            modulus,
+            generator,
+            64,
+            proof::ProofType::Sequential,
+        )
+        .unwrap()
+        .with_rng(7);
+        let (_, receiver) = vdf.run_vdf_worker();
+        let proof = receiver.recv().unwrap().unwrap();
+
+        let certificate = proof.cap_certificate.as_ref().unwrap();
+        assert!(certificate.check(&proof.cap));
+        assert!(proof.verify());
+        assert_eq!(proof.deserialize().serialize(), proof);
+
+        let mut forged = proof.clone();
+        forged.cap = &proof.cap + Int::from(2);
+        assert!(!forged.verify());
+    }
+
+    #[test]
+    fn generated_cap_is_taken_from_the_prime_pool() {
+        let modulus = ModulusChoice::Rsa2048;
+        let generator =
+            util::hash_with_domain(util::SEED_DOMAIN, b"pooled", modulus.int());
+        let pool = Arc::new(PrimePool::new(48, 1));
+        let vdf = evaluation::VDF::new(
+            modulus,
+            generator,
+            64,
+            proof::ProofType::Sequential,
+        )
+        .unwrap()
+        .with_prime_pool(pool);
+
+        let (_, receiver) = vdf.run_vdf_worker();
+        let proof = receiver.recv().unwrap().unwrap();
+        assert!(proof.verify());
+        assert_eq!(proof.cap.bit_length(), 48);
+    }
+
     #[test]
     fn vdf_iter_should_be_correct() {
-        let modulus = Int::from(17);
+        let modulus = ModulusChoice::Custom(Int::from(17));
         let generator = Int::from(11);
         let two = Int::from(2);
         let cap = Int::from(7);
-        let mut vdf = evaluation::VDF::new(
+        let mut vdf = evaluation::VDF::new_in_test_group(
             modulus,
             generator,
             u32::MAX,
             proof::ProofType::Sequential,
-        );
+        )
+        .unwrap();
 
         vdf.result = vdf.next().unwrap();
         assert_eq!(vdf.result.result, two);
@@ -104,41 +2395,48 @@ mod tests {
             &cap,
             &proof::ProofType::Sequential,
         )
+        .unwrap()
         .calculate()
         .unwrap();
 
-        assert!(proof.verify());
+        // 16 is -1 mod 17 and the cap is far too small
+        assert!(!proof.verify());
     }
 
     #[test]
     fn proof_generation_should_be_same_between_predetermined_and_received_input(
     ) {
-        let modulus = Int::from_str_radix(RSA_2048, 10).unwrap();
+        let modulus = ModulusChoice::Rsa2048;
         let hashablings2 = &"ghsalkghsakhgaligheliah<lifehf esipf";
-        let root_hashed =
-            util::hash_to_mod(&hashablings2.to_string(), &modulus);
+        let root_hashed = util::hash_with_domain(
+            util::SEED_DOMAIN,
+            hashablings2.as_bytes(),
+            modulus.int(),
+        );
 
-        let cap = Generator::new_safe_prime(16);
+        let cap = primality::gen_safe_prime(16, &mut rand::thread_rng());
         let vdf = evaluation::VDF::new(
             modulus.clone(),
             root_hashed.clone(),
             u32::MAX,
             proof::ProofType::Sequential,
-        );
+        )
+        .unwrap();
 
         let (capper, receiver) = vdf.run_vdf_worker();
 
         thread::sleep(time::Duration::from_millis(50));
 
-        let mut first_proof = proof::VDFProof::default();
+        let mut first_proof = None;
 
         let cap_error = capper.send(cap).is_err();
         assert!(!cap_error);
 
         if let Ok(Ok(proof)) = receiver.recv() {
             assert!(proof.pi != 1);
-            first_proof = proof;
+            first_proof = Some(proof);
         }
+        let first_proof = first_proof.expect("The first VDF gave no proof");
 
         let vdf2 = evaluation::VDF::new(
             modulus,
@@ -146,7 +2444,9 @@ mod tests {
             first_proof.output.iterations,
             proof::ProofType::Sequential,
         )
-        .with_cap(first_proof.cap.clone());
+        .unwrap()
+        .with_cap(first_proof.cap.clone())
+        .unwrap();
 
         let (_, receiver2) = vdf2.run_vdf_worker();
 
@@ -159,11 +2459,15 @@ mod tests {
 
     #[bench]
     fn bench_sequential(b: &mut Bencher) {
-        let modulus = Int::from_str_radix(RSA_2048, 10).unwrap();
+        let modulus = ModulusChoice::Rsa2048;
         let hashablings2 = &"ghsalkghsakhgaligheliah<lifehf esipf";
-        let root_hashed =
-            util::hash_to_mod(&hashablings2.to_string(), &modulus);
-        let cap_str = Generator::new_safe_prime(64).to_str_radix(10, false);
+        let root_hashed = util::hash_with_domain(
+            util::SEED_DOMAIN,
+            hashablings2.as_bytes(),
+            modulus.int(),
+        );
+        let cap_str = primality::gen_safe_prime(64, &mut rand::thread_rng())
+            .to_str_radix(10, false);
         b.iter(|| {
             let cap = Int::from_str_radix(&cap_str, 10).unwrap();
             let vdf = evaluation::VDF::new(
@@ -172,7 +2476,9 @@ mod tests {
                 256,
                 proof::ProofType::Sequential,
             )
-            .with_cap(cap);
+            .unwrap()
+            .with_cap(cap)
+            .unwrap();
 
             let (_capper, receiver) = vdf.run_vdf_worker();
 
@@ -184,11 +2490,15 @@ mod tests {
     }
     #[bench]
     fn bench_parallel(b: &mut Bencher) {
-        let modulus = Int::from_str_radix(RSA_2048, 10).unwrap();
+        let modulus = ModulusChoice::Rsa2048;
         let hashablings2 = &"ghsalkghsakhgaligheliah<lifehf esipf";
-        let root_hashed =
-            util::hash_to_mod(&hashablings2.to_string(), &modulus);
-        let cap_str = Generator::new_safe_prime(64).to_str_radix(10, false);
+        let root_hashed = util::hash_with_domain(
+            util::SEED_DOMAIN,
+            hashablings2.as_bytes(),
+            modulus.int(),
+        );
+        let cap_str = primality::gen_safe_prime(64, &mut rand::thread_rng())
+            .to_str_radix(10, false);
         b.iter(|| {
             let cap = Int::from_str_radix(&cap_str, 10).unwrap();
             let vdf = evaluation::VDF::new(
@@ -197,7 +2507,9 @@ mod tests {
                 256,
                 proof::ProofType::Parallel,
             )
-            .with_cap(cap);
+            .unwrap()
+            .with_cap(cap)
+            .unwrap();
 
             let (_capper, receiver) = vdf.run_vdf_worker();
 
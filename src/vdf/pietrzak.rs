@@ -0,0 +1,135 @@
+use crate::util;
+use crate::vdf::evaluation::VDFResult;
+use lazy_static::lazy_static;
+use ramp::Int;
+
+lazy_static! {
+    static ref TWO: Int = Int::from(2);
+    static ref CHALLENGE_MODULUS: Int = Int::from(1) << CHALLENGE_BITS;
+}
+
+/// Bit length of the Fiat-Shamir challenges
+const CHALLENGE_BITS: usize = 128;
+
+/// Pietrzak's proof that output = base^(2^T). Each round halves T with a
+/// midpoint mu = x^(2^(T/2)), folding (x, mu) and (mu, y) into a single claim
+/// with a random challenge, so the proof has about log2(T) midpoints. Unlike
+/// the Wesolowski proof, it doesn't need a prime cap from the peer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PietrzakProof {
+    pub midpoints: Vec<Int>,
+    pub output: VDFResult,
+    pub modulus: Int,
+    pub base: Int,
+}
+
+/// Fiat-Shamir challenge binding the claim x^(2^T) = y to its midpoint
+fn challenge(x: &Int, y: &Int, mu: &Int, iterations: u64) -> Int {
+    let mut transcript = Vec::new();
+    for element in [x, y, mu].iter() {
+        let bytes = util::int_to_be_bytes(element);
+        transcript.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+        transcript.extend_from_slice(&bytes);
+    }
+    transcript.extend_from_slice(&iterations.to_be_bytes());
+    util::hash_with_domain(
+        util::PIETRZAK_DOMAIN,
+        &transcript,
+        &CHALLENGE_MODULUS,
+    )
+}
+
+fn square_repeatedly(x: &Int, times: u64, modulus: &Int) -> Int {
+    (0..times).fold(x.clone(), |x, _| x.pow_mod(&TWO, modulus))
+}
+
+/// Proves that result = base^(2^iterations). Checkpoints, when given as the
+/// interval k and the results stored every k iterations, let the first
+/// midpoint be looked up instead of recomputed, halving the proving time.
+/// The rest of the midpoints take about T/2 squarings in total.
+pub fn prove(
+    base: &Int,
+    modulus: &Int,
+    iterations: u32,
+    result: &Int,
+    checkpoints: Option<(usize, &[Int])>,
+) -> Option<PietrzakProof> {
+    if iterations == 0 {
+        return None;
+    }
+    let mut x = base.clone();
+    let mut y = result.clone();
+    let mut t = u64::from(iterations);
+    let mut midpoints = Vec::new();
+
+    while t > 1 {
+        // x^(2^(T+1)) = y^2, which makes T even
+        if t % 2 == 1 {
+            y = y.pow_mod(&TWO, modulus);
+            t += 1;
+        }
+        let half = t / 2;
+        let stored = checkpoints.filter(|_| midpoints.is_empty()).and_then(
+            |(k, checkpoints)| {
+                if half % k as u64 == 0 {
+                    checkpoints.get((half / k as u64) as usize).cloned()
+                } else {
+                    None
+                }
+            },
+        );
+        let mu = stored.unwrap_or_else(|| square_repeatedly(&x, half, modulus));
+
+        let r = challenge(&x, &y, &mu, t);
+        x = x.pow_mod(&r, modulus) * &mu % modulus;
+        y = mu.pow_mod(&r, modulus) * y % modulus;
+        t = half;
+        midpoints.push(mu);
+    }
+
+    Some(PietrzakProof {
+        midpoints,
+        output: VDFResult::new(result.clone(), iterations),
+        modulus: modulus.clone(),
+        base: base.clone(),
+    })
+}
+
+impl PietrzakProof {
+    /// Verifies the proof with a couple of exponentiations by 128-bit
+    /// challenges per midpoint
+    pub fn verify(&self) -> bool {
+        let modulus = &self.modulus;
+        let in_group =
+            |element: &Int| element > &Int::zero() && element < modulus;
+        if self.output.iterations == 0
+            || !in_group(&self.base)
+            || !in_group(&self.output.result)
+            || !self.midpoints.iter().all(in_group)
+        {
+            return false;
+        }
+
+        let mut x = self.base.clone();
+        let mut y = self.output.result.clone();
+        let mut t = u64::from(self.output.iterations);
+        let mut midpoints = self.midpoints.iter();
+
+        while t > 1 {
+            if t % 2 == 1 {
+                y = y.pow_mod(&TWO, modulus);
+                t += 1;
+            }
+            let mu = match midpoints.next() {
+                Some(mu) => mu,
+                None => return false,
+            };
+            let r = challenge(&x, &y, mu, t);
+            x = x.pow_mod(&r, modulus) * mu % modulus;
+            y = mu.pow_mod(&r, modulus) * y % modulus;
+            t /= 2;
+        }
+
+        midpoints.next().is_none() && y == x.pow_mod(&TWO, modulus)
+    }
+}
@@ -1,58 +1,404 @@
+use crate::primality::{
+    self, DeserializablePrimeCertificate, PrimeCertificate,
+};
+use crate::util;
+use crate::vdf;
+use crate::vdf::bigint::PolInt;
 use crate::vdf::evaluation;
 use crossbeam::channel::unbounded;
 use crossbeam::channel::{Receiver, Sender};
-use lazy_static::lazy_static;
 use ramp::Int;
 use rayon::prelude::*;
 use rkyv::{Archive, Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use std::cmp::Ordering;
 use std::convert::TryFrom;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
 use std::thread;
 use std::time::Instant;
+use subtle::{Choice, ConstantTimeEq};
 
-lazy_static! {
-    static ref TWO: Int = Int::from_str_radix("2", 10).unwrap();
+/// Version byte that starts the canonical encoding of a proof
+pub const ENCODING_VERSION: u8 = 1;
+
+/// Version byte of proofs whose exponent isn't 2. The exponent follows the
+/// iterations, and proofs by squaring keep the original encoding.
+pub const EXPONENT_ENCODING_VERSION: u8 = 2;
+
+/// Version byte of proofs whose hash function isn't the default. The
+/// exponent and the id of the hash function follow the iterations.
+pub const HASH_ALG_ENCODING_VERSION: u8 = 3;
+
+/// The exponent of the map x ← x^k the VDF iterates, unless configured
+/// otherwise
+pub const DEFAULT_EXPONENT: u32 = 2;
+
+/// The hash function of proofs that don't name one, those of protocol
+/// version 1.1.0
+pub const DEFAULT_HASH_ALG: util::HashAlg =
+    util::ProtocolVersion::V1_1_0.hash_alg();
+
+/// Multicodec code of raw binary content
+#[cfg(feature = "cid")]
+const RAW_CODEC: u64 = 0x55;
+
+/// Multihash code of SHA3-256
+#[cfg(feature = "cid")]
+const SHA3_256_CODE: u64 = 0x16;
+
+/// Smallest cap accepted in verification, in bits. A small cap leaves the
+/// prover few enough candidates to search for one that fakes the proof.
+pub const MIN_CAP_BITS: u32 = 16;
+
+/// Marks a proof pasted as a string as base64 of the canonical encoding, as
+/// opposed to hex
+pub const PROOF_STRING_PREFIX: &str = "polproof1";
+
+fn put_u32(bytes: &mut Vec<u8>, value: u32) {
+    bytes.extend_from_slice(&value.to_be_bytes());
+}
+
+/// Ints are encoded as their big-endian length in bytes followed by the
+/// minimal big-endian bytes
+fn put_int(bytes: &mut Vec<u8>, value: &Int) {
+    let encoded = util::int_to_be_bytes(value);
+    put_u32(bytes, encoded.len() as u32);
+    bytes.extend_from_slice(&encoded);
+}
+
+/// Reads the canonical encoding front to back, keeping track of the offset
+/// for error messages
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], vdf::DecodeError> {
+        if self.bytes.len() - self.offset < len {
+            return Err(vdf::DecodeError::Truncated(self.bytes.len()));
+        }
+        let taken = &self.bytes[self.offset..self.offset + len];
+        self.offset += len;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, vdf::DecodeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, vdf::DecodeError> {
+        let mut buffer = [0u8; 4];
+        buffer.copy_from_slice(self.take(4)?);
+        Ok(u32::from_be_bytes(buffer))
+    }
+
+    fn int(&mut self) -> Result<Int, vdf::DecodeError> {
+        let len = self.u32()? as usize;
+        Ok(util::int_from_be_bytes(self.take(len)?))
+    }
+
+    /// Reads a count of items that take at least min_len bytes each. The
+    /// count is checked against the bytes left before anything is allocated
+    /// for it, so a forged count can't exhaust the memory.
+    fn count(&mut self, min_len: usize) -> Result<usize, vdf::DecodeError> {
+        let count = self.u32()? as usize;
+        match count.checked_mul(min_len) {
+            Some(len) if len <= self.bytes.len() - self.offset => Ok(count),
+            _ => Err(vdf::DecodeError::Truncated(self.bytes.len())),
+        }
+    }
+
+    fn ints(&mut self) -> Result<Vec<Int>, vdf::DecodeError> {
+        let count = self.count(4)?;
+        (0..count).map(|_| self.int()).collect()
+    }
 }
 
 #[derive(
-    Archive, Debug, Deserialize, Serialize, Clone, Default, PartialEq, Eq,
+    Archive,
+    Debug,
+    Deserialize,
+    Serialize,
+    Clone,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
 )]
 pub struct DeserializableVDFProof {
     pub modulus: String,
     pub generator: String,
     pub output: evaluation::DeserializableVDFResult,
+    /// Left out for squaring, so that those proofs read and write the same
+    /// JSON as before exponents were configurable
+    #[serde(
+        default = "default_exponent",
+        skip_serializing_if = "is_default_exponent"
+    )]
+    pub exponent: u32,
+    /// Left out for the default hash function, like the exponent
+    #[serde(
+        default = "default_hash_alg",
+        skip_serializing_if = "is_default_hash_alg"
+    )]
+    pub hash_alg: util::HashAlg,
     pub cap: String,
     pub pi: String,
     pub proof_type: ProofType,
+    pub cap_certificate: Option<DeserializablePrimeCertificate>,
+    /// Left out when the cap arrived at the output's iterations
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cap_received_at: Option<u64>,
+    /// Left out when the proof didn't come from a local run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timings: Option<evaluation::DeserializableVdfTimings>,
+}
+
+fn default_exponent() -> u32 {
+    DEFAULT_EXPONENT
+}
+
+fn is_default_exponent(exponent: &u32) -> bool {
+    *exponent == DEFAULT_EXPONENT
+}
+
+fn default_hash_alg() -> util::HashAlg {
+    DEFAULT_HASH_ALG
+}
+
+fn is_default_hash_alg(hash_alg: &util::HashAlg) -> bool {
+    *hash_alg == DEFAULT_HASH_ALG
 }
 
 impl DeserializableVDFProof {
     pub fn serialize(&self) -> VDFProof {
-        VDFProof {
-            modulus: Int::from_str_radix(&self.modulus, 10).unwrap(),
-            generator: Int::from_str_radix(&self.generator, 10).unwrap(),
-            output: self.output.serialize(),
-            cap: Int::from_str_radix(&self.cap, 10).unwrap(),
-            pi: Int::from_str_radix(&self.pi, 10).unwrap(),
+        self.try_serialize().unwrap()
+    }
+
+    /// Like serialize, but fails on numbers that don't parse or are negative
+    /// instead of panicking, for proofs from untrusted sources
+    pub fn try_serialize(&self) -> Result<VDFProof, util::ParseUintError> {
+        let output = self.output.try_serialize()?;
+        Ok(VDFProof {
+            modulus: util::uint_from_str(&self.modulus)?,
+            generator: util::uint_from_str(&self.generator)?,
+            cap_received_at: self
+                .cap_received_at
+                .unwrap_or_else(|| u64::from(output.iterations)),
+            output,
+            exponent: self.exponent,
+            hash_alg: self.hash_alg,
+            cap: util::uint_from_str(&self.cap)?,
+            pi: util::uint_from_str(&self.pi)?,
             proof_type: self.proof_type.clone(),
-        }
+            cap_certificate: self
+                .cap_certificate
+                .as_ref()
+                .map(|certificate| certificate.try_serialize())
+                .transpose()?,
+            timings: self.timings.as_ref().map(|timings| timings.serialize()),
+        })
     }
     pub fn verify(&self) -> bool {
-        self.serialize().verify()
+        self.try_serialize().map_or(false, |proof| proof.verify())
     }
 }
 
 /// Proof of an already calculated VDF that gets passed around between peers
-#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[derive(Clone)]
 pub struct VDFProof {
     pub modulus: Int,
     pub generator: Int,
     pub output: evaluation::VDFResult,
+    /// The exponent k of the map x ← x^k the output was iterated with
+    pub exponent: u32,
+    /// The hash function the generator was derived with
+    pub hash_alg: util::HashAlg,
     pub cap: Int,
     pub pi: Int,
     pub proof_type: ProofType,
+    /// Proves the cap prime when the prover generated the cap itself
+    pub cap_certificate: Option<PrimeCertificate>,
+    /// The iteration the cap arrived at. Metadata outside the canonical
+    /// encoding and equality, equal to the output's iterations unless the
+    /// cap came before the VDF's minimum iterations. Only the serde and rkyv
+    /// forms of DeserializableVDFProof carry it: proofs decoded from to_bytes
+    /// or protobuf claim the cap arrived at the output's iterations.
+    pub cap_received_at: u64,
+    /// When the local run started, received the cap and finished, also
+    /// metadata. Only the worker and VDF::run_with record them.
+    pub timings: Option<evaluation::VdfTimings>,
+}
+
+/// Compares every field in constant time, the Ints padded to the length of
+/// the modulus, so comparing against a proof doesn't reveal how much of it
+/// matched
+impl PartialEq for VDFProof {
+    fn eq(&self, other: &Self) -> bool {
+        let len = util::byte_length(&self.modulus)
+            .max(util::byte_length(&other.modulus));
+        let ct_eq = |a: &Int, b: &Int| util::ct_eq(a, b, len);
+        let certificates_eq = match (
+            self.cap_certificate.as_ref(),
+            other.cap_certificate.as_ref(),
+        ) {
+            (Some(a), Some(b)) => a.ct_eq(b, len),
+            (a, b) => Choice::from(u8::from(a.is_none() & b.is_none())),
+        };
+        let equal = ct_eq(&self.modulus, &other.modulus)
+            & ct_eq(&self.generator, &other.generator)
+            & ct_eq(&self.output.result, &other.output.result)
+            & self.output.iterations.ct_eq(&other.output.iterations)
+            & self.exponent.ct_eq(&other.exponent)
+            & self.hash_alg.id().ct_eq(&other.hash_alg.id())
+            & ct_eq(&self.cap, &other.cap)
+            & ct_eq(&self.pi, &other.pi)
+            & Choice::from(u8::from(self.proof_type == other.proof_type))
+            & certificates_eq;
+        equal.into()
+    }
+}
+
+impl Eq for VDFProof {}
+
+/// Orders by iterations and then by the rest of the fields equality
+/// compares. This only gives sets and sorted collections a deterministic
+/// order, a proof that sorts later isn't better in any sense.
+impl Ord for VDFProof {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.output
+            .iterations
+            .cmp(&other.output.iterations)
+            .then_with(|| self.modulus.cmp(&other.modulus))
+            .then_with(|| self.generator.cmp(&other.generator))
+            .then_with(|| self.output.result.cmp(&other.output.result))
+            .then_with(|| self.exponent.cmp(&other.exponent))
+            .then_with(|| self.hash_alg.cmp(&other.hash_alg))
+            .then_with(|| self.cap.cmp(&other.cap))
+            .then_with(|| self.pi.cmp(&other.pi))
+            .then_with(|| self.proof_type.cmp(&other.proof_type))
+            .then_with(|| self.cap_certificate.cmp(&other.cap_certificate))
+    }
+}
+
+impl PartialOrd for VDFProof {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
 }
 
-#[derive(Archive, Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+/// Hashes the fields equality compares
+impl Hash for VDFProof {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.modulus.hash(state);
+        self.generator.hash(state);
+        self.output.result.hash(state);
+        self.output.iterations.hash(state);
+        self.exponent.hash(state);
+        self.hash_alg.hash(state);
+        self.cap.hash(state);
+        self.pi.hash(state);
+        self.proof_type.hash(state);
+        self.cap_certificate.hash(state);
+    }
+}
+
+impl fmt::Debug for VDFProof {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VDFProof")
+            .field("modulus", &util::TruncatedInt(&self.modulus))
+            .field("generator", &util::TruncatedInt(&self.generator))
+            .field("output", &self.output)
+            .field("exponent", &self.exponent)
+            .field("hash_alg", &self.hash_alg)
+            .field("cap", &util::TruncatedInt(&self.cap))
+            .field("pi", &util::TruncatedInt(&self.pi))
+            .field("proof_type", &self.proof_type)
+            .field(
+                "cap_certificate_steps",
+                &self.cap_certificate.as_ref().map(|c| c.steps.len()),
+            )
+            .field("cap_received_at", &self.cap_received_at)
+            .field("timings", &self.timings)
+            .finish()
+    }
+}
+
+/// Parses `polproof1` followed by base64, as written by `{:#}`, or bare hex.
+/// InvalidHex and InvalidBase64 count characters from the start of the whole
+/// string. The other errors are about the decoded bytes: Truncated carries
+/// how many there are, and the remaining offsets count bytes into them.
+impl FromStr for VDFProof {
+    type Err = vdf::DecodeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix(PROOF_STRING_PREFIX) {
+            Some(base64) => {
+                Self::from_base64(base64).map_err(|error| match error {
+                    vdf::DecodeError::InvalidBase64(offset) => {
+                        vdf::DecodeError::InvalidBase64(
+                            PROOF_STRING_PREFIX.len() + offset,
+                        )
+                    }
+                    other => other,
+                })
+            }
+            None => Self::from_hex(s),
+        }
+    }
+}
+
+/// One line summary of the proof, e.g. `Sequential proof of 100 iterations
+/// in a 2048-bit group: result c0ffee00…c0ffee00, pi f00dcafe…f00dcafe, cap
+/// 7fffffff…ffffffff (127 bits), no cap certificate`. The alternate form,
+/// `{:#}`, is the whole proof as `polproof1` followed by base64, which
+/// parses back with FromStr.
+impl fmt::Display for VDFProof {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            return write!(f, "{}{}", PROOF_STRING_PREFIX, self.to_base64());
+        }
+        write!(
+            f,
+            "{:?} proof of {} iterations in a {}-bit group: result {}, pi {}, \
+             cap {} ({} bits), ",
+            self.proof_type,
+            self.output.iterations,
+            self.modulus.bit_length(),
+            util::abbreviated_hex(&self.output.result, 8, 8),
+            util::abbreviated_hex(&self.pi, 8, 8),
+            util::abbreviated_hex(&self.cap, 8, 8),
+            self.cap.bit_length(),
+        )?;
+        match self.cap_certificate.as_ref() {
+            None => write!(f, "no cap certificate"),
+            Some(certificate) => write!(
+                f,
+                "cap certificate of {} steps",
+                certificate.steps.len()
+            ),
+        }
+    }
+}
+
+#[derive(
+    Archive,
+    Debug,
+    Deserialize,
+    Serialize,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+)]
 pub enum ProofType {
     Sequential,
     Parallel,
@@ -64,36 +410,362 @@ impl Default for ProofType {
     }
 }
 
+/// A proof whose evaluation has finished but whose cap hasn't arrived yet.
+/// The result can be sent to the peer right away, and the proof finished once
+/// the cap is known.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingProof {
+    modulus: Int,
+    generator: Int,
+    output: evaluation::VDFResult,
+    exponent: u32,
+    hash_alg: util::HashAlg,
+    proof_type: ProofType,
+}
+
+impl PendingProof {
+    pub fn result(&self) -> &evaluation::VDFResult {
+        &self.output
+    }
+
+    /// Proves iterations of x ← x^exponent instead of squaring
+    pub fn with_exponent(mut self, exponent: u32) -> Self {
+        self.exponent = exponent;
+        self
+    }
+
+    /// Records the hash function the generator was derived with
+    pub fn with_hash_alg(mut self, hash_alg: util::HashAlg) -> Self {
+        self.hash_alg = hash_alg;
+        self
+    }
+
+    /// Calculates the proof against the cap
+    pub fn finish(self, cap: &Int) -> Option<VDFProof> {
+        self.proof(cap)?.calculate()
+    }
+
+    /// Calculates the proof against the cap from checkpoints stored every k
+    /// iterations
+    pub fn finish_with_checkpoints(
+        self,
+        cap: &Int,
+        k: usize,
+        checkpoints: &[Int],
+    ) -> Option<VDFProof> {
+        self.proof(cap)?.calculate_with_checkpoints(k, checkpoints)
+    }
+
+    fn proof(&self, cap: &Int) -> Option<VDFProof> {
+        VDFProof::new(
+            &self.modulus,
+            &self.generator,
+            &self.output,
+            cap,
+            &self.proof_type,
+        )
+        .ok()
+        .map(|proof| {
+            proof
+                .with_exponent(self.exponent)
+                .with_hash_alg(self.hash_alg)
+        })
+    }
+}
+
 impl VDFProof {
-    /// Returns a VDFProof based on a VDFResult
+    /// Starts a proof for a finished evaluation, to be finished with the cap
+    pub fn start(
+        modulus: &Int,
+        generator: &Int,
+        result: &evaluation::VDFResult,
+        proof_type: &ProofType,
+    ) -> PendingProof {
+        PendingProof {
+            modulus: modulus.clone(),
+            generator: generator.clone(),
+            output: result.clone(),
+            exponent: DEFAULT_EXPONENT,
+            hash_alg: DEFAULT_HASH_ALG,
+            proof_type: proof_type.clone(),
+        }
+    }
+
+    /// Returns a VDFProof based on a VDFResult, to be calculated against the
+    /// cap. Fails for a modulus or a cap no VDF could be run with.
+    #[tracing::instrument(
+        level = "trace",
+        skip(modulus, generator, result, cap, proof_type),
+        fields(iterations = result.iterations)
+    )]
     pub fn new(
         modulus: &Int,
         generator: &Int,
         result: &evaluation::VDFResult,
         cap: &Int,
         proof_type: &ProofType,
-    ) -> Self {
-        Self {
+    ) -> Result<Self, vdf::InvalidProofError> {
+        if *modulus < 3 {
+            return Err(vdf::InvalidProofError::ModulusTooSmall);
+        }
+        if *cap < 2 {
+            return Err(vdf::InvalidProofError::CapTooSmall);
+        }
+        Ok(Self {
             modulus: modulus.clone(),
             generator: generator.clone(),
             output: result.clone(),
+            exponent: DEFAULT_EXPONENT,
+            hash_alg: DEFAULT_HASH_ALG,
             cap: cap.clone(),
             pi: Int::zero(),
             proof_type: proof_type.clone(),
+            cap_certificate: None,
+            cap_received_at: u64::from(result.iterations),
+            timings: None,
+        })
+    }
+
+    /// Sets the exponent of the map the output was iterated with, for
+    /// proofs of x ← x^exponent instead of squaring
+    pub fn with_exponent(mut self, exponent: u32) -> Self {
+        self.exponent = exponent;
+        self
+    }
+
+    /// Records the hash function the generator was derived with
+    pub fn with_hash_alg(mut self, hash_alg: util::HashAlg) -> Self {
+        self.hash_alg = hash_alg;
+        self
+    }
+
+    /// Fails unless the generator was derived with the expected hash
+    /// function, as a peer configured with another one would derive a
+    /// generator unrelated to ours
+    pub fn check_hash_alg(
+        &self,
+        expected: util::HashAlg,
+    ) -> Result<(), vdf::VdfError> {
+        if self.hash_alg == expected {
+            Ok(())
+        } else {
+            Err(vdf::VdfError::HashAlgMismatch {
+                expected,
+                received: self.hash_alg,
+            })
         }
     }
 
+    /// Whether the cap arrived before the iterations the proof claims, so
+    /// earlier than any plausible round trip and the latency is suspect.
+    /// Always false for proofs decoded from bytes, see cap_received_at.
+    pub fn cap_arrived_early(&self) -> bool {
+        self.cap_received_at < u64::from(self.output.iterations)
+    }
+
     pub fn deserialize(&self) -> DeserializableVDFProof {
         DeserializableVDFProof {
             modulus: self.modulus.to_str_radix(10, false),
             generator: self.generator.to_str_radix(10, false),
             output: self.output.deserialize(),
+            exponent: self.exponent,
+            hash_alg: self.hash_alg,
             cap: self.cap.to_str_radix(10, false),
             pi: self.pi.to_str_radix(10, false),
             proof_type: self.proof_type.clone(),
+            cap_certificate: self
+                .cap_certificate
+                .as_ref()
+                .map(|certificate| certificate.deserialize()),
+            cap_received_at: if self.cap_arrived_early() {
+                Some(self.cap_received_at)
+            } else {
+                None
+            },
+            timings: self.timings.as_ref().map(|timings| timings.deserialize()),
         }
     }
 
+    /// Encodes the proof in the canonical byte encoding: a version byte, then
+    /// the fields in declaration order. Ints are length-prefixed big-endian
+    /// bytes, counts and the iterations are big-endian u32s, and the proof
+    /// type and the presence of a certificate are single bytes. An exponent
+    /// other than 2 bumps the version and follows the iterations, and a hash
+    /// function other than the default bumps it again and follows the
+    /// exponent as its id byte.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let version = if self.hash_alg != DEFAULT_HASH_ALG {
+            HASH_ALG_ENCODING_VERSION
+        } else if self.exponent != DEFAULT_EXPONENT {
+            EXPONENT_ENCODING_VERSION
+        } else {
+            ENCODING_VERSION
+        };
+        let mut bytes = vec![version];
+        put_int(&mut bytes, &self.modulus);
+        put_int(&mut bytes, &self.generator);
+        put_int(&mut bytes, &self.output.result);
+        put_u32(&mut bytes, self.output.iterations);
+        if version != ENCODING_VERSION {
+            put_u32(&mut bytes, self.exponent);
+        }
+        if version == HASH_ALG_ENCODING_VERSION {
+            bytes.push(self.hash_alg.id());
+        }
+        put_int(&mut bytes, &self.cap);
+        put_int(&mut bytes, &self.pi);
+        bytes.push(match self.proof_type {
+            ProofType::Sequential => 0,
+            ProofType::Parallel => 1,
+        });
+        match self.cap_certificate.as_ref() {
+            None => bytes.push(0),
+            Some(certificate) => {
+                bytes.push(1);
+                put_u32(&mut bytes, certificate.steps.len() as u32);
+                for step in certificate.steps.iter() {
+                    put_int(&mut bytes, &step.prime);
+                    put_u32(&mut bytes, step.factors.len() as u32);
+                    for factor in step.factors.iter() {
+                        put_int(&mut bytes, factor);
+                    }
+                    put_u32(&mut bytes, step.witnesses.len() as u32);
+                    for witness in step.witnesses.iter() {
+                        put_int(&mut bytes, witness);
+                    }
+                }
+            }
+        }
+        bytes
+    }
+
+    /// Decodes a proof from the canonical byte encoding. Decoding doesn't
+    /// verify the proof.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, vdf::DecodeError> {
+        let mut reader = Reader { bytes, offset: 0 };
+        let version = reader.u8()?;
+        if version != ENCODING_VERSION
+            && version != EXPONENT_ENCODING_VERSION
+            && version != HASH_ALG_ENCODING_VERSION
+        {
+            return Err(vdf::DecodeError::UnknownVersion(version));
+        }
+        let modulus = reader.int()?;
+        let generator = reader.int()?;
+        let output = evaluation::VDFResult::new(reader.int()?, reader.u32()?);
+        let exponent = if version != ENCODING_VERSION {
+            // Squaring has exactly one encoding per hash function, in the
+            // first version for the default one
+            let offset = reader.offset;
+            match reader.u32()? {
+                DEFAULT_EXPONENT if version == EXPONENT_ENCODING_VERSION => {
+                    return Err(vdf::DecodeError::RedundantExponent(offset))
+                }
+                exponent if exponent < 2 => {
+                    return Err(vdf::DecodeError::InvalidProof(
+                        vdf::InvalidProofError::ExponentTooSmall(exponent),
+                    ))
+                }
+                exponent => exponent,
+            }
+        } else {
+            DEFAULT_EXPONENT
+        };
+        let hash_alg = if version == HASH_ALG_ENCODING_VERSION {
+            let offset = reader.offset;
+            match reader.u8()? {
+                id if id == DEFAULT_HASH_ALG.id() => {
+                    return Err(vdf::DecodeError::RedundantHashAlg(offset))
+                }
+                id => util::HashAlg::from_id(id)
+                    .ok_or(vdf::DecodeError::UnknownHashAlg(id))?,
+            }
+        } else {
+            DEFAULT_HASH_ALG
+        };
+        let cap = reader.int()?;
+        let pi = reader.int()?;
+        let proof_type = match reader.u8()? {
+            0 => ProofType::Sequential,
+            1 => ProofType::Parallel,
+            other => return Err(vdf::DecodeError::UnknownProofType(other)),
+        };
+        let cap_certificate = match reader.u8()? {
+            0 => None,
+            1 => {
+                // A prime and the counts of its factors and witnesses
+                let count = reader.count(12)?;
+                let steps = (0..count)
+                    .map(|_| {
+                        Ok(primality::CertificateStep {
+                            prime: reader.int()?,
+                            factors: reader.ints()?,
+                            witnesses: reader.ints()?,
+                        })
+                    })
+                    .collect::<Result<Vec<_>, vdf::DecodeError>>()?;
+                Some(PrimeCertificate { steps })
+            }
+            other => return Err(vdf::DecodeError::UnknownFlag(other)),
+        };
+        if reader.offset != bytes.len() {
+            return Err(vdf::DecodeError::TrailingBytes(reader.offset));
+        }
+        let mut proof =
+            Self::new(&modulus, &generator, &output, &cap, &proof_type)
+                .map_err(vdf::DecodeError::InvalidProof)?
+                .with_exponent(exponent)
+                .with_hash_alg(hash_alg);
+        proof.pi = pi;
+        proof.cap_certificate = cap_certificate;
+        Ok(proof)
+    }
+
+    /// The canonical encoding in lowercase hex
+    pub fn to_hex(&self) -> String {
+        util::bytes_to_hex(&self.to_bytes())
+    }
+
+    /// Decodes a proof from the canonical encoding in hex of either case
+    pub fn from_hex(s: &str) -> Result<Self, vdf::DecodeError> {
+        Self::from_bytes(
+            &util::hex_to_bytes(s).map_err(vdf::DecodeError::InvalidHex)?,
+        )
+    }
+
+    /// The canonical encoding in padded, standard base64
+    pub fn to_base64(&self) -> String {
+        util::bytes_to_base64(&self.to_bytes())
+    }
+
+    /// Decodes a proof from the canonical encoding in padded, standard base64
+    pub fn from_base64(s: &str) -> Result<Self, vdf::DecodeError> {
+        Self::from_bytes(
+            &util::base64_to_bytes(s)
+                .map_err(vdf::DecodeError::InvalidBase64)?,
+        )
+    }
+
+    /// Short digest of the canonical encoding for telling proofs apart in
+    /// logs: the first 8 bytes of its SHA3-256 hash in hex
+    pub fn fingerprint(&self) -> String {
+        Sha3_256::digest(&self.to_bytes())[..8]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    /// Content identifier for anchoring the proof elsewhere: a CIDv1 of the
+    /// canonical encoding as raw content, with a SHA3-256 multihash
+    #[cfg(feature = "cid")]
+    pub fn cid(&self) -> cid::Cid {
+        let digest = Sha3_256::digest(&self.to_bytes());
+        let hash =
+            cid::multihash::MultihashGeneric::wrap(SHA3_256_CODE, &digest)
+                .expect("SHA3-256 digests fit in a multihash");
+        cid::Cid::new_v1(RAW_CODEC, hash)
+    }
+
     /// Parallel proof calculator. This should be nudged in parallel to the
     /// evaluator, in the end generating a proof on a false nudge value. Cap
     /// must be defined before VDF evaluation.
@@ -106,6 +778,7 @@ impl VDFProof {
         thread::spawn(move || {
             let mut r: Int = Int::from(1);
             let mut b: Int;
+            let k = Int::from(self_clone.exponent);
             let modulus: &Int = &self_clone.modulus;
             let generator: &Int = &self_clone.generator;
             let cap: &Int = &self_clone.cap;
@@ -115,11 +788,11 @@ impl VDFProof {
                 match nudge {
                     true => {
                         // calculate next proof
-                        b = &*TWO * &r / cap;
-                        pi = pi.pow_mod(&*TWO, modulus)
+                        b = &k * &r / cap;
+                        pi = pi.pow_mod(&k, modulus)
                             * generator.pow_mod(&b, modulus)
                             % modulus;
-                        r = r * &*TWO % cap;
+                        r = r * &k % cap;
                         continue;
                     }
                     false => {
@@ -150,16 +823,17 @@ impl VDFProof {
                     0 => None,
                     _ => {
                         let cap = &self.cap;
+                        let k = Int::from(self.exponent);
                         let mut r: Vec<Int> = Vec::with_capacity(iter);
                         r.push(Int::from(1));
 
                         // Calculate r values
                         (0..iter)
                             .skip(1)
-                            .for_each(|i| r.push(&r[i - 1] * &*TWO % cap));
+                            .for_each(|i| r.push(&r[i - 1] * &k % cap));
 
                         // Construct a parallel iterator for values of b
-                        let b = r.into_par_iter().map(|r| &*TWO * r / cap);
+                        let b = r.into_par_iter().map(|r| &k * r / cap);
                         let pi_y: Vec<Int> = b
                             .into_par_iter()
                             .map(|b| self.generator.pow_mod(&b, &self.modulus))
@@ -172,7 +846,7 @@ impl VDFProof {
 
                         let pi_last = |mut pi: Int| {
                             for y in pi_y {
-                                pi = pi.pow_mod(&*TWO, &self.modulus) * y
+                                pi = pi.pow_mod(&k, &self.modulus) * y
                                     % &self.modulus;
                             }
                             pi
@@ -196,18 +870,128 @@ impl VDFProof {
         }
     }
 
+    /// Calculates the same proof as calculate from the intermediate results
+    /// stored every k iterations, checkpoints[i] being
+    /// generator^(2^(i * k)). Written in base 2^k, floor(2^T / cap) makes the
+    /// proof a product of checkpoints raised to k-bit digits. Grouping the
+    /// checkpoints by digit brings the cost down to about T/k + 2^(k+1)
+    /// multiplications, so k should be around log2(T). The digits only line
+    /// up with the checkpoints for squaring, so other exponents are proved
+    /// with calculate.
+    pub fn calculate_with_checkpoints(
+        &mut self,
+        k: usize,
+        checkpoints: &[Int],
+    ) -> Option<VDFProof> {
+        if self.exponent != DEFAULT_EXPONENT {
+            return self.calculate();
+        }
+        let timer = Instant::now();
+        let iterations = self.output.iterations as usize;
+        if iterations == 0
+            || k == 0
+            || checkpoints.len() <= (iterations - 1) / k
+        {
+            return None;
+        }
+
+        let quotient = (Int::from(1) << iterations) / &self.cap;
+
+        // buckets[d] is the product of the checkpoints with digit d
+        let mut buckets: Vec<Int> = vec![Int::from(1); 1 << k];
+        let mut position = 0;
+        while position < iterations {
+            let digit = (0..k)
+                .filter(|j| quotient.bit((position + j) as u32))
+                .fold(0, |digit, j| digit | 1 << j);
+            if digit != 0 {
+                let checkpoint = &checkpoints[position / k];
+                buckets[digit] = &buckets[digit] * checkpoint % &self.modulus;
+            }
+            position += k;
+        }
+
+        // The product of buckets[d]^d, as a product of suffix products
+        let mut suffix = Int::from(1);
+        let mut pi = Int::from(1);
+        for bucket in buckets.iter().skip(1).rev() {
+            suffix = suffix * bucket % &self.modulus;
+            pi = pi * &suffix % &self.modulus;
+        }
+
+        debug!(
+            "Proof generation from checkpoints took {:?} milliseconds",
+            timer.elapsed().as_millis()
+        );
+
+        if pi != self.pi {
+            self.pi = pi;
+            Some(self.clone())
+        } else {
+            None
+        }
+    }
+
     /// A public function that a receiver can use to verify the correctness of
     /// the VDFProof
     pub fn verify(&self) -> bool {
-        // Check first that the proof belongs in the RSA group
-        if self.pi > self.modulus {
-            return false;
+        let valid = self.check().is_ok();
+        debug!(valid, iterations = self.output.iterations, "proof verified");
+        valid
+    }
+
+    /// Like verify, but says why the proof doesn't verify. Checks the
+    /// certificate of a self-generated cap, and the rest with
+    /// verify_raw_with_exponent.
+    pub fn check(&self) -> Result<(), vdf::VerifyError> {
+        if let Some(certificate) = self.cap_certificate.as_ref() {
+            if !certificate.check(&self.cap) {
+                return Err(vdf::VerifyError::CapNotPrime);
+            }
+        }
+        verify_raw_with_exponent(
+            &self.modulus,
+            &self.generator,
+            &self.output.result,
+            u64::from(self.output.iterations),
+            self.exponent,
+            &self.cap,
+            &self.pi,
+        )
+    }
+
+    /// Checks that the proofs form a chain, each one continuing from the
+    /// output of the previous one in the same group, and that every link
+    /// verifies. Returns the total number of iterations in the chain.
+    pub fn check_chain(chain: &[VDFProof]) -> Result<u64, vdf::ChainError> {
+        if chain.is_empty() {
+            return Err(vdf::ChainError::Empty);
+        }
+        let mut iterations: u64 = 0;
+        for (index, link) in chain.iter().enumerate() {
+            if index > 0 {
+                let previous = &chain[index - 1];
+                if link.modulus != previous.modulus {
+                    return Err(vdf::ChainError::ModulusChanged(index));
+                }
+                if link.generator != previous.output.result {
+                    return Err(vdf::ChainError::BaseMismatch(index));
+                }
+            }
+            if link.output.iterations == 0 {
+                return Err(vdf::ChainError::NoProgress(index));
+            }
+            if !link.verify() {
+                return Err(vdf::ChainError::InvalidProof(index));
+            }
+            iterations += u64::from(link.output.iterations);
         }
-        let r = TWO.pow_mod(&Int::from(self.output.iterations), &self.cap);
-        self.output.result
-            == (self.pi.pow_mod(&self.cap, &self.modulus)
-                * self.generator.pow_mod(&r, &self.modulus))
-                % &self.modulus
+        Ok(iterations)
+    }
+
+    /// Whether the proofs form a valid chain, see check_chain
+    pub fn verify_chain(chain: &[VDFProof]) -> bool {
+        Self::check_chain(chain).is_ok()
     }
 
     /// Helper function for calculating the difference in iterations between two
@@ -220,3 +1004,134 @@ impl VDFProof {
         }
     }
 }
+
+/// Verifies a proof by squaring from its bare values, without building a
+/// VDFProof. See verify_raw_with_exponent.
+pub fn verify_raw<I: PolInt>(
+    modulus: &I,
+    base: &I,
+    result: &I,
+    iterations: u64,
+    cap: &I,
+    proof: &I,
+) -> Result<(), vdf::VerifyError> {
+    verify_raw_with_exponent(
+        modulus,
+        base,
+        result,
+        iterations,
+        DEFAULT_EXPONENT,
+        cap,
+        proof,
+    )
+}
+
+/// Verifies a proof of iterating x ← x^exponent from its bare values. Checks
+/// that the values are in the group and not degenerate, that the cap is
+/// prime and that pi^cap · base^(exponent^T mod cap) = result. Works with
+/// any bigint backend, see vdf::bigint.
+pub fn verify_raw_with_exponent<I: PolInt>(
+    modulus: &I,
+    base: &I,
+    result: &I,
+    iterations: u64,
+    exponent: u32,
+    cap: &I,
+    proof: &I,
+) -> Result<(), vdf::VerifyError> {
+    if exponent < 2 {
+        return Err(vdf::VerifyError::DegenerateValue("exponent"));
+    }
+    check_values(modulus, base, result, iterations, cap, proof)?;
+    if !cap.is_probable_prime() {
+        return Err(vdf::VerifyError::CapNotPrime);
+    }
+    let remainder =
+        I::from_u64(u64::from(exponent)).pow_mod(&I::from_u64(iterations), cap);
+    let expected = proof
+        .pow_mod(cap, modulus)
+        .mul_mod(&base.pow_mod(&remainder, modulus), modulus);
+    if *result == expected {
+        Ok(())
+    } else {
+        Err(vdf::VerifyError::EquationMismatch)
+    }
+}
+
+/// Checks the values of a proof without the primality of the cap
+fn check_values<I: PolInt>(
+    modulus: &I,
+    base: &I,
+    result: &I,
+    iterations: u64,
+    cap: &I,
+    proof: &I,
+) -> Result<(), vdf::VerifyError> {
+    let one = I::from_u64(1);
+    if *modulus < I::from_u64(3) {
+        return Err(vdf::VerifyError::DegenerateValue("modulus"));
+    }
+    if iterations == 0 {
+        return Err(vdf::VerifyError::DegenerateValue("iterations"));
+    }
+    if *proof < I::from_u64(0) || proof >= modulus {
+        return Err(vdf::VerifyError::ProofOutOfRange);
+    }
+    // Values outside the group don't belong in it, and 0, 1 and -1
+    // satisfy the equation for too many choices of the other values
+    let minus_one = modulus.sub(&one);
+    let values = [("base", base), ("result", result), ("proof", proof)];
+    for (name, value) in values.iter() {
+        if **value <= one || **value >= minus_one {
+            return Err(vdf::VerifyError::DegenerateValue(*name));
+        }
+    }
+    if *cap < I::from_u64(2) || cap.bit_length() < MIN_CAP_BITS {
+        return Err(vdf::VerifyError::DegenerateValue("cap"));
+    }
+    // A composite cap lets the prover fake the proof
+    if cap.is_even() {
+        return Err(vdf::VerifyError::CapNotPrime);
+    }
+    Ok(())
+}
+
+/// Verifies each proof on its own, returning the same results as calling
+/// verify on each of them. Nothing is amortized over the batch, see
+/// verify_batch_parallel for spreading it over the cores.
+///
+/// The equations aren't combined with random weights: -1 is in every group
+/// mod N, so negating the outputs of two proofs cancels out in the weighted
+/// product, and batching over the signed residues, away from -1, would accept
+/// the negated outputs verify rejects.
+pub fn verify_batch(proofs: &[VDFProof]) -> Vec<bool> {
+    proofs.iter().map(VDFProof::verify).collect()
+}
+
+/// Batches smaller than this are verified on the calling thread by
+/// verify_batch_parallel, as handing a few proofs to the pool costs about as
+/// much as it saves
+#[cfg(feature = "parallel")]
+pub const PARALLEL_BATCH_THRESHOLD: usize = 8;
+
+/// Verifies each proof on its own in rayon's thread pool, returning the same
+/// results as calling verify on each of them
+#[cfg(feature = "parallel")]
+pub fn verify_batch_parallel(proofs: &[VDFProof]) -> Vec<bool> {
+    check_batch_parallel(proofs)
+        .iter()
+        .map(Result::is_ok)
+        .collect()
+}
+
+/// Like verify_batch_parallel, but says why each invalid proof doesn't
+/// verify, as check does
+#[cfg(feature = "parallel")]
+pub fn check_batch_parallel(
+    proofs: &[VDFProof],
+) -> Vec<Result<(), vdf::VerifyError>> {
+    if proofs.len() < PARALLEL_BATCH_THRESHOLD {
+        return proofs.iter().map(VDFProof::check).collect();
+    }
+    proofs.par_iter().map(VDFProof::check).collect()
+}
@@ -0,0 +1,102 @@
+//! Proptest strategies for honest proofs and for tampering with them
+
+use crate::primality;
+use crate::util;
+use crate::vdf::evaluation::VDF;
+use crate::vdf::proof::{ProofType, VDFProof};
+use crate::vdf::ModulusChoice;
+use proptest::prelude::*;
+use ramp::Int;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::convert::TryFrom;
+
+/// Safe primes of exactly bits bits, each generated from a random seed
+pub fn safe_prime(bits: usize) -> impl Strategy<Value = Int> {
+    any::<u64>().prop_map(move |seed| {
+        primality::gen_safe_prime(bits, &mut StdRng::seed_from_u64(seed))
+    })
+}
+
+/// Evaluates the VDF from a seeded generator in the RSA-2048 group and proves
+/// it against the cap
+pub fn prove(seed: &[u8], iterations: u32, cap: Int) -> VDFProof {
+    let modulus = ModulusChoice::Rsa2048;
+    let generator =
+        util::hash_with_domain(util::SEED_DOMAIN, seed, modulus.int());
+    let (_, receiver) =
+        VDF::new(modulus, generator, iterations, ProofType::Sequential)
+            .unwrap()
+            .with_cap(cap)
+            .unwrap()
+            .run_vdf_worker();
+    receiver.recv().unwrap().unwrap()
+}
+
+/// Honest proofs of 1 to max_iterations iterations against safe prime caps
+/// of cap_bits bits
+pub fn honest_proof(
+    max_iterations: u32,
+    cap_bits: usize,
+) -> impl Strategy<Value = VDFProof> {
+    (any::<u64>(), 1..=max_iterations, safe_prime(cap_bits)).prop_map(
+        |(seed, iterations, cap)| prove(&seed.to_be_bytes(), iterations, cap),
+    )
+}
+
+/// The fields of a proof that the verification equation depends on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProofField {
+    Modulus,
+    Generator,
+    Result,
+    Iterations,
+    Exponent,
+    Cap,
+    Pi,
+}
+
+pub const PROOF_FIELDS: [ProofField; 7] = [
+    ProofField::Modulus,
+    ProofField::Generator,
+    ProofField::Result,
+    ProofField::Iterations,
+    ProofField::Exponent,
+    ProofField::Cap,
+    ProofField::Pi,
+];
+
+pub fn proof_field() -> impl Strategy<Value = ProofField> {
+    proptest::sample::select(PROOF_FIELDS.to_vec())
+}
+
+/// The proof with delta added to the field
+pub fn mutate_proof(
+    proof: &VDFProof,
+    field: ProofField,
+    delta: i64,
+) -> VDFProof {
+    let mut mutated = proof.clone();
+    let delta_int = Int::from(delta);
+    match field {
+        ProofField::Modulus => mutated.modulus = &proof.modulus + delta_int,
+        ProofField::Generator => {
+            mutated.generator = &proof.generator + delta_int
+        }
+        ProofField::Result => {
+            mutated.output.result = &proof.output.result + delta_int
+        }
+        ProofField::Iterations => {
+            mutated.output.iterations =
+                u32::try_from(i64::from(proof.output.iterations) + delta)
+                    .expect("The iterations stay within a u32")
+        }
+        ProofField::Exponent => {
+            mutated.exponent = u32::try_from(i64::from(proof.exponent) + delta)
+                .expect("The exponent stays within a u32")
+        }
+        ProofField::Cap => mutated.cap = &proof.cap + delta_int,
+        ProofField::Pi => mutated.pi = &proof.pi + delta_int,
+    }
+    mutated
+}
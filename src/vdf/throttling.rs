@@ -0,0 +1,136 @@
+//! Detects a squaring rate that falls below the calibrated one mid-run, as
+//! happens when a laptop or a shared VM throttles the CPU. A throttled run
+//! takes longer for the same iterations and inflates the latency measured.
+
+use crate::vdf::evaluation::{ProgressReport, VdfObserver};
+use std::time;
+
+/// Fraction of the baseline rate below which the run counts as slowed down
+pub const DEFAULT_THRESHOLD: f64 = 0.8;
+/// How long the rate must stay below the threshold to be reported
+pub const DEFAULT_WINDOW: time::Duration = time::Duration::from_millis(500);
+
+/// The rate stayed below the threshold for longer than the window
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateAnomaly {
+    /// Iterations done when the anomaly was detected
+    pub iterations: u32,
+    /// Time into the run the rate first fell below the threshold
+    pub since: time::Duration,
+    /// Iterations per second at detection
+    pub rate: f64,
+    /// The rate over the baseline rate
+    pub ratio: f64,
+}
+
+/// Compares the iteration rate between progress reports against the
+/// baseline measured on calibration
+#[derive(Debug, Clone)]
+pub struct ThrottlingDetector {
+    baseline: f64,
+    threshold: f64,
+    window: time::Duration,
+    last: Option<(u32, time::Duration)>,
+    below_since: Option<time::Duration>,
+    slowest_ratio: f64,
+    anomaly: Option<RateAnomaly>,
+}
+
+impl ThrottlingDetector {
+    /// A detector for a baseline of iterations per second
+    pub fn new(baseline: f64) -> Self {
+        assert!(baseline > 0.0, "The baseline rate must be positive");
+        Self {
+            baseline,
+            threshold: DEFAULT_THRESHOLD,
+            window: DEFAULT_WINDOW,
+            last: None,
+            below_since: None,
+            slowest_ratio: 1.0,
+            anomaly: None,
+        }
+    }
+
+    /// Sets the fraction of the baseline rate that counts as slowed down
+    pub fn with_threshold(mut self, threshold: f64) -> Self {
+        assert!(
+            threshold > 0.0 && threshold <= 1.0,
+            "The threshold must be in (0, 1]"
+        );
+        self.threshold = threshold;
+        self
+    }
+
+    /// Sets how long the rate must stay below the threshold
+    pub fn with_window(mut self, window: time::Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Feeds a progress report to the detector. Returns the anomaly once,
+    /// when the rate has been below the threshold for longer than the window.
+    pub fn observe(&mut self, report: &ProgressReport) -> Option<RateAnomaly> {
+        let (iterations, elapsed) =
+            match self.last.replace((report.iterations, report.elapsed)) {
+                Some(last) => last,
+                None => return None,
+            };
+        let seconds = report.elapsed.checked_sub(elapsed)?.as_secs_f64();
+        if seconds <= 0.0 {
+            return None;
+        }
+        let rate = f64::from(report.iterations - iterations) / seconds;
+        let ratio = rate / self.baseline;
+        if ratio >= self.threshold {
+            self.below_since = None;
+            return None;
+        }
+
+        if ratio < self.slowest_ratio {
+            self.slowest_ratio = ratio;
+        }
+        let since = *self.below_since.get_or_insert(elapsed);
+        if self.anomaly.is_some() || report.elapsed - since <= self.window {
+            return None;
+        }
+        let anomaly = RateAnomaly {
+            iterations: report.iterations,
+            since,
+            rate,
+            ratio,
+        };
+        warn!(
+            iterations = report.iterations,
+            ratio, "iteration rate below the calibrated one"
+        );
+        self.anomaly = Some(anomaly.clone());
+        Some(anomaly)
+    }
+
+    /// The first anomaly detected, if any
+    pub fn anomaly(&self) -> Option<&RateAnomaly> {
+        self.anomaly.as_ref()
+    }
+
+    /// Whether throttling is suspected for the run
+    pub fn throttling_suspected(&self) -> bool {
+        self.anomaly.is_some()
+    }
+
+    /// How much to widen the uncertainty of a latency estimate by: the
+    /// baseline rate over the slowest rate seen below the threshold, or 1
+    /// when the run wasn't throttled
+    pub fn uncertainty_factor(&self) -> f64 {
+        if self.throttling_suspected() {
+            1.0 / self.slowest_ratio
+        } else {
+            1.0
+        }
+    }
+}
+
+impl VdfObserver for ThrottlingDetector {
+    fn on_progress(&mut self, report: &ProgressReport) {
+        self.observe(report);
+    }
+}
@@ -0,0 +1,257 @@
+//! Where the VDF worker delivers its progress and proof, either to the one
+//! caller of VDF::run_vdf_worker or to every subscriber of a VdfWorkerHandle
+
+use crate::metrics::{MetricsSink, SUBSCRIBER_DROPS};
+use crate::vdf::evaluation::ProgressReport;
+use crate::vdf::proof::VDFProof;
+use crate::vdf::{self, VdfError};
+use crossbeam::channel::{RecvError, Sender};
+use ramp::Int;
+use std::any::Any;
+use std::collections::VecDeque;
+use std::iter;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+/// Messages a subscriber can fall behind by before the oldest are dropped
+pub const DEFAULT_SUBSCRIBER_CAPACITY: usize = 1024;
+
+/// A message from a broadcasting worker. Finished is the last one sent.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WorkerMessage {
+    Progress(ProgressReport),
+    Finished(Result<VDFProof, VdfError>),
+}
+
+/// Outbox is what the worker thread sends through
+pub(crate) trait Outbox: Send + 'static {
+    fn progress(&self, _report: &ProgressReport) {}
+
+    fn finish(&self, result: Result<VDFProof, VdfError>);
+}
+
+/// The single consumer of run_vdf_worker, who only hears of the proof and
+/// rejected caps. Failed proofs close the channel.
+impl Outbox for Sender<Result<VDFProof, vdf::InvalidCapError>> {
+    fn finish(&self, result: Result<VDFProof, VdfError>) {
+        let message = match result {
+            Ok(proof) => Ok(proof),
+            Err(VdfError::InvalidCap) => Err(vdf::InvalidCapError),
+            Err(_) => return,
+        };
+        if self.send(message).is_err() {
+            error!("Couldn't send the proof to the worker listener!");
+        }
+    }
+}
+
+/// The message a panic was raised with
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    match payload.downcast_ref::<&str>() {
+        Some(message) => message.to_string(),
+        None => match payload.downcast_ref::<String>() {
+            Some(message) => message.clone(),
+            None => "Unknown panic".to_string(),
+        },
+    }
+}
+
+#[derive(Debug, Default)]
+struct Queue {
+    messages: VecDeque<WorkerMessage>,
+    closed: bool,
+    dropped: u64,
+}
+
+type SharedQueue = Arc<(Mutex<Queue>, Condvar)>;
+
+/// The messages of a VdfWorkerHandle for one subscriber. At most capacity
+/// messages wait in it, and each message beyond that drops the oldest one.
+/// Finished is never dropped, as nothing is sent after it.
+#[derive(Debug)]
+pub struct Subscription {
+    queue: SharedQueue,
+}
+
+impl Subscription {
+    /// Blocks for the next message. Fails once the worker has finished and
+    /// every message has been received.
+    pub fn recv(&self) -> Result<WorkerMessage, RecvError> {
+        let (queue, available) = &*self.queue;
+        let mut queue = queue.lock().unwrap();
+        loop {
+            if let Some(message) = queue.messages.pop_front() {
+                return Ok(message);
+            }
+            if queue.closed {
+                return Err(RecvError);
+            }
+            queue = available.wait(queue).unwrap();
+        }
+    }
+
+    /// The next message if one is waiting
+    pub fn try_recv(&self) -> Option<WorkerMessage> {
+        self.queue.0.lock().unwrap().messages.pop_front()
+    }
+
+    /// Blocks for each message until the worker has finished
+    pub fn iter(&self) -> impl Iterator<Item = WorkerMessage> + '_ {
+        iter::from_fn(move || self.recv().ok())
+    }
+
+    /// Messages waiting to be received
+    pub fn len(&self) -> usize {
+        self.queue.0.lock().unwrap().messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Messages dropped because the subscriber fell behind
+    pub fn dropped(&self) -> u64 {
+        self.queue.0.lock().unwrap().dropped
+    }
+}
+
+#[derive(Debug)]
+struct Subscribers {
+    queues: Vec<SharedQueue>,
+    capacity: usize,
+    finished: Option<WorkerMessage>,
+}
+
+/// Sends a copy of each message to every subscriber. Dropped subscribers are
+/// forgotten on the next send.
+#[derive(Debug, Clone)]
+pub(crate) struct Broadcast {
+    subscribers: Arc<Mutex<Subscribers>>,
+    metrics: Option<Arc<dyn MetricsSink>>,
+}
+
+impl Broadcast {
+    /// Keeps up to capacity messages for each subscriber, counting the ones
+    /// dropped past that to the sink
+    pub(crate) fn new(
+        capacity: usize,
+        metrics: Option<Arc<dyn MetricsSink>>,
+    ) -> Self {
+        assert!(capacity > 0, "Subscribers must hold at least one message");
+        Self {
+            subscribers: Arc::new(Mutex::new(Subscribers {
+                queues: Vec::new(),
+                capacity,
+                finished: None,
+            })),
+            metrics,
+        }
+    }
+
+    /// A new subscriber, which only gets the last message if the worker has
+    /// already finished
+    pub(crate) fn subscribe(&self) -> Subscription {
+        let queue = SharedQueue::default();
+        let mut subscribers = self.subscribers.lock().unwrap();
+        match subscribers.finished.as_ref() {
+            Some(message) => {
+                let mut waiting = queue.0.lock().unwrap();
+                waiting.messages.push_back(message.clone());
+                waiting.closed = true;
+            }
+            None => subscribers.queues.push(queue.clone()),
+        }
+        Subscription { queue }
+    }
+
+    fn send(&self, message: WorkerMessage, close: bool) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        let capacity = subscribers.capacity;
+        let mut dropped = 0;
+        subscribers.queues.retain(|shared| {
+            // Only the broadcast holds the queue once the subscriber is gone
+            if Arc::strong_count(shared) == 1 {
+                return false;
+            }
+            let (queue, available) = &**shared;
+            let mut queue = queue.lock().unwrap();
+            if queue.messages.len() >= capacity {
+                queue.messages.pop_front();
+                queue.dropped += 1;
+                dropped += 1;
+            }
+            queue.messages.push_back(message.clone());
+            queue.closed = close;
+            available.notify_all();
+            true
+        });
+        if let Some(metrics) = self.metrics.as_ref() {
+            for _ in 0..dropped {
+                metrics.incr_counter(SUBSCRIBER_DROPS, &[]);
+            }
+        }
+    }
+}
+
+impl Outbox for Broadcast {
+    fn progress(&self, report: &ProgressReport) {
+        self.send(WorkerMessage::Progress(report.clone()), false);
+    }
+
+    fn finish(&self, result: Result<VDFProof, VdfError>) {
+        let message = WorkerMessage::Finished(result);
+        self.send(message.clone(), true);
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.queues.clear();
+        subscribers.finished = Some(message);
+    }
+}
+
+/// A running worker from VDF::spawn_worker, which any number of consumers
+/// can watch
+#[derive(Debug)]
+pub struct VdfWorkerHandle {
+    capper: Sender<Int>,
+    outbox: Broadcast,
+    thread: thread::JoinHandle<()>,
+}
+
+impl VdfWorkerHandle {
+    pub(crate) fn new(
+        capper: Sender<Int>,
+        outbox: Broadcast,
+        thread: thread::JoinHandle<()>,
+    ) -> Self {
+        Self {
+            capper,
+            outbox,
+            thread,
+        }
+    }
+
+    /// Stops the evaluation at the next iteration and proves against cap.
+    /// False if the worker has already stopped.
+    pub fn send_cap(&self, cap: Int) -> bool {
+        self.capper.send(cap).is_ok()
+    }
+
+    /// Progress reports from now on and the final result
+    pub fn subscribe(&self) -> Subscription {
+        self.outbox.subscribe()
+    }
+
+    /// Waits for the worker to exit and returns its final result
+    pub fn join(self) -> Result<VDFProof, VdfError> {
+        let joined = self.thread.join();
+        let finished = self.outbox.subscribers.lock().unwrap().finished.take();
+        match (finished, joined) {
+            (Some(WorkerMessage::Finished(result)), _) => result,
+            (_, Err(payload)) => Err(VdfError::WorkerPanicked {
+                message: panic_message(payload.as_ref()),
+            }),
+            (_, Ok(())) => Err(VdfError::WorkerPanicked {
+                message: "The worker exited without a result".to_string(),
+            }),
+        }
+    }
+}
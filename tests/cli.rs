@@ -0,0 +1,181 @@
+use assert_cmd::Command;
+use proof_of_latency::vdf::proof::DeserializableVDFProof;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// 2^127 - 1
+const CAP: &str = "7fffffffffffffffffffffffffffffff";
+
+fn temp_file(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("pol-cli-{}-{}", std::process::id(), name))
+}
+
+fn prove(out: &Path, cap: &str) -> assert_cmd::assert::Assert {
+    Command::cargo_bin("pol")
+        .unwrap()
+        .args(&["prove", "--upper-bound", "200", "--cap", cap, "--out"])
+        .arg(out)
+        .assert()
+}
+
+fn verify(proof: &Path) -> assert_cmd::assert::Assert {
+    Command::cargo_bin("pol")
+        .unwrap()
+        .arg("verify")
+        .arg(proof)
+        .assert()
+}
+
+#[test]
+fn prove_then_verify() {
+    let out = temp_file("valid.json");
+    prove(&out, CAP).success();
+    verify(&out).success();
+    fs::remove_file(out).unwrap();
+}
+
+#[test]
+fn rejects_a_tampered_proof() {
+    let out = temp_file("tampered.json");
+    prove(&out, CAP).success();
+
+    let mut proof: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&out).unwrap()).unwrap();
+    proof["output"]["iterations"] = serde_json::json!(201);
+    fs::write(&out, proof.to_string()).unwrap();
+
+    verify(&out).code(1);
+    fs::remove_file(out).unwrap();
+}
+
+#[test]
+fn verifies_pasted_proof_strings() {
+    let out = temp_file("pasted.json");
+    prove(&out, CAP).success();
+    let proof: DeserializableVDFProof =
+        serde_json::from_str(&fs::read_to_string(&out).unwrap()).unwrap();
+    let proof = proof.serialize();
+    fs::remove_file(out).unwrap();
+
+    let verify_string = |proof: &str| {
+        Command::cargo_bin("pol")
+            .unwrap()
+            .args(&["verify", proof])
+            .assert()
+    };
+    verify_string(&format!("{:#}", proof)).success();
+    verify_string(&proof.to_hex()).success();
+
+    let mut tampered = proof.clone();
+    tampered.output.iterations += 1;
+    verify_string(&tampered.to_hex()).code(1);
+
+    let mut malformed = format!("{:#}", proof);
+    malformed.push('!');
+    let stderr = verify_string(&malformed)
+        .code(4)
+        .get_output()
+        .stderr
+        .clone();
+    assert!(String::from_utf8(stderr)
+        .unwrap()
+        .contains("Invalid base64 at character"));
+}
+
+#[test]
+fn failures_have_distinct_exit_codes() {
+    Command::cargo_bin("pol")
+        .unwrap()
+        .args(&["prove", "--upper-bound", "10"])
+        .assert()
+        .code(2);
+
+    verify(&temp_file("missing.json")).code(3);
+
+    let garbage = temp_file("garbage.json");
+    fs::write(&garbage, "not a proof").unwrap();
+    verify(&garbage).code(4);
+    fs::remove_file(garbage).unwrap();
+
+    // 2^127 + 1 is divisible by 3
+    prove(
+        &temp_file("composite.json"),
+        "80000000000000000000000000000001",
+    )
+    .code(5);
+}
+
+#[test]
+fn flags_override_the_config_file() {
+    let config = temp_file("pol.toml");
+    fs::write(&config, "upper_bound = 100\ncap_bits = 32").unwrap();
+    let out = temp_file("configured.json");
+
+    Command::cargo_bin("pol")
+        .unwrap()
+        .arg("--config")
+        .arg(&config)
+        .args(&["prove", "--cap-bits", "48", "--out"])
+        .arg(&out)
+        .assert()
+        .success();
+    let proof: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&out).unwrap()).unwrap();
+    assert_eq!(proof["output"]["iterations"], 100);
+    let cap = proof["cap"].as_str().unwrap().parse::<u64>().unwrap();
+    assert_eq!(64 - cap.leading_zeros(), 48);
+
+    fs::write(&config, "upper_bound = 0").unwrap();
+    Command::cargo_bin("pol")
+        .unwrap()
+        .arg("--config")
+        .arg(&config)
+        .args(&["prove", "--out"])
+        .arg(&out)
+        .assert()
+        .code(6);
+
+    fs::remove_file(config).unwrap();
+    fs::remove_file(out).unwrap();
+}
+
+#[test]
+fn hash_flag_changes_the_generator() {
+    let generator = |hash: &str| {
+        let out = temp_file(&format!("{}.json", hash));
+        Command::cargo_bin("pol")
+            .unwrap()
+            .args(&["--hash", hash, "prove", "--upper-bound", "50", "--cap"])
+            .arg(CAP)
+            .arg("--out")
+            .arg(&out)
+            .assert()
+            .success();
+        let proof: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&out).unwrap()).unwrap();
+        verify(&out).success();
+        fs::remove_file(out).unwrap();
+        proof["generator"].as_str().unwrap().to_string()
+    };
+    assert_ne!(generator("sha3-512"), generator("blake3"));
+
+    Command::cargo_bin("pol")
+        .unwrap()
+        .args(&["--hash", "md5", "prove", "--upper-bound", "10"])
+        .assert()
+        .code(6);
+}
+
+#[test]
+fn calibrate_reports_a_rate() {
+    let output = Command::cargo_bin("pol")
+        .unwrap()
+        .args(&["calibrate", "--ms", "50"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("squarings per second"));
+}
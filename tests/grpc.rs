@@ -0,0 +1,119 @@
+#![cfg(feature = "grpc")]
+
+use proof_of_latency::grpc::proto::proof_service_client::ProofServiceClient;
+use proof_of_latency::grpc::proto::{ProofBatch, ProofBlob, ProveRequest};
+use proof_of_latency::grpc::{ProofService, ServiceConfig};
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::transport::{Channel, Server};
+use tonic::Code;
+
+// 2^127 - 1
+const CAP: &str = "7fffffffffffffffffffffffffffffff";
+
+/// Serves on an ephemeral port and connects a client to it
+async fn client(config: ServiceConfig) -> ProofServiceClient<Channel> {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(
+        Server::builder()
+            .add_service(ProofService::new(config).into_server())
+            .serve_with_incoming(TcpListenerStream::new(listener)),
+    );
+    ProofServiceClient::connect(format!("http://{}", address))
+        .await
+        .unwrap()
+}
+
+fn prove_request(cap: &str) -> ProveRequest {
+    ProveRequest {
+        upper_bound: 200,
+        cap: cap.to_string(),
+    }
+}
+
+#[tokio::test]
+async fn round_trips_a_proof() {
+    let mut client = client(ServiceConfig::default()).await;
+    let proof = client.prove(prove_request(CAP)).await.unwrap().into_inner();
+
+    let reply = client.verify(proof.clone()).await.unwrap().into_inner();
+    assert!(reply.valid);
+    assert_eq!(reply.iterations, 200);
+    assert!(reply.error.is_empty());
+
+    // A flipped bit in pi, the last Int before the proof type and the
+    // certificate flag
+    let mut tampered = proof;
+    let len = tampered.proof.len();
+    tampered.proof[len - 3] ^= 1;
+    let reply = client.verify(tampered).await.unwrap().into_inner();
+    assert!(!reply.valid);
+    assert!(!reply.error.is_empty());
+}
+
+#[tokio::test]
+async fn verifies_batches_in_order() {
+    let mut client = client(ServiceConfig::default()).await;
+    let proof = client.prove(prove_request(CAP)).await.unwrap().into_inner();
+    let mut tampered = proof.proof.clone();
+    let len = tampered.len();
+    tampered[len - 3] ^= 1;
+
+    let mut proofs = vec![proof.proof; 12];
+    proofs[3] = tampered;
+    proofs[7] = b"not a proof".to_vec();
+    let replies = client
+        .verify_batch(ProofBatch { proofs })
+        .await
+        .unwrap()
+        .into_inner()
+        .replies;
+    assert_eq!(replies.len(), 12);
+    for (index, reply) in replies.iter().enumerate() {
+        assert_eq!(reply.valid, index != 3 && index != 7, "{}", index);
+        assert_eq!(reply.error.is_empty(), reply.valid);
+    }
+    assert_eq!(replies[3].iterations, 200);
+    assert_eq!(replies[7].iterations, 0);
+}
+
+#[tokio::test]
+async fn rejects_oversized_and_malformed_requests() {
+    let mut client = client(ServiceConfig {
+        max_concurrent_proofs: 1,
+        max_request_bytes: 64,
+    })
+    .await;
+
+    let oversized = ProofBlob { proof: vec![1; 65] };
+    let status = client.verify(oversized).await.unwrap_err();
+    assert_eq!(status.code(), Code::ResourceExhausted);
+
+    let malformed = ProofBlob {
+        proof: b"not a proof".to_vec(),
+    };
+    let status = client.verify(malformed).await.unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    let status = client.prove(prove_request("xyz")).await.unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    // 2^127 + 1 is divisible by 3
+    let status = client
+        .prove(prove_request("80000000000000000000000000000001"))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn limits_concurrent_proofs() {
+    let mut client = client(ServiceConfig {
+        max_concurrent_proofs: 0,
+        ..ServiceConfig::default()
+    })
+    .await;
+    let status = client.prove(prove_request(CAP)).await.unwrap_err();
+    assert_eq!(status.code(), Code::ResourceExhausted);
+}
@@ -0,0 +1,125 @@
+#![cfg(feature = "http")]
+
+use async_std::io::{ReadExt, WriteExt};
+use async_std::net::TcpStream;
+use async_std::task;
+use proof_of_latency::http::{self, HttpConfig};
+use proof_of_latency::util;
+use proof_of_latency::vdf::evaluation::VDF;
+use proof_of_latency::vdf::proof::ProofType;
+use proof_of_latency::vdf::ModulusChoice;
+use tide::listener::Listener;
+
+// 2^127 - 1
+const CAP: &str = "7fffffffffffffffffffffffffffffff";
+
+/// Serves on an ephemeral port and returns its address
+async fn serve(config: HttpConfig) -> String {
+    let mut listener =
+        http::app(config, 12345).bind("127.0.0.1:0").await.unwrap();
+    let address = listener.info()[0]
+        .connection()
+        .trim_start_matches("http://")
+        .to_string();
+    task::spawn(async move { listener.accept().await });
+    address
+}
+
+async fn request(
+    address: &str,
+    head: &str,
+    body: &[u8],
+) -> (u16, serde_json::Value) {
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    let head = format!(
+        "{}\r\nHost: localhost\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n",
+        head,
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await.unwrap();
+    stream.write_all(body).await.unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    let status = response[9..12].parse().unwrap();
+    let (_, body) = response.split_at(response.find("\r\n\r\n").unwrap() + 4);
+    (status, serde_json::from_str(body).unwrap())
+}
+
+fn proof_json() -> serde_json::Value {
+    let modulus = ModulusChoice::Rsa2048;
+    let generator =
+        util::hash_with_domain(util::SEED_DOMAIN, b"http", modulus.int());
+    let (_, receiver) =
+        VDF::new(modulus, generator, 200, ProofType::Sequential)
+            .unwrap()
+            .with_cap(util::int_from_hex(CAP).unwrap())
+            .unwrap()
+            .run_vdf_worker();
+    let proof = receiver.recv().unwrap().unwrap();
+    serde_json::to_value(&proof.deserialize()).unwrap()
+}
+
+#[test]
+fn verifies_proofs() {
+    task::block_on(async {
+        let address = serve(HttpConfig::default()).await;
+        let mut proof = proof_json();
+
+        let (status, reply) = request(
+            &address,
+            "POST /verify HTTP/1.1",
+            proof.to_string().as_bytes(),
+        )
+        .await;
+        assert_eq!(status, 200);
+        assert_eq!(reply["valid"], true);
+        assert_eq!(reply["iterations"], 200);
+
+        proof["output"]["iterations"] = serde_json::json!(201);
+        let (status, reply) = request(
+            &address,
+            "POST /verify HTTP/1.1",
+            proof.to_string().as_bytes(),
+        )
+        .await;
+        assert_eq!(status, 200);
+        assert_eq!(reply["valid"], false);
+        assert_eq!(
+            reply["reason"],
+            "The proof doesn't satisfy the verification equation"
+        );
+    });
+}
+
+#[test]
+fn rejects_bad_requests() {
+    task::block_on(async {
+        let address = serve(HttpConfig { max_body_bytes: 64 }).await;
+
+        let (status, reply) =
+            request(&address, "POST /verify HTTP/1.1", b"{\"modulus\": ").await;
+        assert_eq!(status, 400);
+        assert!(reply["reason"]
+            .as_str()
+            .unwrap()
+            .contains("EOF while parsing"));
+
+        let (status, reply) =
+            request(&address, "POST /verify HTTP/1.1", &[b' '; 65]).await;
+        assert_eq!(status, 413);
+        assert_eq!(reply["valid"], false);
+    });
+}
+
+#[test]
+fn reports_the_calibration() {
+    task::block_on(async {
+        let address = serve(HttpConfig::default()).await;
+        let (status, reply) =
+            request(&address, "GET /calibration HTTP/1.1", b"").await;
+        assert_eq!(status, 200);
+        assert_eq!(reply["squarings_per_second"], 12345);
+    });
+}
@@ -0,0 +1,130 @@
+//! Deterministic fuzzing of the parsers that read bytes from peers, for when
+//! cargo-fuzz and a nightly toolchain aren't at hand. The targets in fuzz/
+//! run the same checks on whatever libFuzzer comes up with.
+
+use proof_of_latency::primality::{CertificateStep, PrimeCertificate};
+use proof_of_latency::vdf::evaluation::VDFResult;
+use proof_of_latency::vdf::proof::{
+    DeserializableVDFProof, ProofType, VDFProof,
+};
+use proof_of_latency::vdf::DecodeError;
+use ramp::Int;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// A proof with small Ints and a certificate, so that every part of the
+/// encoding is covered and each input decodes quickly. It doesn't verify.
+fn seed_proof() -> VDFProof {
+    let mut proof = VDFProof::new(
+        &Int::from(1_000_003u64 * 1_000_033),
+        &Int::from(5),
+        &VDFResult::new(Int::from(123_456_789), 100),
+        &Int::from(1_000_000_007),
+        &ProofType::Sequential,
+    )
+    .unwrap();
+    proof.pi = Int::from(987_654_321);
+    proof.cap_certificate = Some(PrimeCertificate {
+        steps: vec![CertificateStep {
+            prime: Int::from(23),
+            factors: vec![Int::from(2), Int::from(11)],
+            witnesses: vec![Int::from(5), Int::from(5)],
+        }],
+    });
+    proof
+}
+
+/// Whatever the bytes, decoding returns, and anything decoded survives a
+/// round trip
+fn check_bytes(bytes: &[u8]) {
+    if let Ok(proof) = VDFProof::from_bytes(bytes) {
+        let _ = proof.fingerprint();
+        assert_eq!(VDFProof::from_bytes(&proof.to_bytes()), Ok(proof));
+    }
+}
+
+fn check_json(json: &[u8]) {
+    if let Ok(proof) = serde_json::from_slice::<DeserializableVDFProof>(json) {
+        if let Ok(proof) = proof.try_serialize() {
+            let _ = proof.to_bytes();
+        }
+    }
+}
+
+/// Derives an input from the seed with one of the usual fuzzing mutations
+fn mutate(rng: &mut StdRng, seed: &[u8]) -> Vec<u8> {
+    let mut bytes = seed.to_vec();
+    match rng.gen_range(0, 5) {
+        0 => {
+            for _ in 0..rng.gen_range(1, 5) {
+                let bit = rng.gen_range(0, bytes.len() * 8);
+                bytes[bit / 8] ^= 1 << (bit % 8);
+            }
+        }
+        1 => bytes.truncate(rng.gen_range(0, seed.len())),
+        2 => {
+            // Mostly hits the length prefixes and counts
+            let offset = rng.gen_range(0, bytes.len() - 4);
+            let value = if rng.gen() { u32::MAX } else { rng.gen() };
+            bytes[offset..offset + 4].copy_from_slice(&value.to_be_bytes());
+        }
+        3 => {
+            let offset = rng.gen_range(0, bytes.len());
+            let mut inserted = vec![0u8; rng.gen_range(1, 16)];
+            rng.fill(&mut inserted[..]);
+            bytes.splice(offset..offset, inserted);
+        }
+        _ => {
+            bytes = vec![0u8; rng.gen_range(1, 2 * seed.len())];
+            rng.fill(&mut bytes[..]);
+            bytes[0] = seed[0];
+        }
+    }
+    bytes
+}
+
+#[test]
+fn decoding_arbitrary_bytes_never_panics() {
+    let mut rng = StdRng::seed_from_u64(580);
+    let seed = seed_proof().to_bytes();
+    check_bytes(&seed);
+    for _ in 0..200_000 {
+        check_bytes(&mutate(&mut rng, &seed));
+    }
+}
+
+#[test]
+fn parsing_arbitrary_json_never_panics() {
+    let mut rng = StdRng::seed_from_u64(580);
+    let seed = serde_json::to_vec(&seed_proof().deserialize()).unwrap();
+    check_json(&seed);
+    for _ in 0..50_000 {
+        check_json(&mutate(&mut rng, &seed));
+    }
+}
+
+#[test]
+fn forged_counts_are_rejected_before_allocating() {
+    let mut proof = seed_proof();
+    proof.cap_certificate = None;
+    let mut bytes = proof.to_bytes();
+
+    // A certificate of u32::MAX steps in four bytes
+    *bytes.last_mut().unwrap() = 1;
+    bytes.extend_from_slice(&u32::MAX.to_be_bytes());
+    assert_eq!(
+        VDFProof::from_bytes(&bytes),
+        Err(DecodeError::Truncated(bytes.len()))
+    );
+
+    // A step of u32::MAX factors
+    bytes.truncate(bytes.len() - 4);
+    bytes.extend_from_slice(&1u32.to_be_bytes());
+    bytes.extend_from_slice(&[0, 0, 0, 1, 23]);
+    bytes.extend_from_slice(&u32::MAX.to_be_bytes());
+    bytes.extend_from_slice(&[0; 8]);
+    assert_eq!(
+        VDFProof::from_bytes(&bytes),
+        Err(DecodeError::Truncated(bytes.len()))
+    );
+}
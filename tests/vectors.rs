@@ -0,0 +1,88 @@
+use proof_of_latency::util;
+use proof_of_latency::vdf::evaluation::VDF;
+use proof_of_latency::vdf::proof::ProofType;
+use proof_of_latency::vdf::{ModulusChoice, ProofScheme};
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+/// A golden vector, as written by gen-vectors
+#[derive(Debug, Deserialize)]
+struct Vector {
+    name: String,
+    scheme: ProofScheme,
+    backend: String,
+    modulus: ModulusChoice,
+    base: String,
+    iterations: u32,
+    cap: Option<String>,
+    result: String,
+    proof: Vec<String>,
+}
+
+fn vectors() -> Vec<Vector> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/vectors");
+    let mut paths: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().map_or(false, |ext| ext == "json"))
+        .collect();
+    paths.sort();
+    paths
+        .iter()
+        .map(|path| {
+            serde_json::from_str(&fs::read_to_string(path).unwrap())
+                .unwrap_or_else(|error| {
+                    panic!("{} is malformed: {}", path.display(), error)
+                })
+        })
+        .collect()
+}
+
+/// Recomputes the result and the proof of the vector, in hex
+fn recompute(vector: &Vector) -> (String, Vec<String>) {
+    let mut vdf = VDF::new(
+        vector.modulus.clone(),
+        util::int_from_hex(&vector.base).unwrap(),
+        vector.iterations,
+        ProofType::Sequential,
+    )
+    .unwrap();
+    let (result, proof) = match vector.scheme {
+        ProofScheme::Wesolowski => {
+            let cap = util::int_from_hex(vector.cap.as_ref().unwrap()).unwrap();
+            let proof = vdf.with_cap(cap).unwrap().run_sync().unwrap();
+            assert!(proof.verify(), "{} doesn't verify", vector.name);
+            (proof.output.result.clone(), vec![proof.pi])
+        }
+        ProofScheme::Pietrzak => {
+            let result = vdf.evaluate().result.clone();
+            let proof = vdf.pietrzak_proof().unwrap();
+            assert!(proof.verify(), "{} doesn't verify", vector.name);
+            (result, proof.midpoints)
+        }
+    };
+    (
+        util::int_to_hex(&result),
+        proof.iter().map(util::int_to_hex).collect(),
+    )
+}
+
+#[test]
+fn vectors_match_the_implementation() {
+    let vectors = vectors();
+    for scheme in [ProofScheme::Wesolowski, ProofScheme::Pietrzak].iter() {
+        assert!(
+            vectors.iter().any(|vector| vector.scheme == *scheme),
+            "No vectors for {:?}",
+            scheme
+        );
+    }
+
+    for vector in vectors.iter() {
+        assert_eq!(vector.backend, "ramp", "{}", vector.name);
+        let (result, proof) = recompute(vector);
+        assert_eq!(result, vector.result, "{}: result", vector.name);
+        assert_eq!(proof, vector.proof, "{}: proof", vector.name);
+    }
+}